@@ -59,4 +59,12 @@ pub struct BurnFromAction {
     pub owner: HumanAddr,
     pub amount: Uint128,
     pub memo: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct SetAllowanceAction {
+    pub spender: HumanAddr,
+    pub amount: Uint128,
+    pub expiration: Option<u64>,
+}
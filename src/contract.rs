@@ -1,11 +1,12 @@
-use cosmwasm_std::{Api, Binary, Env, Extern, HandleResponse, HumanAddr, InitResponse, Querier, QueryResult, StdError, StdResult, Storage};
+use cosmwasm_std::{log, Api, Binary, Env, Extern, HandleResponse, HumanAddr, InitResponse, MigrateResponse, Querier, QueryResult, StdError, StdResult, Storage, Uint128};
 use hermit_toolkit::permit::{Permit, TokenPermissions, validate};
-use crate::handle::{add_minters, change_admin, remove_minters, revoke_permit, set_contract_status, set_minters, try_batch_burn_from, try_batch_mint, try_batch_send, try_batch_send_from, try_batch_transfer, try_batch_transfer_from, try_burn, try_burn_from, try_create_key, try_decrease_allowance, try_deposit, try_increase_allowance, try_mint, try_redeem, try_register_receive, try_send, try_send_from, try_set_key, try_transfer, try_transfer_from};
-use crate::msg::{ContractStatusLevel, HandleMsg, InitMsg, QueryMsg, QueryWithPermit, space_pad};
-use crate::query::{query_allowance, query_balance, query_contract_status, query_exchange_rate, query_minters, query_token_config, query_token_info, query_transactions, query_transfers, viewing_keys_queries};
-use crate::state::{Balances, Config, Constants, ReadonlyConfig};
+use crate::handle::{accept_admin, add_fee_exemptions, add_minters, add_to_allowlist, add_vesting_schedule, cancel_admin_transfer, change_admin, create_vesting, force_transfer, freeze_account, grant_role, remove_fee_exemptions, remove_from_allowlist, remove_minters, renounce_admin, revoke_all_permits, revoke_permit, revoke_role, revoke_vesting, set_contract_status, set_fee_config, set_max_supply, set_minter_cap, set_minters, set_permit_scope, set_token_metadata, try_approve_with_permit, try_batch_burn_from, try_batch_mint, try_batch_send, try_batch_send_from, try_batch_set_allowance, try_batch_transfer, try_batch_transfer_from, try_burn, try_burn_from, try_cancel_stream, try_claim_vested, try_create_escrow, try_create_key, try_create_mandate, try_create_stream, try_decrease_allowance, try_deposit, try_execute_mandate, try_increase_allowance, try_lock_htlc, try_mint, try_redeem, try_redeem_htlc, try_refund_htlc, try_register_receive, try_revoke_all_allowances, try_revoke_mandate, try_send, try_send_from, try_set_allowance, try_set_key, try_settle_escrow, try_transfer, try_transfer_from, try_transfer_with_permit, try_withdraw_from_stream, update_config};
+use crate::migrate::{migrate_storage, CURRENT_SCHEMA_VERSION};
+use crate::msg::{ContractStatusLevel, HandleMsg, InitMsg, Logo, MigrateMsg, NativeDenom, PermitScope, QueryMsg, QueryWithPermit, Role, space_pad, TokenMetadata};
+use crate::query::{query_admin, query_allowance, query_allowances, query_allowlist, query_balance, query_config_changes, query_contract_status, query_escrows, query_exchange_rate, query_fee_config, query_htlc, query_is_frozen, query_mandate, query_max_supply, query_minter_cap, query_minters, query_permit_nonce, query_revoked_permits, query_roles, query_streams, query_token_config, query_token_info, query_token_metadata, query_transactions, query_transfers, query_vesting_schedule, viewing_keys_queries};
+use crate::state::{add_allowlisted_account, Balances, check_permit_tag, Config, Constants, read_permit_scope, ReadonlyConfig};
 use crate::tools::rand::sha_256;
-use crate::transaction_history::store_mint;
+use crate::transaction_history::{get_latest_tx_time, store_mint, store_vesting_create};
 
 /// We make sure that responses from `handle` are padded to a multiple of this size.
 pub const RESPONSE_BLOCK_SIZE: usize = 256;
@@ -32,6 +33,8 @@ pub fn init<S: Storage, A: Api, Q: Querier> (
     }
 
     let init_config = msg.config();
+    let native_denoms = init_config.native_denoms()?;
+    validate_native_denoms(&native_denoms)?;
     let admin = msg.admin.unwrap_or(env.message.sender);
     let canon_admin = deps.api.canonical_address(&admin)?;
 
@@ -43,6 +46,9 @@ pub fn init<S: Storage, A: Api, Q: Querier> (
             let amount = balance.amount.u128();
             let mut balances = Balances::from_storage(&mut deps.storage);
             balances.set_account_balance(&balance_address, amount);
+            if init_config.allowlist_enabled() {
+                add_allowlisted_account(&mut deps.storage, &balance_address)?;
+            }
             if let Some(new_total_supply) = total_supply.checked_add(amount) {
                 total_supply = new_total_supply;
             } else {
@@ -62,6 +68,53 @@ pub fn init<S: Storage, A: Api, Q: Querier> (
         }
     }
 
+    {
+        // Vesting allocations count towards the total supply but stay locked until claimed
+        let initial_vesting = msg.initial_vesting.unwrap_or_default();
+        for vesting in initial_vesting {
+            if !vesting.terms.is_valid() {
+                return Err(StdError::generic_err(
+                    "Vesting must start no later than its cliff, which must be no later than its end",
+                ));
+            }
+            if vesting.amount.is_zero() {
+                return Err(StdError::generic_err("Cannot vest zero tokens"));
+            }
+            let vesting_address = deps.api.canonical_address(&vesting.address)?;
+            let amount = vesting.amount.u128();
+            // Beneficiaries have to be able to claim, just like holders of initial balances
+            if init_config.allowlist_enabled() {
+                add_allowlisted_account(&mut deps.storage, &vesting_address)?;
+            }
+            add_vesting_schedule(
+                &mut deps.storage,
+                &vesting_address,
+                &canon_admin,
+                amount,
+                vesting.terms,
+            )?;
+            store_vesting_create(
+                &mut deps.storage,
+                &vesting_address,
+                &canon_admin,
+                Uint128(amount),
+                msg.symbol.clone(),
+                Some("Initial Vesting".to_string()),
+                &env.block,
+            )?;
+            if let Some(new_total_supply) = total_supply.checked_add(amount) {
+                total_supply = new_total_supply;
+            } else {
+                return Err(StdError::generic_err(
+                    "The sum of all initial balances exceeds the maximum possible total supply",
+                ));
+            }
+        }
+    }
+
+    let max_supply = init_config.max_supply();
+    check_max_supply(max_supply, total_supply)?;
+
     let prng_seed_hashed = sha_256(&msg.prng_seed.0);
 
     let mut config = Config::from_storage(&mut deps.storage);
@@ -69,17 +122,23 @@ pub fn init<S: Storage, A: Api, Q: Querier> (
         name: msg.name,
         symbol: msg.symbol,
         decimals: msg.decimals,
-        admin: admin.clone(),
+        admin: Some(admin.clone()),
         prng_seed: prng_seed_hashed.to_vec(),
         total_supply_is_public: init_config.public_total_supply(),
         deposit_is_enabled: init_config.deposit_enabled(),
         redeem_is_enabled: init_config.redeem_enabled(),
         mint_is_enabled: init_config.mint_enabled(),
         burn_is_enabled: init_config.burn_enabled(),
+        native_denoms,
         contract_address: env.contract.address,
     })?;
     config.set_total_supply(total_supply);
+    config.set_max_supply(max_supply)?;
+    config.set_max_supply_can_increase(init_config.max_supply_increase_enabled())?;
+    config.set_allowlist_enabled(init_config.allowlist_enabled())?;
+    config.set_force_transfer_enabled(init_config.force_transfer_enabled())?;
     config.set_contract_status(ContractStatusLevel::NormalRun);
+    config.set_schema_version(CURRENT_SCHEMA_VERSION)?;
     let minters = if init_config.mint_enabled() {
         Vec::from([admin])
     } else {
@@ -90,6 +149,23 @@ pub fn init<S: Storage, A: Api, Q: Querier> (
     Ok(InitResponse::default())
 }
 
+pub fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+    msg: MigrateMsg,
+) -> StdResult<MigrateResponse> {
+    let from_version = migrate_storage(&mut deps.storage, &msg)?;
+
+    Ok(MigrateResponse {
+        messages: vec![],
+        log: vec![
+            log("migrated_from", from_version),
+            log("migrated_to", CURRENT_SCHEMA_VERSION),
+        ],
+        data: None,
+    })
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -101,10 +177,10 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         ContractStatusLevel::StopAll | ContractStatusLevel::StopAllButRedeems => {
             let response = match msg {
                 HandleMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, level),
-                HandleMsg::Redeem { amount, .. }
+                HandleMsg::Redeem { amount, denom, .. }
                 if contract_status == ContractStatusLevel::StopAllButRedeems =>
                     {
-                        try_redeem(deps, env, amount)
+                        try_redeem(deps, env, amount, denom)
                     }
                 _ => Err(StdError::generic_err(
                     "This contract is stopped and this action is not allowed",
@@ -115,10 +191,13 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         ContractStatusLevel::NormalRun => {} // If it's a normal run just continue
     }
 
+    // Permit scopes expire by the time of the latest handled block
+    Config::from_storage(&mut deps.storage).set_block_time(env.block.time)?;
+
     let response = match msg {
         // Native
         HandleMsg::Deposit { .. } => try_deposit(deps, env),
-        HandleMsg::Redeem { amount, .. } => try_redeem(deps, env, amount),
+        HandleMsg::Redeem { amount, denom, .. } => try_redeem(deps, env, amount, denom),
 
         // Base
         HandleMsg::Transfer {
@@ -138,6 +217,50 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
         HandleMsg::BatchTransfer { actions, .. } => try_batch_transfer(deps, env, actions),
         HandleMsg::BatchSend { actions, .. } => try_batch_send(deps, env, actions),
         HandleMsg::Burn { amount, memo, .. } => try_burn(deps, env, amount, memo),
+        HandleMsg::ClaimVested { .. } => try_claim_vested(deps, env),
+        HandleMsg::CreateEscrow {
+            recipient,
+            arbiter,
+            amount,
+            expires_at,
+            memo,
+            ..
+        } => try_create_escrow(deps, env, recipient, arbiter, amount, expires_at, memo),
+        HandleMsg::ReleaseEscrow {
+            escrow_id,
+            memo,
+            ..
+        } => try_settle_escrow(deps, env, escrow_id, true, memo),
+        HandleMsg::RefundEscrow {
+            escrow_id,
+            memo,
+            ..
+        } => try_settle_escrow(deps, env, escrow_id, false, memo),
+        HandleMsg::LockHtlc {
+            recipient,
+            amount,
+            hashlock,
+            expires_at,
+            ..
+        } => try_lock_htlc(deps, env, recipient, amount, hashlock, expires_at),
+        HandleMsg::RedeemHtlc {
+            htlc_id,
+            preimage,
+            ..
+        } => try_redeem_htlc(deps, env, htlc_id, preimage),
+        HandleMsg::RefundHtlc { htlc_id, .. } => try_refund_htlc(deps, env, htlc_id),
+        HandleMsg::CreateStream {
+            recipient,
+            rate_per_second,
+            start,
+            end,
+            deposit,
+            ..
+        } => try_create_stream(deps, env, recipient, rate_per_second, start, end, deposit),
+        HandleMsg::WithdrawFromStream { stream_id, .. } => {
+            try_withdraw_from_stream(deps, env, stream_id)
+        }
+        HandleMsg::CancelStream { stream_id, .. } => try_cancel_stream(deps, env, stream_id),
         HandleMsg::RegisterReceive { code_hash, .. } => try_register_receive(deps, env, code_hash),
         HandleMsg::CreateViewingKey { entropy, .. } => try_create_key(deps, env, entropy),
         HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, key),
@@ -147,14 +270,40 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
             spender,
             amount,
             expiration,
+            period_limit,
             ..
-        } => try_increase_allowance(deps, env, spender, amount, expiration),
+        } => try_increase_allowance(deps, env, spender, amount, expiration, period_limit),
         HandleMsg::DecreaseAllowance {
             spender,
             amount,
             expiration,
             ..
         } => try_decrease_allowance(deps, env, spender, amount, expiration),
+        HandleMsg::SetAllowance {
+            spender,
+            amount,
+            expiration,
+            ..
+        } => try_set_allowance(deps, env, spender, amount, expiration),
+        HandleMsg::BatchSetAllowance { actions, .. } => try_batch_set_allowance(deps, env, actions),
+        HandleMsg::RevokeAllAllowances { spenders, .. } => {
+            try_revoke_all_allowances(deps, env, spenders.unwrap_or_default())
+        }
+        HandleMsg::CreateMandate {
+            payee,
+            amount,
+            period,
+            max_count,
+            first_due,
+            expiration,
+            ..
+        } => try_create_mandate(
+            deps, env, payee, amount, period, max_count, first_due, expiration,
+        ),
+        HandleMsg::ExecuteMandate { payer, payee, .. } => {
+            try_execute_mandate(deps, env, payer, payee)
+        }
+        HandleMsg::RevokeMandate { payee, .. } => try_revoke_mandate(deps, env, payee),
         HandleMsg::TransferFrom {
             owner,
             recipient,
@@ -203,11 +352,88 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 
         // Other
         HandleMsg::ChangeAdmin { address, .. } => change_admin(deps, env, address),
+        HandleMsg::ForceTransfer {
+            from,
+            to,
+            amount,
+            reason,
+            ..
+        } => force_transfer(deps, env, from, to, amount, reason),
+        HandleMsg::CreateVesting {
+            recipient,
+            amount,
+            terms,
+            ..
+        } => create_vesting(deps, env, recipient, amount, terms),
+        HandleMsg::RevokeVesting { account, index, .. } => revoke_vesting(deps, env, account, index),
+        HandleMsg::AcceptAdmin { .. } => accept_admin(deps, env),
+        HandleMsg::CancelAdminTransfer { .. } => cancel_admin_transfer(deps, env),
+        HandleMsg::RenounceAdmin { .. } => renounce_admin(deps, env),
+        HandleMsg::GrantRole { role, address, .. } => grant_role(deps, env, role, address),
+        HandleMsg::RevokeRole { role, address, .. } => revoke_role(deps, env, role, address),
         HandleMsg::SetContractStatus { level, .. } => set_contract_status(deps, env, level),
         HandleMsg::AddMinters { minters, .. } => add_minters(deps, env, minters),
         HandleMsg::RemoveMinters { minters, .. } => remove_minters(deps, env, minters),
         HandleMsg::SetMinters { minters, .. } => set_minters(deps, env, minters),
+        HandleMsg::SetMinterCap {
+            minter,
+            lifetime_cap,
+            window_cap,
+            window_seconds,
+            ..
+        } => set_minter_cap(deps, env, minter, lifetime_cap, window_cap, window_seconds),
+        HandleMsg::SetMaxSupply { max_supply, .. } => set_max_supply(deps, env, max_supply),
+        HandleMsg::AddToAllowlist { addresses, .. } => add_to_allowlist(deps, env, addresses),
+        HandleMsg::RemoveFromAllowlist { addresses, .. } => {
+            remove_from_allowlist(deps, env, addresses)
+        }
+        HandleMsg::FreezeAccount { address, reason, .. } => {
+            freeze_account(deps, env, address, true, reason)
+        }
+        HandleMsg::UnfreezeAccount { address, reason, .. } => {
+            freeze_account(deps, env, address, false, reason)
+        }
+        HandleMsg::SetFeeConfig {
+            bps,
+            min,
+            max,
+            collector,
+            ..
+        } => set_fee_config(deps, env, bps, min, max, collector),
+        HandleMsg::AddFeeExemptions { addresses, .. } => add_fee_exemptions(deps, env, addresses),
+        HandleMsg::RemoveFeeExemptions { addresses, .. } => {
+            remove_fee_exemptions(deps, env, addresses)
+        }
+        HandleMsg::SetTokenMetadata { name, metadata, .. } => {
+            set_token_metadata(deps, env, name, metadata)
+        }
+        HandleMsg::UpdateConfig {
+            public_total_supply,
+            enable_deposit,
+            enable_redeem,
+            enable_mint,
+            enable_burn,
+            lock,
+            ..
+        } => update_config(
+            deps,
+            env,
+            public_total_supply,
+            enable_deposit,
+            enable_redeem,
+            enable_mint,
+            enable_burn,
+            lock.unwrap_or_default(),
+        ),
         HandleMsg::RevokePermit { permit_name, .. } => revoke_permit(deps, env, permit_name),
+        HandleMsg::SetPermitScope {
+            permit_name,
+            scope,
+            ..
+        } => set_permit_scope(deps, env, permit_name, scope),
+        HandleMsg::RevokeAllPermits { .. } => revoke_all_permits(deps, env),
+        HandleMsg::TransferWithPermit { permit, .. } => try_transfer_with_permit(deps, env, permit),
+        HandleMsg::ApproveWithPermit { permit, .. } => try_approve_with_permit(deps, env, permit),
     };
 
     pad_response(response)
@@ -216,10 +442,24 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
 pub fn query<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, msg: QueryMsg) -> QueryResult {
     match msg {
         QueryMsg::TokenInfo {} => query_token_info(&deps.storage),
+        QueryMsg::TokenMetadata {} => query_token_metadata(&deps.storage),
         QueryMsg::TokenConfig {} => query_token_config(&deps.storage),
+        QueryMsg::ConfigChanges { page, page_size } => {
+            query_config_changes(&deps.storage, page.unwrap_or(0), page_size)
+        }
         QueryMsg::ContractStatus {} => query_contract_status(&deps.storage),
         QueryMsg::ExchangeRate {} => query_exchange_rate(&deps.storage),
         QueryMsg::Minters { .. } => query_minters(deps),
+        QueryMsg::MinterCap { minter } => query_minter_cap(&deps.storage, minter),
+        QueryMsg::MaxSupply {} => query_max_supply(&deps.storage),
+        QueryMsg::FeeConfig {} => query_fee_config(deps),
+        QueryMsg::Htlc { htlc_id } => query_htlc(&deps.storage, htlc_id),
+        QueryMsg::IsFrozen { address } => query_is_frozen(deps, &address),
+        QueryMsg::Allowlist { page, page_size } => {
+            query_allowlist(deps, page.unwrap_or(0), page_size)
+        }
+        QueryMsg::Admin {} => query_admin(&deps.storage),
+        QueryMsg::Roles {} => query_roles(&deps.storage),
         QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
         _ => viewing_keys_queries(deps, msg),
     }
@@ -241,6 +481,10 @@ fn permit_queries<S: Storage, A: Api, Q: Querier>(
         token_address,
         None,
     )?);
+    let permit_name = &permit.params.permit_name;
+    check_permit_tag(&deps.storage, &account, permit_name)?;
+    let scope = read_permit_scope(&deps.storage, &account, permit_name)?;
+    check_scope_expiry(deps, &account, &scope, permit_name)?;
 
     // Permit validated! We can now execute the query.
     match query {
@@ -254,6 +498,63 @@ fn permit_queries<S: Storage, A: Api, Q: Querier>(
 
             query_balance(deps, &account)
         }
+        QueryWithPermit::PermitNonce {} => {
+            if !permit.check_permission(&TokenPermissions::History) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query history, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            check_no_history_range(&scope, permit_name, "permit nonce")?;
+            query_permit_nonce(deps, &account)
+        }
+        QueryWithPermit::RevokedPermits { page, page_size } => {
+            if !permit.check_permission(&TokenPermissions::Owner) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query revoked permits, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_revoked_permits(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::VestingSchedule { time } => {
+            if !permit.check_permission(&TokenPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query balance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            query_vesting_schedule(deps, &account, time)
+        }
+        QueryWithPermit::Escrows { page, page_size } => {
+            if !permit.check_permission(&TokenPermissions::History) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query history, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            check_no_history_range(&scope, permit_name, "escrows")?;
+            query_escrows(deps, &account, page.unwrap_or(0), page_size)
+        }
+        QueryWithPermit::Streams {
+            page,
+            page_size,
+            time,
+        } => {
+            if !permit.check_permission(&TokenPermissions::History) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query history, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            check_no_history_range(&scope, permit_name, "streams")?;
+            query_streams(deps, &account, page.unwrap_or(0), page_size, time)
+        }
         QueryWithPermit::TransferHistory { page, page_size } => {
             if !permit.check_permission(&TokenPermissions::History) {
                 return Err(StdError::generic_err(format!(
@@ -262,7 +563,13 @@ fn permit_queries<S: Storage, A: Api, Q: Querier>(
                 )));
             }
 
-            query_transfers(deps, &account, page.unwrap_or(0), page_size)
+            query_transfers(
+                deps,
+                &account,
+                page.unwrap_or(0),
+                page_size,
+                scope.history.as_ref(),
+            )
         }
         QueryWithPermit::TransactionHistory { page, page_size } => {
             if !permit.check_permission(&TokenPermissions::History) {
@@ -272,7 +579,79 @@ fn permit_queries<S: Storage, A: Api, Q: Querier>(
                 )));
             }
 
-            query_transactions(deps, &account, page.unwrap_or(0), page_size)
+            query_transactions(
+                deps,
+                &account,
+                page.unwrap_or(0),
+                page_size,
+                scope.history.as_ref(),
+            )
+        }
+        QueryWithPermit::Mandate { payer, payee } => {
+            if !permit.check_permission(&TokenPermissions::Allowance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query allowance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            if account != payer && account != payee {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query mandate. Requires permit for either payer {:?} or payee {:?}, got permit for {:?}",
+                    payer.as_str(), payee.as_str(), account.as_str()
+                )));
+            }
+
+            let counterparty = if account == payer { &payee } else { &payer };
+            check_counterparty(&scope, permit_name, counterparty)?;
+
+            query_mandate(deps, payer, payee)
+        }
+        QueryWithPermit::AllowancesGiven {
+            owner,
+            page,
+            page_size,
+            time,
+        } => {
+            if !permit.check_permission(&TokenPermissions::Allowance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query allowance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            if account != owner {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query allowances. Requires permit for owner {:?}, got permit for {:?}",
+                    owner.as_str(), account.as_str()
+                )));
+            }
+
+            check_no_counterparties(&scope, permit_name)?;
+            query_allowances(deps, owner, true, page.unwrap_or(0), page_size, time)
+        }
+        QueryWithPermit::AllowancesReceived {
+            spender,
+            page,
+            page_size,
+            time,
+        } => {
+            if !permit.check_permission(&TokenPermissions::Allowance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query allowance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            if account != spender {
+                return Err(StdError::generic_err(format!(
+                    "Cannot query allowances. Requires permit for spender {:?}, got permit for {:?}",
+                    spender.as_str(), account.as_str()
+                )));
+            }
+
+            check_no_counterparties(&scope, permit_name)?;
+            query_allowances(deps, spender, false, page.unwrap_or(0), page_size, time)
         }
         QueryWithPermit::Allowance { owner, spender } => {
             if !permit.check_permission(&TokenPermissions::Allowance) {
@@ -289,14 +668,83 @@ fn permit_queries<S: Storage, A: Api, Q: Querier>(
                 )));
             }
 
+            let counterparty = if account == owner { &spender } else { &owner };
+            check_counterparty(&scope, permit_name, counterparty)?;
+
             query_allowance(deps, owner, spender)
         }
     }
 }
 
+/// Queries can't see the block time, so a scope expires once the contract handled a transaction at
+/// or after `expires_at`, by any account. The account's own latest transaction counts as well, for
+/// the ones not written by `handle`, such as initial balances.
+pub(crate) fn check_scope_expiry<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    scope: &PermitScope,
+    permit_name: &str,
+) -> StdResult<()> {
+    if let Some(expires_at) = scope.expires_at {
+        let address = deps.api.canonical_address(account)?;
+        let latest_tx_time = get_latest_tx_time(&deps.storage, &address)?.unwrap_or_default();
+        let block_time = ReadonlyConfig::from_storage(&deps.storage).block_time();
+        if block_time.max(latest_tx_time) >= expires_at {
+            return Err(StdError::generic_err(format!(
+                "Permit {:?} expired at {}",
+                permit_name, expires_at
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Queries that can't be filtered by a history range are off limits to permits scoped to one
+pub(crate) fn check_no_history_range(
+    scope: &PermitScope,
+    permit_name: &str,
+    what: &str,
+) -> StdResult<()> {
+    if scope.history.is_some() {
+        return Err(StdError::generic_err(format!(
+            "Permit {:?} is limited to a history range, so it can't query {}",
+            permit_name, what
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_counterparty(
+    scope: &PermitScope,
+    permit_name: &str,
+    counterparty: &HumanAddr,
+) -> StdResult<()> {
+    match &scope.counterparties {
+        Some(counterparties) if !counterparties.contains(counterparty) => {
+            Err(StdError::generic_err(format!(
+                "Permit {:?} doesn't cover allowances with {:?}",
+                permit_name,
+                counterparty.as_str()
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Listing allowances would reveal counterparties a scoped permit doesn't cover
+pub(crate) fn check_no_counterparties(scope: &PermitScope, permit_name: &str) -> StdResult<()> {
+    if scope.counterparties.is_some() {
+        return Err(StdError::generic_err(format!(
+            "Permit {:?} is limited to allowances with specific counterparties, so it can't list allowances",
+            permit_name
+        )));
+    }
+    Ok(())
+}
+
 fn is_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> StdResult<bool> {
     let consts = config.constants()?;
-    if &consts.admin != account {
+    if consts.admin.as_ref() != Some(account) {
         return Ok(false);
     }
 
@@ -313,7 +761,91 @@ pub fn check_if_admin<S: Storage>(config: &Config<S>, account: &HumanAddr) -> St
     Ok(())
 }
 
-fn is_valid_name(name: &str) -> bool {
+/// Passes if `account` is the admin or holds `role`
+pub fn check_role<S: Storage>(config: &Config<S>, role: Role, account: &HumanAddr) -> StdResult<()> {
+    if is_admin(config, account)? || config.role_members(role).contains(account) {
+        return Ok(());
+    }
+
+    Err(StdError::generic_err(format!(
+        "This command requires the {} role. Admin commands can only be run from admin address or an address holding that role",
+        role.as_str()
+    )))
+}
+
+pub fn check_max_supply(max_supply: Option<u128>, total_supply: u128) -> StdResult<()> {
+    match max_supply {
+        Some(max_supply) if total_supply > max_supply => Err(StdError::generic_err(format!(
+            "This would increase the total supply to {} above the max supply of {}",
+            total_supply, max_supply
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub const MAX_METADATA_TEXT_LEN: usize = 1024;
+pub const MAX_METADATA_URL_LEN: usize = 256;
+pub const MAX_EMBEDDED_LOGO_SIZE: usize = 5 * 1024;
+
+pub fn validate_token_metadata(metadata: &TokenMetadata) -> StdResult<()> {
+    let too_long = |field: &str, max: usize| {
+        StdError::generic_err(format!("The {} must not exceed {} bytes", field, max))
+    };
+
+    if let Some(description) = &metadata.description {
+        if description.len() > MAX_METADATA_TEXT_LEN {
+            return Err(too_long("description", MAX_METADATA_TEXT_LEN));
+        }
+    }
+    if let Some(project_url) = &metadata.project_url {
+        if project_url.len() > MAX_METADATA_URL_LEN {
+            return Err(too_long("project URL", MAX_METADATA_URL_LEN));
+        }
+    }
+    match &metadata.logo {
+        Some(Logo::Url(url)) if url.len() > MAX_METADATA_URL_LEN => {
+            return Err(too_long("logo URL", MAX_METADATA_URL_LEN));
+        }
+        Some(Logo::Embedded(logo)) if logo.as_slice().len() > MAX_EMBEDDED_LOGO_SIZE => {
+            return Err(too_long("embedded logo", MAX_EMBEDDED_LOGO_SIZE));
+        }
+        _ => {}
+    }
+    if let Some(contact) = &metadata.marketing_contact {
+        if contact.len() > MAX_METADATA_URL_LEN {
+            return Err(too_long("marketing contact", MAX_METADATA_URL_LEN));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn validate_native_denoms(native_denoms: &[NativeDenom]) -> StdResult<()> {
+    if native_denoms.is_empty() {
+        return Err(StdError::generic_err("At least one native denom must be configured"));
+    }
+    for (i, native) in native_denoms.iter().enumerate() {
+        if native.denom.is_empty() {
+            return Err(StdError::generic_err("Native denom must not be empty"));
+        }
+        if !native.weight.is_valid() {
+            return Err(StdError::generic_err(format!(
+                "Weight of {} must be non-zero on both sides",
+                native.denom
+            )));
+        }
+        if native_denoms[..i].iter().any(|other| other.denom == native.denom) {
+            return Err(StdError::generic_err(format!(
+                "Native denom {} is configured more than once",
+                native.denom
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn is_valid_name(name: &str) -> bool {
     let len = name.len();
     (3..=30).contains(&len)
 }
@@ -1,14 +1,16 @@
 
 use cosmwasm_std::{Api, BankMsg, Binary, CanonicalAddr, Coin, CosmosMsg, Env, Extern, HandleResponse, HumanAddr, log, Querier, ReadonlyStorage, StdError, StdResult, Storage, to_binary, Uint128};
-use hermit_toolkit::permit::RevokedPermits;
+use hermit_toolkit::permit::{pubkey_to_account, PermitSignature, RevokedPermits};
+use serde::Serialize;
 use crate::batch;
-use crate::contract::{check_if_admin, PREFIX_REVOKED_PERMITS};
-use crate::msg::{ContractStatusLevel, HandleAnswer};
+use crate::contract::{check_if_admin, check_max_supply, check_role, is_valid_name, validate_token_metadata, PREFIX_REVOKED_PERMITS};
+use crate::msg::{AllowanceInfo, ApprovePermit, ApprovePermitParams, ConfigChange, ContractStatusLevel, EscrowStatus, Feature, HtlcStatus, HandleAnswer, PermitScope, Role, SpendingLimit, TokenMetadata, TransferPermit, TransferPermitParams, VestingTerms};
 use crate::msg::ResponseStatus::Success;
 use crate::receiver::Hmip20ReceiveMsg;
-use crate::state::{Balances, Config, get_receiver_hash, read_allowance, ReadonlyConfig, set_receiver_hash, write_allowance, write_viewing_key};
+use crate::state::{ALLOWANCES_GIVEN, add_allowlisted_account, add_escrow, add_htlc, add_stream, Allowance, append_config_change, append_revoked_permit_name, Balances, check_permit_tag, Config, Escrow, FeeConfig, get_allowance_counterparties, get_receiver_hash, Htlc, is_allowlisted, is_frozen, Mandate, PeriodLimit, read_allowance, read_escrow, read_htlc, read_mandate, read_minter_cap, read_permit_nonce, read_permit_tag, read_stream, read_vesting_schedules, ReadonlyConfig, remove_allowlisted_account, remove_mandate, set_frozen, set_receiver_hash, Stream, VestingSchedule, write_allowance, write_escrow, write_htlc, write_mandate, write_minter_cap, write_permit_nonce, write_permit_scope, write_permit_tag, write_stream, write_vesting_schedules, write_viewing_key};
+use crate::tools::rand::{Prng, sha_256};
 use crate::tools::viewing_key::ViewingKey;
-use crate::transaction_history::{store_burn, store_deposit, store_mint, store_redeem, store_transfer};
+use crate::transaction_history::{store_burn, store_deposit, store_escrow_create, store_escrow_settle, store_fee, store_forced_transfer, store_freeze, store_htlc_lock, store_htlc_settle, store_mint, store_redeem, store_stream_cancel, store_stream_payment, store_transfer, store_vesting_claim, store_vesting_create, store_vesting_revoke};
 
 
 
@@ -16,33 +18,46 @@ pub fn try_deposit<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
 ) -> StdResult<HandleResponse> {
-    let mut amount = Uint128::zero();
+    let mut config = Config::from_storage(&mut deps.storage);
+    let constants = config.constants()?;
+    if !constants.deposit_is_enabled {
+        return Err(StdError::generic_err(
+            "Deposit functionality is not enabled for this token.",
+        ));
+    }
+
+    let mut raw_amount: u128 = 0;
+    let mut deposited: Vec<Coin> = vec![];
 
     for coin in &env.message.sent_funds {
-        if coin.denom == "ughm" {
-            amount = coin.amount
-        } else {
-            return Err(StdError::generic_err(
-                "Tried to deposit an unsupported token",
-            ));
+        let native = constants.native_denom(&coin.denom).ok_or_else(|| {
+            StdError::generic_err("Tried to deposit an unsupported token")
+        })?;
+        if coin.amount.is_zero() {
+            continue;
         }
+        let minted = native
+            .weight
+            .native_to_token(coin.amount.u128())
+            .ok_or_else(|| {
+                StdError::generic_err(format!(
+                    "Deposit of {}{} does not convert to a whole number of tokens",
+                    coin.amount, coin.denom
+                ))
+            })?;
+        raw_amount = raw_amount.checked_add(minted).ok_or_else(|| {
+            StdError::generic_err("This deposit would overflow the currency's total supply")
+        })?;
+        deposited.push(coin.clone());
     }
 
-    if amount.is_zero() {
+    if deposited.is_empty() {
         return Err(StdError::generic_err("No funds were sent to be deposited"));
     }
 
-    let raw_amount = amount.u128();
-
-    let mut config = Config::from_storage(&mut deps.storage);
-    let constants = config.constants()?;
-    if !constants.deposit_is_enabled {
-        return Err(StdError::generic_err(
-            "Deposit functionality is not enabled for this token.",
-        ));
-    }
     let total_supply = config.total_supply();
     if let Some(total_supply) = total_supply.checked_add(raw_amount) {
+        check_max_supply(config.max_supply(), total_supply)?;
         config.set_total_supply(total_supply);
     } else {
         return Err(StdError::generic_err(
@@ -51,6 +66,8 @@ pub fn try_deposit<S: Storage, A: Api, Q: Querier>(
     }
 
     let sender_address = deps.api.canonical_address(&env.message.sender)?;
+    check_not_frozen(&deps.storage, &sender_address, "sender")?;
+    check_allowlisted(&deps.storage, &sender_address)?;
 
     let mut balances = Balances::from_storage(&mut deps.storage);
     let account_balance = balances.balance(&sender_address);
@@ -62,13 +79,15 @@ pub fn try_deposit<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
-    store_deposit(
-        &mut deps.storage,
-        &sender_address,
-        amount,
-        "ughm".to_string(),
-        &env.block,
-    )?;
+    for coin in deposited {
+        store_deposit(
+            &mut deps.storage,
+            &sender_address,
+            coin.amount,
+            coin.denom,
+            &env.block,
+        )?;
+    }
 
     let res = HandleResponse {
         messages: vec![],
@@ -83,6 +102,7 @@ pub fn try_redeem<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     amount: Uint128,
+    denom: Option<String>,
 ) -> StdResult<HandleResponse> {
     let config = ReadonlyConfig::from_storage(&deps.storage);
     let constants = config.constants()?;
@@ -92,9 +112,29 @@ pub fn try_redeem<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
+    // Redeem pays out in the first configured denom unless another one is requested
+    let native = match &denom {
+        Some(denom) => constants.native_denom(denom).ok_or_else(|| {
+            StdError::generic_err(format!("Tried to redeem for an unsupported token: {}", denom))
+        })?,
+        None => constants
+            .native_denoms
+            .first()
+            .ok_or_else(|| StdError::generic_err("No native denoms are configured"))?,
+    }
+    .clone();
+
     let sender_address = deps.api.canonical_address(&env.message.sender)?;
+    check_not_frozen(&deps.storage, &sender_address, "sender")?;
     let amount_raw = amount.u128();
 
+    let native_amount = native.weight.token_to_native(amount_raw).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "Redeem of {} does not convert to a whole number of {}",
+            amount, native.denom
+        ))
+    })?;
+
     let mut balances = Balances::from_storage(&mut deps.storage);
     let account_balance = balances.balance(&sender_address);
 
@@ -119,17 +159,18 @@ pub fn try_redeem<S: Storage, A: Api, Q: Querier>(
 
     let token_reserve = deps
         .querier
-        .query_balance(&env.contract.address, "ughm")?
+        .query_balance(&env.contract.address, &native.denom)?
         .amount;
-    if amount > token_reserve {
-        return Err(StdError::generic_err(
-            "You are trying to redeem for more SCRT than the token has in its deposit reserve.",
-        ));
+    if native_amount > token_reserve.u128() {
+        return Err(StdError::generic_err(format!(
+            "You are trying to redeem for more {} than the token has in its deposit reserve.",
+            native.denom
+        )));
     }
 
     let withdrawal_coins: Vec<Coin> = vec![Coin {
-        denom: "ughm".to_string(),
-        amount,
+        denom: native.denom,
+        amount: Uint128(native_amount),
     }];
 
     store_redeem(
@@ -160,23 +201,25 @@ pub fn try_transfer_impl<S: Storage, A: Api, Q: Querier>(
     amount: Uint128,
     memo: Option<String>,
     block: &cosmwasm_std::BlockInfo,
-) -> StdResult<()> {
-    perform_transfer(&mut deps.storage, sender, recipient, amount.u128())?;
-
+) -> StdResult<Uint128> {
     let symbol = Config::from_storage(&mut deps.storage).constants()?.symbol;
 
-    store_transfer(
+    let (received, fee) = split_transfer_fee(&deps.storage, sender, recipient, amount.u128());
+    perform_transfer(&mut deps.storage, sender, recipient, received, false)?;
+
+    let transfer_id = store_transfer(
         &mut deps.storage,
         sender,
         sender,
         recipient,
-        amount,
-        symbol,
+        Uint128(received),
+        symbol.clone(),
         memo,
         block,
     )?;
+    charge_transfer_fee(&mut deps.storage, sender, fee, symbol, transfer_id, block)?;
 
-    Ok(())
+    Ok(Uint128(received))
 }
 
 pub fn try_transfer<S: Storage, A: Api, Q: Querier>(
@@ -269,7 +312,7 @@ pub fn try_send_impl<S: Storage, A: Api, Q: Querier>(
     block: &cosmwasm_std::BlockInfo,
 ) -> StdResult<()> {
     let recipient_canon = deps.api.canonical_address(&recipient)?;
-    try_transfer_impl(
+    let amount = try_transfer_impl(
         deps,
         sender_canon,
         &recipient_canon,
@@ -387,6 +430,9 @@ pub fn use_allowance<S: Storage>(
     spender: &CanonicalAddr,
     amount: u128,
 ) -> StdResult<()> {
+    check_not_frozen(storage, owner, "owner")?;
+    check_not_frozen(storage, spender, "spender")?;
+
     let mut allowance = read_allowance(storage, owner, spender)?;
 
     if allowance.is_expired_at(&env.block) {
@@ -397,6 +443,9 @@ pub fn use_allowance<S: Storage>(
     } else {
         return Err(insufficient_allowance(allowance.amount, amount));
     }
+    if let Some(period_limit) = allowance.period_limit.as_mut() {
+        period_limit.consume(amount, &env.block)?;
+    }
 
     write_allowance(storage, owner, spender, allowance)?;
 
@@ -411,27 +460,29 @@ pub fn try_transfer_from_impl<S: Storage, A: Api, Q: Querier>(
     recipient: &CanonicalAddr,
     amount: Uint128,
     memo: Option<String>,
-) -> StdResult<()> {
+) -> StdResult<Uint128> {
     let raw_amount = amount.u128();
 
     use_allowance(&mut deps.storage, env, owner, spender, raw_amount)?;
 
-    perform_transfer(&mut deps.storage, owner, recipient, raw_amount)?;
-
     let symbol = Config::from_storage(&mut deps.storage).constants()?.symbol;
 
-    store_transfer(
+    let (received, fee) = split_transfer_fee(&deps.storage, owner, recipient, raw_amount);
+    perform_transfer(&mut deps.storage, owner, recipient, received, false)?;
+
+    let transfer_id = store_transfer(
         &mut deps.storage,
         owner,
         spender,
         recipient,
-        amount,
-        symbol,
+        Uint128(received),
+        symbol.clone(),
         memo,
         &env.block,
     )?;
+    charge_transfer_fee(&mut deps.storage, owner, fee, symbol, transfer_id, &env.block)?;
 
-    Ok(())
+    Ok(Uint128(received))
 }
 
 pub fn try_transfer_from<S: Storage, A: Api, Q: Querier>(
@@ -500,7 +551,7 @@ pub fn try_send_from_impl<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<()> {
     let owner_canon = deps.api.canonical_address(&owner)?;
     let recipient_canon = deps.api.canonical_address(&recipient)?;
-    try_transfer_from_impl(
+    let amount = try_transfer_from_impl(
         deps,
         &env,
         spender_canon,
@@ -733,7 +784,14 @@ pub fn try_increase_allowance<S: Storage, A: Api, Q: Querier>(
     spender: HumanAddr,
     amount: Uint128,
     expiration: Option<u64>,
+    period_limit: Option<SpendingLimit>,
 ) -> StdResult<HandleResponse> {
+    if let Some(SpendingLimit { period_seconds: 0, .. }) = period_limit {
+        return Err(StdError::generic_err(
+            "A period limit requires a period of at least one second",
+        ));
+    }
+
     let owner_address = deps.api.canonical_address(&env.message.sender)?;
     let spender_address = deps.api.canonical_address(&spender)?;
 
@@ -745,6 +803,7 @@ pub fn try_increase_allowance<S: Storage, A: Api, Q: Querier>(
     if allowance.is_expired_at(&env.block) {
         allowance.amount = amount.u128();
         allowance.expiration = None;
+        allowance.period_limit = None;
     } else {
         allowance.amount = allowance.amount.saturating_add(amount.u128());
     }
@@ -752,6 +811,14 @@ pub fn try_increase_allowance<S: Storage, A: Api, Q: Querier>(
     if expiration.is_some() {
         allowance.expiration = expiration;
     }
+    if let Some(period_limit) = period_limit {
+        allowance.period_limit = Some(PeriodLimit {
+            limit: period_limit.limit.u128(),
+            period_seconds: period_limit.period_seconds,
+            period_start: env.block.time,
+            spent: 0,
+        });
+    }
     let new_amount = allowance.amount;
     write_allowance(
         &mut deps.storage,
@@ -790,6 +857,7 @@ pub fn try_decrease_allowance<S: Storage, A: Api, Q: Querier>(
     if allowance.is_expired_at(&env.block) {
         allowance.amount = 0;
         allowance.expiration = None;
+        allowance.period_limit = None;
     } else {
         allowance.amount = allowance.amount.saturating_sub(amount.u128());
     }
@@ -817,58 +885,251 @@ pub fn try_decrease_allowance<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
-pub fn add_minters<S: Storage, A: Api, Q: Querier>(
+pub fn try_set_allowance<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    minters_to_add: Vec<HumanAddr>,
+    spender: HumanAddr,
+    amount: Uint128,
+    expiration: Option<u64>,
 ) -> StdResult<HandleResponse> {
-    let mut config = Config::from_storage(&mut deps.storage);
-    let constants = config.constants()?;
-    if !constants.mint_is_enabled {
+    let allowance =
+        set_allowance_impl(deps, &env.block, &env.message.sender, spender, amount, expiration)?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetAllowance { allowance })?),
+    };
+    Ok(res)
+}
+
+pub fn try_batch_set_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    actions: Vec<batch::SetAllowanceAction>,
+) -> StdResult<HandleResponse> {
+    let mut allowances = vec![];
+    for action in actions {
+        allowances.push(set_allowance_impl(
+            deps,
+            &env.block,
+            &env.message.sender,
+            action.spender,
+            action.amount,
+            action.expiration,
+        )?);
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::BatchSetAllowance {
+            status: Success,
+            allowances,
+        })?),
+    };
+    Ok(res)
+}
+
+fn set_allowance_impl<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    block: &cosmwasm_std::BlockInfo,
+    owner: &HumanAddr,
+    spender: HumanAddr,
+    amount: Uint128,
+    expiration: Option<u64>,
+) -> StdResult<AllowanceInfo> {
+    let owner_address = deps.api.canonical_address(owner)?;
+    let spender_address = deps.api.canonical_address(&spender)?;
+
+    let mut allowance = read_allowance(&deps.storage, &owner_address, &spender_address)?;
+    // Unlike an increase, the amount doesn't depend on the previous allowance, so only its
+    // period limit has to be dropped once it expired
+    if allowance.is_expired_at(block) {
+        allowance.period_limit = None;
+    }
+    allowance.amount = amount.u128();
+    allowance.expiration = expiration;
+    let expired = allowance.is_expired_at(block);
+    let period_limit = allowance.period_limit.as_ref().map(PeriodLimit::info);
+    write_allowance(
+        &mut deps.storage,
+        &owner_address,
+        &spender_address,
+        allowance,
+    )?;
+
+    Ok(AllowanceInfo {
+        owner: owner.clone(),
+        spender,
+        allowance: amount,
+        expiration,
+        expired: Some(expired),
+        period_limit,
+    })
+}
+
+/// Zeroes the allowances in the sender's index, plus those to `extra_spenders`, which may have been
+/// written before the index existed
+pub fn try_revoke_all_allowances<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    extra_spenders: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let owner_address = deps.api.canonical_address(&env.message.sender)?;
+    let (mut spenders, _) = get_allowance_counterparties(
+        &deps.storage,
+        ALLOWANCES_GIVEN,
+        &owner_address,
+        0,
+        u32::MAX,
+    )?;
+    for spender in extra_spenders {
+        let spender_address = deps.api.canonical_address(&spender)?;
+        if !spenders.contains(&spender_address) {
+            spenders.push(spender_address);
+        }
+    }
+
+    let mut allowances = vec![];
+    for spender_address in spenders {
+        write_allowance(
+            &mut deps.storage,
+            &owner_address,
+            &spender_address,
+            Allowance::default(),
+        )?;
+        allowances.push(AllowanceInfo {
+            owner: env.message.sender.clone(),
+            spender: deps.api.human_address(&spender_address)?,
+            allowance: Uint128(0),
+            expiration: None,
+            expired: Some(false),
+            period_limit: None,
+        });
+    }
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeAllAllowances {
+            status: Success,
+            allowances,
+        })?),
+    };
+    Ok(res)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn try_create_mandate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    payee: HumanAddr,
+    amount: Uint128,
+    period: u64,
+    max_count: u32,
+    first_due: Option<u64>,
+    expiration: Option<u64>,
+) -> StdResult<HandleResponse> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("A mandate must pay a positive amount"));
+    }
+    if period == 0 {
         return Err(StdError::generic_err(
-            "Mint functionality is not enabled for this token.",
+            "A mandate's period must be at least one second",
+        ));
+    }
+    if max_count == 0 {
+        return Err(StdError::generic_err(
+            "A mandate must allow at least one installment",
         ));
     }
 
-    check_if_admin(&config, &env.message.sender)?;
+    let payer = deps.api.canonical_address(&env.message.sender)?;
+    let payee = deps.api.canonical_address(&payee)?;
+    if payer == payee {
+        return Err(StdError::generic_err("You can't create a mandate to pay yourself"));
+    }
 
-    config.add_minters(minters_to_add)?;
+    // A new mandate replaces any earlier one between the same accounts
+    let mandate = Mandate {
+        amount: amount.u128(),
+        expiration,
+        period,
+        max_count,
+        executed: 0,
+        next_due: first_due.unwrap_or(env.block.time),
+    };
+    write_mandate(&mut deps.storage, &payer, &payee, &mandate)?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::AddMinters { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::CreateMandate { status: Success })?),
     })
 }
 
-pub fn remove_minters<S: Storage, A: Api, Q: Querier>(
+/// Pays the next due installment of a mandate. Anyone can execute a mandate, e.g. a keeper on
+/// behalf of the payee.
+pub fn try_execute_mandate<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    minters_to_remove: Vec<HumanAddr>,
+    payer: HumanAddr,
+    payee: HumanAddr,
 ) -> StdResult<HandleResponse> {
-    let mut config = Config::from_storage(&mut deps.storage);
-    let constants = config.constants()?;
-    if !constants.mint_is_enabled {
-        return Err(StdError::generic_err(
-            "Mint functionality is not enabled for this token.",
-        ));
-    }
+    let payer = deps.api.canonical_address(&payer)?;
+    let payee = deps.api.canonical_address(&payee)?;
+    let mut mandate = read_mandate(&deps.storage, &payer, &payee)?
+        .ok_or_else(|| StdError::generic_err("No mandate from this payer to this payee"))?;
+    mandate.check_due_at(&env.block)?;
+
+    mandate.executed += 1;
+    mandate.next_due = mandate.next_due.saturating_add(mandate.period);
+    let memo = format!("Installment {} of {}", mandate.executed, mandate.max_count);
+    try_transfer_impl(
+        deps,
+        &payer,
+        &payee,
+        Uint128(mandate.amount),
+        Some(memo),
+        &env.block,
+    )?;
+    write_mandate(&mut deps.storage, &payer, &payee, &mandate)?;
 
-    check_if_admin(&config, &env.message.sender)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ExecuteMandate {
+            status: Success,
+            installment: mandate.executed,
+            remaining: mandate.max_count - mandate.executed,
+        })?),
+    })
+}
 
-    config.remove_minters(minters_to_remove)?;
+pub fn try_revoke_mandate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    payee: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let payer = deps.api.canonical_address(&env.message.sender)?;
+    let payee = deps.api.canonical_address(&payee)?;
+    if read_mandate(&deps.storage, &payer, &payee)?.is_none() {
+        return Err(StdError::generic_err("No mandate from this payer to this payee"));
+    }
+    remove_mandate(&mut deps.storage, &payer, &payee);
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::RemoveMinters { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::RevokeMandate { status: Success })?),
     })
 }
 
-pub fn set_minters<S: Storage, A: Api, Q: Querier>(
+pub fn add_minters<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    minters_to_set: Vec<HumanAddr>,
+    minters_to_add: Vec<HumanAddr>,
 ) -> StdResult<HandleResponse> {
     let mut config = Config::from_storage(&mut deps.storage);
     let constants = config.constants()?;
@@ -878,130 +1139,1674 @@ pub fn set_minters<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
-    check_if_admin(&config, &env.message.sender)?;
+    check_role(&config, Role::MinterManager, &env.message.sender)?;
 
-    config.set_minters(minters_to_set)?;
+    config.add_minters(minters_to_add)?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::SetMinters { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::AddMinters { status: Success })?),
     })
 }
 
-/// Burn tokens
-///
-/// Remove `amount` tokens from the system irreversibly, from signer account
-///
-/// @param amount the amount of money to burn
-pub fn try_burn<S: Storage, A: Api, Q: Querier>(
+pub fn set_minter_cap<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    amount: Uint128,
-    memo: Option<String>,
+    minter: HumanAddr,
+    lifetime_cap: Option<Uint128>,
+    window_cap: Option<Uint128>,
+    window_seconds: Option<u64>,
 ) -> StdResult<HandleResponse> {
-    let config = ReadonlyConfig::from_storage(&deps.storage);
+    let config = Config::from_storage(&mut deps.storage);
     let constants = config.constants()?;
-    if !constants.burn_is_enabled {
+    if !constants.mint_is_enabled {
         return Err(StdError::generic_err(
-            "Burn functionality is not enabled for this token.",
+            "Mint functionality is not enabled for this token.",
         ));
     }
 
-    let sender_address = deps.api.canonical_address(&env.message.sender)?;
-    let raw_amount = amount.u128();
-
-    let mut balances = Balances::from_storage(&mut deps.storage);
-    let mut account_balance = balances.balance(&sender_address);
-
-    if let Some(new_account_balance) = account_balance.checked_sub(raw_amount) {
-        account_balance = new_account_balance;
-    } else {
-        return Err(StdError::generic_err(format!(
-            "insufficient funds to burn: balance={}, required={}",
-            account_balance, raw_amount
-        )));
+    check_role(&config, Role::MinterManager, &env.message.sender)?;
+
+    let mut cap = read_minter_cap(&deps.storage, &minter)?;
+    cap.lifetime_cap = lifetime_cap.map(|cap| cap.u128());
+    match (window_cap, window_seconds) {
+        (Some(window_cap), Some(window_seconds)) if window_seconds > 0 => {
+            if cap.window_seconds != window_seconds {
+                // Start a fresh window on the next mint when the window length changes
+                cap.window_start = 0;
+                cap.window_minted = 0;
+            }
+            cap.window_cap = Some(window_cap.u128());
+            cap.window_seconds = window_seconds;
+        }
+        (Some(_), _) => {
+            return Err(StdError::generic_err(
+                "A window cap requires a window length of at least one second",
+            ));
+        }
+        (None, _) => {
+            cap.window_cap = None;
+            cap.window_seconds = 0;
+            cap.window_start = 0;
+            cap.window_minted = 0;
+        }
     }
+    write_minter_cap(&mut deps.storage, &minter, &cap)?;
 
-    balances.set_account_balance(&sender_address, account_balance);
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetMinterCap { status: Success })?),
+    })
+}
 
+pub fn set_max_supply<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    max_supply: Option<Uint128>,
+) -> StdResult<HandleResponse> {
     let mut config = Config::from_storage(&mut deps.storage);
-    let mut total_supply = config.total_supply();
-    if let Some(new_total_supply) = total_supply.checked_sub(raw_amount) {
-        total_supply = new_total_supply;
-    } else {
+
+    check_role(&config, Role::ConfigManager, &env.message.sender)?;
+
+    let max_supply = max_supply.map(|max_supply| max_supply.u128());
+    let is_increase = match (config.max_supply(), max_supply) {
+        (Some(current), Some(new)) => new > current,
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+    if is_increase && !config.max_supply_can_increase() {
         return Err(StdError::generic_err(
-            "You're trying to burn more than is available in the total supply",
+            "The max supply of this token can only be lowered",
         ));
     }
-    config.set_total_supply(total_supply);
-
-    store_burn(
-        &mut deps.storage,
-        &sender_address,
-        &sender_address,
-        amount,
-        constants.symbol,
-        memo,
-        &env.block,
-    )?;
+    if let Some(new) = max_supply {
+        if new < config.total_supply() {
+            return Err(StdError::generic_err(
+                "The max supply cannot be lower than the current total supply",
+            ));
+        }
+    }
+    config.set_max_supply(max_supply)?;
 
-    let res = HandleResponse {
+    Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::Burn { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::SetMaxSupply { status: Success })?),
+    })
+}
+
+pub fn set_token_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: Option<String>,
+    metadata: TokenMetadata,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_role(&config, Role::ConfigManager, &env.message.sender)?;
+
+    validate_token_metadata(&metadata)?;
+    if let Some(name) = name {
+        if !is_valid_name(&name) {
+            return Err(StdError::generic_err(
+                "Name is not in the expected format (3-30 UTF-8 bytes)",
+            ));
+        }
+        let mut constants = config.constants()?;
+        constants.name = name;
+        config.set_constants(&constants)?;
+    }
+    config.set_token_metadata(&metadata)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetTokenMetadata { status: Success })?),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    public_total_supply: Option<bool>,
+    enable_deposit: Option<bool>,
+    enable_redeem: Option<bool>,
+    enable_mint: Option<bool>,
+    enable_burn: Option<bool>,
+    lock: Vec<Feature>,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_role(&config, Role::ConfigManager, &env.message.sender)?;
+
+    let mut constants = config.constants()?;
+    let mut locked = config.locked_features();
+    let mut changes: Vec<ConfigChange> = vec![];
+
+    let requested = [
+        (Feature::PublicTotalSupply, public_total_supply),
+        (Feature::Deposit, enable_deposit),
+        (Feature::Redeem, enable_redeem),
+        (Feature::Mint, enable_mint),
+        (Feature::Burn, enable_burn),
+    ];
+    for (feature, enabled) in requested.iter() {
+        let enabled = match enabled {
+            Some(enabled) if *enabled != constants.feature_enabled(*feature) => *enabled,
+            _ => continue,
+        };
+        if locked.contains(feature) {
+            return Err(StdError::generic_err(format!(
+                "The {} setting is locked and can no longer be changed",
+                feature.as_str()
+            )));
+        }
+        constants.set_feature_enabled(*feature, enabled);
+        changes.push(ConfigChange {
+            feature: *feature,
+            enabled,
+            locked: false,
+            changed_by: env.message.sender.clone(),
+            block_time: env.block.time,
+            block_height: env.block.height,
+        });
+    }
+
+    for feature in lock {
+        if locked.contains(&feature) {
+            continue;
+        }
+        locked.push(feature);
+        changes.push(ConfigChange {
+            feature,
+            enabled: constants.feature_enabled(feature),
+            locked: true,
+            changed_by: env.message.sender.clone(),
+            block_time: env.block.time,
+            block_height: env.block.height,
+        });
+    }
+
+    config.set_constants(&constants)?;
+    config.set_locked_features(&locked)?;
+    for change in &changes {
+        append_config_change(&mut deps.storage, change)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::UpdateConfig { status: Success })?),
+    })
+}
+
+pub fn remove_minters<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    minters_to_remove: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+    let constants = config.constants()?;
+    if !constants.mint_is_enabled {
+        return Err(StdError::generic_err(
+            "Mint functionality is not enabled for this token.",
+        ));
+    }
+
+    check_role(&config, Role::MinterManager, &env.message.sender)?;
+
+    config.remove_minters(minters_to_remove)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveMinters { status: Success })?),
+    })
+}
+
+pub fn set_minters<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    minters_to_set: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+    let constants = config.constants()?;
+    if !constants.mint_is_enabled {
+        return Err(StdError::generic_err(
+            "Mint functionality is not enabled for this token.",
+        ));
+    }
+
+    check_role(&config, Role::MinterManager, &env.message.sender)?;
+
+    config.set_minters(minters_to_set)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetMinters { status: Success })?),
+    })
+}
+
+/// Burn tokens
+///
+/// Remove `amount` tokens from the system irreversibly, from signer account
+///
+/// @param amount the amount of money to burn
+pub fn try_burn<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let config = ReadonlyConfig::from_storage(&deps.storage);
+    let constants = config.constants()?;
+    if !constants.burn_is_enabled {
+        return Err(StdError::generic_err(
+            "Burn functionality is not enabled for this token.",
+        ));
+    }
+
+    let sender_address = deps.api.canonical_address(&env.message.sender)?;
+    check_not_frozen(&deps.storage, &sender_address, "sender")?;
+    let raw_amount = amount.u128();
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let mut account_balance = balances.balance(&sender_address);
+
+    if let Some(new_account_balance) = account_balance.checked_sub(raw_amount) {
+        account_balance = new_account_balance;
+    } else {
+        return Err(StdError::generic_err(format!(
+            "insufficient funds to burn: balance={}, required={}",
+            account_balance, raw_amount
+        )));
+    }
+
+    balances.set_account_balance(&sender_address, account_balance);
+
+    let mut config = Config::from_storage(&mut deps.storage);
+    let mut total_supply = config.total_supply();
+    if let Some(new_total_supply) = total_supply.checked_sub(raw_amount) {
+        total_supply = new_total_supply;
+    } else {
+        return Err(StdError::generic_err(
+            "You're trying to burn more than is available in the total supply",
+        ));
+    }
+    config.set_total_supply(total_supply);
+
+    store_burn(
+        &mut deps.storage,
+        &sender_address,
+        &sender_address,
+        amount,
+        constants.symbol,
+        memo,
+        &env.block,
+    )?;
+
+    let res = HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Burn { status: Success })?),
+    };
+
+    Ok(res)
+}
+
+/// Moves `amount` from `from` to `to`. Only forced transfers set `bypass_sender_freeze`, to recover
+/// funds from frozen accounts.
+pub fn perform_transfer<T: Storage>(
+    store: &mut T,
+    from: &CanonicalAddr,
+    to: &CanonicalAddr,
+    amount: u128,
+    bypass_sender_freeze: bool,
+) -> StdResult<()> {
+    if !bypass_sender_freeze {
+        check_not_frozen(store, from, "sender")?;
+    }
+    check_not_frozen(store, to, "recipient")?;
+    check_allowlisted(store, to)?;
+
+    let mut balances = Balances::from_storage(store);
+
+    let mut from_balance = balances.balance(from);
+    if let Some(new_from_balance) = from_balance.checked_sub(amount) {
+        from_balance = new_from_balance;
+    } else {
+        return Err(StdError::generic_err(format!(
+            "insufficient funds: balance={}, required={}",
+            from_balance, amount
+        )));
+    }
+    balances.set_account_balance(from, from_balance);
+
+    let mut to_balance = balances.balance(to);
+    to_balance = to_balance.checked_add(amount).ok_or_else(|| {
+        StdError::generic_err("This tx will literally make them too rich. Try transferring less")
+    })?;
+    balances.set_account_balance(to, to_balance);
+
+    Ok(())
+}
+
+/// Splits a transfer of `amount` into what the recipient receives and the fee
+pub fn split_transfer_fee<S: ReadonlyStorage>(
+    storage: &S,
+    owner: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: u128,
+) -> (u128, u128) {
+    let config = ReadonlyConfig::from_storage(storage);
+    let exempt = config.fee_exempt();
+    if exempt.contains(owner) || exempt.contains(recipient) {
+        return (amount, 0);
+    }
+
+    let fee = config.fee_config().fee_for(amount);
+    (amount - fee, fee)
+}
+
+/// Moves a transfer fee from `payer` to the fee collector, or burns it when there is none.
+/// The fee's history record is linked to the transfer with id `transfer_id`.
+pub fn charge_transfer_fee<S: Storage>(
+    storage: &mut S,
+    payer: &CanonicalAddr,
+    fee: u128,
+    symbol: String,
+    transfer_id: u64,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    if fee == 0 {
+        return Ok(());
+    }
+
+    let mut balances = Balances::from_storage(storage);
+    let balance = balances.balance(payer);
+    let balance = balance.checked_sub(fee).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "insufficient funds to pay the transfer fee: balance={}, required={}",
+            balance, fee
+        ))
+    })?;
+    balances.set_account_balance(payer, balance);
+
+    let collector = ReadonlyConfig::from_storage(storage).fee_config().collector;
+    if let Some(collector) = &collector {
+        // The collector is credited even when frozen or not allowlisted, otherwise freezing it
+        // would block every transfer that pays a fee
+        credit_balance(storage, collector, fee)?;
+    } else {
+        let mut config = Config::from_storage(storage);
+        // This can't underflow since the fee was part of someone's balance
+        let total_supply = config.total_supply().saturating_sub(fee);
+        config.set_total_supply(total_supply);
+    }
+
+    store_fee(
+        storage,
+        payer,
+        collector.as_ref(),
+        Uint128(fee),
+        symbol,
+        transfer_id,
+        block,
+    )
+}
+
+pub fn set_fee_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    bps: u16,
+    min: Option<Uint128>,
+    max: Option<Uint128>,
+    collector: Option<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    check_role(
+        &Config::from_storage(&mut deps.storage),
+        Role::FeeManager,
+        &env.message.sender,
+    )?;
+
+    if bps > FeeConfig::MAX_BPS {
+        return Err(StdError::generic_err(format!(
+            "The fee can't exceed {} basis points",
+            FeeConfig::MAX_BPS
+        )));
+    }
+    let min = min.map(|min| min.u128()).unwrap_or_default();
+    let max = max.map(|max| max.u128());
+    if let Some(max) = max {
+        if max < min {
+            return Err(StdError::generic_err(
+                "The maximum fee can't be lower than the minimum fee",
+            ));
+        }
+    }
+    let collector = match collector {
+        Some(collector) => Some(deps.api.canonical_address(&collector)?),
+        None => None,
+    };
+
+    Config::from_storage(&mut deps.storage).set_fee_config(&FeeConfig {
+        bps,
+        min,
+        max,
+        collector,
+    })?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetFeeConfig { status: Success })?),
+    })
+}
+
+pub fn add_fee_exemptions<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    addresses: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    check_role(
+        &Config::from_storage(&mut deps.storage),
+        Role::FeeManager,
+        &env.message.sender,
+    )?;
+
+    let mut exempt = ReadonlyConfig::from_storage(&deps.storage).fee_exempt();
+    for address in addresses {
+        let address = deps.api.canonical_address(&address)?;
+        if !exempt.contains(&address) {
+            exempt.push(address);
+        }
+    }
+    Config::from_storage(&mut deps.storage).set_fee_exempt(&exempt)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AddFeeExemptions { status: Success })?),
+    })
+}
+
+pub fn remove_fee_exemptions<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    addresses: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    check_role(
+        &Config::from_storage(&mut deps.storage),
+        Role::FeeManager,
+        &env.message.sender,
+    )?;
+
+    let mut exempt = ReadonlyConfig::from_storage(&deps.storage).fee_exempt();
+    for address in addresses {
+        let address = deps.api.canonical_address(&address)?;
+        exempt.retain(|x| x != &address);
+    }
+    Config::from_storage(&mut deps.storage).set_fee_exempt(&exempt)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveFeeExemptions { status: Success })?),
+    })
+}
+
+pub fn check_not_frozen<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    party: &str,
+) -> StdResult<()> {
+    if is_frozen(storage, account) {
+        return Err(StdError::generic_err(format!(
+            "The {} account is frozen",
+            party
+        )));
+    }
+    Ok(())
+}
+
+/// In allowlist mode only allowlisted accounts may receive tokens
+pub fn check_allowlisted<S: ReadonlyStorage>(storage: &S, account: &CanonicalAddr) -> StdResult<()> {
+    if ReadonlyConfig::from_storage(storage).allowlist_enabled() && !is_allowlisted(storage, account) {
+        return Err(StdError::generic_err(
+            "The recipient account is not on the allowlist",
+        ));
+    }
+    Ok(())
+}
+
+pub fn add_to_allowlist<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    addresses: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+    if !config.allowlist_enabled() {
+        return Err(StdError::generic_err(
+            "Allowlist mode is not enabled for this token.",
+        ));
+    }
+
+    check_role(&config, Role::ComplianceManager, &env.message.sender)?;
+
+    for address in addresses {
+        let account = deps.api.canonical_address(&address)?;
+        add_allowlisted_account(&mut deps.storage, &account)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AddToAllowlist { status: Success })?),
+    })
+}
+
+pub fn remove_from_allowlist<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    addresses: Vec<HumanAddr>,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+    if !config.allowlist_enabled() {
+        return Err(StdError::generic_err(
+            "Allowlist mode is not enabled for this token.",
+        ));
+    }
+
+    check_role(&config, Role::ComplianceManager, &env.message.sender)?;
+
+    for address in addresses {
+        let account = deps.api.canonical_address(&address)?;
+        remove_allowlisted_account(&mut deps.storage, &account)?;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RemoveFromAllowlist { status: Success })?),
+    })
+}
+
+pub fn freeze_account<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    frozen: bool,
+    reason: Option<String>,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+
+    check_role(&config, Role::ComplianceManager, &env.message.sender)?;
+    let symbol = config.constants()?.symbol;
+
+    let account = deps.api.canonical_address(&address)?;
+    if is_frozen(&deps.storage, &account) == frozen {
+        return Err(StdError::generic_err(if frozen {
+            "This account is already frozen"
+        } else {
+            "This account is not frozen"
+        }));
+    }
+    set_frozen(&mut deps.storage, &account, frozen);
+
+    let by = deps.api.canonical_address(&env.message.sender)?;
+    store_freeze(&mut deps.storage, &account, &by, frozen, symbol, reason, &env.block)?;
+
+    let answer = if frozen {
+        HandleAnswer::FreezeAccount { status: Success }
+    } else {
+        HandleAnswer::UnfreezeAccount { status: Success }
+    };
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&answer)?),
+    })
+}
+
+pub fn revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> StdResult<HandleResponse> {
+    if !RevokedPermits::is_permit_revoked(
+        &deps.storage,
+        PREFIX_REVOKED_PERMITS,
+        &env.message.sender,
+        &permit_name,
+    ) {
+        append_revoked_permit_name(&mut deps.storage, &env.message.sender, &permit_name)?;
+    }
+    RevokedPermits::revoke_permit(
+        &mut deps.storage,
+        PREFIX_REVOKED_PERMITS,
+        &env.message.sender,
+        &permit_name,
+    );
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit { status: Success })?),
+    })
+}
+
+/// Sets what the sender's query permits named `permit_name` can read, replacing any earlier scope.
+/// Setting it before handing out the permit leaves no window in which the permit is unscoped.
+pub fn set_permit_scope<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+    scope: PermitScope,
+) -> StdResult<HandleResponse> {
+    if let Some(range) = &scope.history {
+        let backwards = |from: Option<u64>, to: Option<u64>| {
+            matches!((from, to), (Some(from), Some(to)) if from > to)
+        };
+        if backwards(range.from_id, range.to_id) || backwards(range.from_time, range.to_time) {
+            return Err(StdError::generic_err("A history range can't end before it starts"));
+        }
+    }
+    write_permit_scope(&mut deps.storage, &env.message.sender, &permit_name, &scope)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetPermitScope { status: Success })?),
+    })
+}
+
+/// Invalidates every permit the sender has signed so far, by drawing a new permit tag that no
+/// earlier permit name could have carried
+pub fn revoke_all_permits<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let prng_seed = ReadonlyConfig::from_storage(&deps.storage).constants()?.prng_seed;
+    let previous_tag = read_permit_tag(&deps.storage, &env.message.sender)?.unwrap_or_default();
+
+    let mut rng_entropy = Vec::new();
+    rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
+    rng_entropy.extend_from_slice(&env.block.time.to_be_bytes());
+    rng_entropy.extend_from_slice(env.message.sender.0.as_bytes());
+    rng_entropy.extend_from_slice(previous_tag.as_bytes());
+    let rand_bytes = Prng::new(&prng_seed, &rng_entropy).rand_bytes();
+    let tag: String = rand_bytes[..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    write_permit_tag(&mut deps.storage, &env.message.sender, &tag)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeAllPermits {
+            status: Success,
+            tag,
+        })?),
+    })
+}
+
+/// The amino sign doc of a permit that authorizes a handle. Wallets sign it like the one of a query
+/// permit, only with a message of another type. Every field is fixed here rather than taken from a
+/// library, so that the README can spell out the exact bytes to sign.
+#[derive(Serialize)]
+struct SignedHandlePermit<'a, P: Serialize> {
+    account_number: Uint128,
+    chain_id: &'a str,
+    fee: SignedHandlePermitFee,
+    memo: &'static str,
+    msgs: Vec<HandlePermitMsg<'a, P>>,
+    sequence: Uint128,
+}
+
+/// The zero fee of a handle permit's sign doc: `{"amount":[{"amount":"0","denom":"ughm"}],"gas":"1"}`
+#[derive(Serialize)]
+struct SignedHandlePermitFee {
+    amount: Vec<SignedHandlePermitCoin>,
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct SignedHandlePermitCoin {
+    amount: Uint128,
+    denom: &'static str,
+}
+
+#[derive(Serialize)]
+struct HandlePermitMsg<'a, P: Serialize> {
+    r#type: &'static str,
+    value: &'a P,
+}
+
+/// The signed params of a permit that authorizes a handle
+trait HandlePermitParams: Serialize {
+    /// The type of the message in the sign doc
+    const PERMIT_TYPE: &'static str;
+
+    fn token(&self) -> &HumanAddr;
+    fn chain_id(&self) -> &str;
+    fn expires_at(&self) -> u64;
+    fn owner(&self) -> &HumanAddr;
+}
+
+impl HandlePermitParams for TransferPermitParams {
+    const PERMIT_TYPE: &'static str = "transfer_permit";
+
+    fn token(&self) -> &HumanAddr {
+        &self.token
+    }
+    fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+    fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+    fn owner(&self) -> &HumanAddr {
+        &self.owner
+    }
+}
+
+impl HandlePermitParams for ApprovePermitParams {
+    const PERMIT_TYPE: &'static str = "approve_permit";
+
+    fn token(&self) -> &HumanAddr {
+        &self.token
+    }
+    fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+    fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+    fn owner(&self) -> &HumanAddr {
+        &self.owner
+    }
+}
+
+/// Checks that a permit was signed by its owner for this token and chain and hasn't expired, and
+/// returns the owner's canonical address
+fn verify_handle_permit<S: Storage, A: Api, Q: Querier, P: HandlePermitParams>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    params: &P,
+    signature: &PermitSignature,
+) -> StdResult<CanonicalAddr> {
+    let token = params.token();
+    let chain_id = params.chain_id();
+    let expires_at = params.expires_at();
+    let owner = params.owner();
+    let token_address = ReadonlyConfig::from_storage(&deps.storage)
+        .constants()?
+        .contract_address;
+    if *token != token_address {
+        return Err(StdError::generic_err(format!(
+            "This permit is for token {}, not {}",
+            token, token_address
+        )));
+    }
+    if chain_id != env.block.chain_id {
+        return Err(StdError::generic_err(format!(
+            "This permit is for chain {}, not {}",
+            chain_id, env.block.chain_id
+        )));
+    }
+    if env.block.time >= expires_at {
+        return Err(StdError::generic_err(format!(
+            "This permit expired at {}",
+            expires_at
+        )));
+    }
+
+    let pubkey = &signature.pub_key.value;
+    let signer = pubkey_to_account(pubkey);
+    if deps.api.human_address(&signer)? != *owner {
+        return Err(StdError::generic_err(format!(
+            "This permit was not signed by its owner {}",
+            owner
+        )));
+    }
+    let sign_doc = SignedHandlePermit {
+        account_number: Uint128(0),
+        chain_id,
+        fee: SignedHandlePermitFee {
+            amount: vec![SignedHandlePermitCoin {
+                amount: Uint128(0),
+                denom: "ughm",
+            }],
+            gas: Uint128(1),
+        },
+        memo: "",
+        msgs: vec![HandlePermitMsg {
+            r#type: P::PERMIT_TYPE,
+            value: params,
+        }],
+        sequence: Uint128(0),
+    };
+    let signed_hash = sha_256(&to_binary(&sign_doc)?.0);
+    let verified = deps
+        .api
+        .secp256k1_verify(&signed_hash, &signature.signature.0, &pubkey.0)
+        .map_err(|err| {
+            StdError::generic_err(format!("Failed to verify the permit's signature: {:?}", err))
+        })?;
+    if !verified {
+        return Err(StdError::generic_err("The permit's signature is invalid"));
+    }
+
+    Ok(signer)
+}
+
+/// Checks that `owner` revoked neither the permit named `permit_name` nor all of their permits
+fn check_permit_not_revoked<S: ReadonlyStorage>(
+    store: &S,
+    owner: &HumanAddr,
+    permit_name: &str,
+) -> StdResult<()> {
+    if RevokedPermits::is_permit_revoked(store, PREFIX_REVOKED_PERMITS, owner, permit_name) {
+        return Err(StdError::generic_err(format!(
+            "Permit {:?} was revoked by account {:?}",
+            permit_name, owner
+        )));
+    }
+    check_permit_tag(store, owner, permit_name)
+}
+
+/// Bumps `owner`'s nonce if `nonce` is the current one, so that the permit carrying it can't be
+/// used again
+fn use_permit_nonce<S: Storage>(store: &mut S, owner: &CanonicalAddr, nonce: u64) -> StdResult<()> {
+    let current = read_permit_nonce(&*store, owner)?;
+    if nonce != current {
+        return Err(StdError::generic_err(format!(
+            "This permit's nonce is {}, expected {}",
+            nonce, current
+        )));
+    }
+    write_permit_nonce(store, owner, current + 1)
+}
+
+pub fn try_transfer_with_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit: TransferPermit,
+) -> StdResult<HandleResponse> {
+    let params = &permit.params;
+    check_permit_not_revoked(&deps.storage, &params.owner, &params.permit_name)?;
+    let owner = verify_handle_permit(deps, &env, params, &permit.signature)?;
+    use_permit_nonce(&mut deps.storage, &owner, params.nonce)?;
+
+    let recipient = deps.api.canonical_address(&params.recipient)?;
+    try_transfer_impl(
+        deps,
+        &owner,
+        &recipient,
+        params.amount,
+        params.memo.clone(),
+        &env.block,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::TransferWithPermit { status: Success })?),
+    })
+}
+
+/// Sets the allowance of `params.spender` on behalf of the permit's signer
+pub fn try_approve_with_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit: ApprovePermit,
+) -> StdResult<HandleResponse> {
+    let params = &permit.params;
+    check_permit_not_revoked(&deps.storage, &params.owner, &params.permit_name)?;
+    let owner = verify_handle_permit(deps, &env, params, &permit.signature)?;
+    use_permit_nonce(&mut deps.storage, &owner, params.nonce)?;
+
+    let allowance = set_allowance_impl(
+        deps,
+        &env.block,
+        &params.owner,
+        params.spender.clone(),
+        params.amount,
+        params.expiration,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ApproveWithPermit { allowance })?),
+    })
+}
+
+/// Moves `amount` from `from` to `to` on the admin's authority, like a transfer but bypassing
+/// allowances and the sender freeze check.
+pub fn force_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    to: HumanAddr,
+    amount: Uint128,
+    reason: String,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+    if !config.force_transfer_enabled() {
+        return Err(StdError::generic_err(
+            "Forced transfers are not enabled for this token.",
+        ));
+    }
+
+    check_if_admin(&config, &env.message.sender)?;
+    let symbol = config.constants()?.symbol;
+
+    if reason.trim().is_empty() {
+        return Err(StdError::generic_err(
+            "A forced transfer requires a reason",
+        ));
+    }
+
+    let from = deps.api.canonical_address(&from)?;
+    let to = deps.api.canonical_address(&to)?;
+    let admin = deps.api.canonical_address(&env.message.sender)?;
+
+    let (received, fee) = split_transfer_fee(&deps.storage, &from, &to, amount.u128());
+    perform_transfer(&mut deps.storage, &from, &to, received, true)?;
+
+    let transfer_id = store_forced_transfer(
+        &mut deps.storage,
+        &from,
+        &admin,
+        &to,
+        Uint128(received),
+        symbol.clone(),
+        Some(reason),
+        &env.block,
+    )?;
+    charge_transfer_fee(&mut deps.storage, &from, fee, symbol, transfer_id, &env.block)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ForceTransfer { status: Success })?),
+    })
+}
+
+pub fn create_vesting<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    amount: Uint128,
+    terms: VestingTerms,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+    check_if_admin(&config, &env.message.sender)?;
+    let symbol = config.constants()?.symbol;
+
+    if !terms.is_valid() {
+        return Err(StdError::generic_err(
+            "Vesting must start no later than its cliff, which must be no later than its end",
+        ));
+    }
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot vest zero tokens"));
+    }
+
+    // The vested tokens come out of the admin's balance
+    let admin = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&recipient)?;
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let admin_balance = balances.balance(&admin);
+    let admin_balance = admin_balance.checked_sub(amount.u128()).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "insufficient funds to vest: balance={}, required={}",
+            admin_balance, amount
+        ))
+    })?;
+    balances.set_account_balance(&admin, admin_balance);
+
+    add_vesting_schedule(&mut deps.storage, &recipient, &admin, amount.u128(), terms)?;
+    store_vesting_create(&mut deps.storage, &recipient, &admin, amount, symbol, None, &env.block)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateVesting { status: Success })?),
+    })
+}
+
+pub fn add_vesting_schedule<S: Storage>(
+    storage: &mut S,
+    account: &CanonicalAddr,
+    funder: &CanonicalAddr,
+    amount: u128,
+    terms: VestingTerms,
+) -> StdResult<()> {
+    let mut schedules = read_vesting_schedules(storage, account)?;
+    schedules.push(VestingSchedule {
+        amount,
+        claimed: 0,
+        terms,
+        revoked_at: None,
+        funder: Some(funder.clone()),
+    });
+    write_vesting_schedules(storage, account, &schedules)
+}
+
+pub fn revoke_vesting<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    account: HumanAddr,
+    index: u32,
+) -> StdResult<HandleResponse> {
+    let config = Config::from_storage(&mut deps.storage);
+    check_if_admin(&config, &env.message.sender)?;
+    let symbol = config.constants()?.symbol;
+
+    let account = deps.api.canonical_address(&account)?;
+    let mut schedules = read_vesting_schedules(&deps.storage, &account)?;
+    let schedule = schedules
+        .get_mut(index as usize)
+        .ok_or_else(|| StdError::generic_err(format!("No vesting schedule at index {}", index)))?;
+    if !schedule.terms.revocable {
+        return Err(StdError::generic_err("This vesting schedule is not revocable"));
+    }
+    if schedule.revoked_at.is_some() {
+        return Err(StdError::generic_err("This vesting schedule was already revoked"));
+    }
+
+    // The unvested remainder goes back to whoever funded the schedule
+    let admin = deps.api.canonical_address(&env.message.sender)?;
+    let funder = schedule.funder.clone().unwrap_or_else(|| admin.clone());
+    let vested = schedule.vested_at(env.block.time);
+    let returned = schedule.amount - vested;
+    schedule.amount = vested;
+    schedule.revoked_at = Some(env.block.time);
+    write_vesting_schedules(&mut deps.storage, &account, &schedules)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let funder_balance = balances.balance(&funder).checked_add(returned).ok_or_else(|| {
+        StdError::generic_err("This revocation would overflow the funder's balance")
+    })?;
+    balances.set_account_balance(&funder, funder_balance);
+    store_vesting_revoke(
+        &mut deps.storage,
+        &account,
+        &admin,
+        &funder,
+        Uint128(returned),
+        symbol,
+        &env.block,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeVesting {
+            status: Success,
+            returned: Uint128(returned),
+        })?),
+    })
+}
+
+pub fn try_claim_vested<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let account = deps.api.canonical_address(&env.message.sender)?;
+    check_not_frozen(&deps.storage, &account, "sender")?;
+    check_allowlisted(&deps.storage, &account)?;
+
+    let mut schedules = read_vesting_schedules(&deps.storage, &account)?;
+    let mut claimed: u128 = 0;
+    for schedule in schedules.iter_mut() {
+        let claimable = schedule.claimable_at(env.block.time);
+        schedule.claimed += claimable;
+        claimed += claimable;
+    }
+    if claimed == 0 {
+        return Err(StdError::generic_err("There are no vested tokens to claim"));
+    }
+    write_vesting_schedules(&mut deps.storage, &account, &schedules)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let balance = balances.balance(&account).checked_add(claimed).ok_or_else(|| {
+        StdError::generic_err("This claim would overflow your balance")
+    })?;
+    balances.set_account_balance(&account, balance);
+
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_vesting_claim(&mut deps.storage, &account, Uint128(claimed), symbol, &env.block)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClaimVested {
+            status: Success,
+            claimed: Uint128(claimed),
+        })?),
+    })
+}
+
+pub fn try_create_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    arbiter: Option<HumanAddr>,
+    amount: Uint128,
+    expires_at: u64,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    if expires_at <= env.block.time {
+        return Err(StdError::generic_err("An escrow must expire in the future"));
+    }
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot escrow zero tokens"));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&recipient)?;
+    let arbiter = match arbiter {
+        Some(arbiter) => Some(deps.api.canonical_address(&arbiter)?),
+        None => None,
+    };
+    check_not_frozen(&deps.storage, &sender, "sender")?;
+    // Refuse up front what the release would refuse anyway, rather than leave it refund-only
+    check_not_frozen(&deps.storage, &recipient, "recipient")?;
+    check_allowlisted(&deps.storage, &recipient)?;
+
+    // Escrowed funds leave the sender's balance but remain part of the total supply
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let sender_balance = balances.balance(&sender);
+    let sender_balance = sender_balance.checked_sub(amount.u128()).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "insufficient funds: balance={}, required={}",
+            sender_balance, amount
+        ))
+    })?;
+    balances.set_account_balance(&sender, sender_balance);
+
+    let escrow_id = add_escrow(
+        &mut deps.storage,
+        Escrow {
+            id: 0,
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            arbiter: arbiter.clone(),
+            amount: amount.u128(),
+            expires_at,
+            status: EscrowStatus::Pending,
+        },
+    )?;
+
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_escrow_create(
+        &mut deps.storage,
+        &sender,
+        &recipient,
+        arbiter.as_ref(),
+        amount,
+        symbol,
+        memo,
+        &env.block,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateEscrow {
+            status: Success,
+            escrow_id,
+        })?),
+    })
+}
+
+/// Releases a pending escrow to its recipient, or refunds it to its sender. The arbiter may do
+/// either at any time, while the sender may release at any time and refund once the escrow expired.
+pub fn try_settle_escrow<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    escrow_id: u64,
+    release: bool,
+    memo: Option<String>,
+) -> StdResult<HandleResponse> {
+    let mut escrow = read_escrow(&deps.storage, escrow_id)?;
+    if escrow.status != EscrowStatus::Pending {
+        return Err(StdError::generic_err("This escrow was already settled"));
+    }
+
+    let by = deps.api.canonical_address(&env.message.sender)?;
+    let is_arbiter = escrow.arbiter.as_ref() == Some(&by);
+    if !is_arbiter && by != escrow.sender {
+        return Err(StdError::generic_err(
+            "Only the sender or arbiter of this escrow can settle it",
+        ));
+    }
+    if !release && !is_arbiter && env.block.time < escrow.expires_at {
+        return Err(StdError::generic_err(format!(
+            "This escrow can't be refunded before it expires at {}",
+            escrow.expires_at
+        )));
+    }
+
+    let payee = if release {
+        check_not_frozen(&deps.storage, &escrow.recipient, "recipient")?;
+        check_allowlisted(&deps.storage, &escrow.recipient)?;
+        escrow.recipient.clone()
+    } else {
+        check_not_frozen(&deps.storage, &escrow.sender, "sender")?;
+        escrow.sender.clone()
+    };
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let payee_balance = balances.balance(&payee).checked_add(escrow.amount).ok_or_else(|| {
+        StdError::generic_err("This tx will literally make them too rich. Try transferring less")
+    })?;
+    balances.set_account_balance(&payee, payee_balance);
+
+    escrow.status = if release {
+        EscrowStatus::Released
+    } else {
+        EscrowStatus::Refunded
+    };
+    write_escrow(&mut deps.storage, &escrow)?;
+
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_escrow_settle(
+        &mut deps.storage,
+        &escrow.sender,
+        &escrow.recipient,
+        &by,
+        release,
+        Uint128(escrow.amount),
+        symbol,
+        memo,
+        &env.block,
+    )?;
+
+    let answer = if release {
+        HandleAnswer::ReleaseEscrow { status: Success }
+    } else {
+        HandleAnswer::RefundEscrow { status: Success }
     };
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&answer)?),
+    })
+}
+
+pub fn try_lock_htlc<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    amount: Uint128,
+    hashlock: Binary,
+    expires_at: u64,
+) -> StdResult<HandleResponse> {
+    if hashlock.as_slice().len() != 32 {
+        return Err(StdError::generic_err("The hashlock must be a 32 byte SHA-256 hash"));
+    }
+    if expires_at <= env.block.time {
+        return Err(StdError::generic_err("An HTLC must expire in the future"));
+    }
+    if amount.is_zero() {
+        return Err(StdError::generic_err("Cannot lock zero tokens"));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&recipient)?;
+    check_not_frozen(&deps.storage, &sender, "sender")?;
+    // Refuse up front what the redemption would refuse anyway, rather than leave it refund-only
+    check_not_frozen(&deps.storage, &recipient, "recipient")?;
+    check_allowlisted(&deps.storage, &recipient)?;
 
-    Ok(res)
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let sender_balance = balances.balance(&sender);
+    let sender_balance = sender_balance.checked_sub(amount.u128()).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "insufficient funds: balance={}, required={}",
+            sender_balance, amount
+        ))
+    })?;
+    balances.set_account_balance(&sender, sender_balance);
+
+    let htlc_id = add_htlc(
+        &mut deps.storage,
+        Htlc {
+            id: 0,
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            amount: amount.u128(),
+            hashlock,
+            expires_at,
+            preimage: None,
+            status: HtlcStatus::Locked,
+        },
+    )?;
+
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_htlc_lock(&mut deps.storage, &sender, &recipient, amount, symbol, &env.block)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::LockHtlc {
+            status: Success,
+            htlc_id,
+        })?),
+    })
 }
 
-pub fn perform_transfer<T: Storage>(
-    store: &mut T,
-    from: &CanonicalAddr,
-    to: &CanonicalAddr,
-    amount: u128,
-) -> StdResult<()> {
-    let mut balances = Balances::from_storage(store);
+/// Anyone holding the preimage can redeem an HTLC before it expires. The funds always go to the
+/// recipient, and the preimage becomes public for the counterparty chain.
+pub fn try_redeem_htlc<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    htlc_id: u64,
+    preimage: Binary,
+) -> StdResult<HandleResponse> {
+    let mut htlc = read_htlc(&deps.storage, htlc_id)?;
+    if htlc.status != HtlcStatus::Locked {
+        return Err(StdError::generic_err("This HTLC was already settled"));
+    }
+    if env.block.time >= htlc.expires_at {
+        return Err(StdError::generic_err("This HTLC has expired"));
+    }
+    if sha_256(preimage.as_slice()).as_ref() != htlc.hashlock.as_slice() {
+        return Err(StdError::generic_err("The preimage does not match the hashlock"));
+    }
 
-    let mut from_balance = balances.balance(from);
-    if let Some(new_from_balance) = from_balance.checked_sub(amount) {
-        from_balance = new_from_balance;
-    } else {
+    check_not_frozen(&deps.storage, &htlc.recipient, "recipient")?;
+    check_allowlisted(&deps.storage, &htlc.recipient)?;
+    credit_balance(&mut deps.storage, &htlc.recipient, htlc.amount)?;
+
+    htlc.preimage = Some(preimage.clone());
+    htlc.status = HtlcStatus::Redeemed;
+    write_htlc(&mut deps.storage, &htlc)?;
+
+    let by = deps.api.canonical_address(&env.message.sender)?;
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_htlc_settle(
+        &mut deps.storage,
+        &htlc.sender,
+        &htlc.recipient,
+        &by,
+        true,
+        Uint128(htlc.amount),
+        symbol,
+        &env.block,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RedeemHtlc {
+            status: Success,
+            preimage,
+        })?),
+    })
+}
+
+/// Returns an expired HTLC to its sender. Anyone can trigger the refund.
+pub fn try_refund_htlc<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    htlc_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut htlc = read_htlc(&deps.storage, htlc_id)?;
+    if htlc.status != HtlcStatus::Locked {
+        return Err(StdError::generic_err("This HTLC was already settled"));
+    }
+    if env.block.time < htlc.expires_at {
         return Err(StdError::generic_err(format!(
-            "insufficient funds: balance={}, required={}",
-            from_balance, amount
+            "This HTLC can't be refunded before it expires at {}",
+            htlc.expires_at
         )));
     }
-    balances.set_account_balance(from, from_balance);
 
-    let mut to_balance = balances.balance(to);
-    to_balance = to_balance.checked_add(amount).ok_or_else(|| {
+    check_not_frozen(&deps.storage, &htlc.sender, "sender")?;
+    credit_balance(&mut deps.storage, &htlc.sender, htlc.amount)?;
+
+    htlc.status = HtlcStatus::Refunded;
+    write_htlc(&mut deps.storage, &htlc)?;
+
+    let by = deps.api.canonical_address(&env.message.sender)?;
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_htlc_settle(
+        &mut deps.storage,
+        &htlc.sender,
+        &htlc.recipient,
+        &by,
+        false,
+        Uint128(htlc.amount),
+        symbol,
+        &env.block,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RefundHtlc { status: Success })?),
+    })
+}
+
+fn credit_balance<S: Storage>(
+    store: &mut S,
+    account: &CanonicalAddr,
+    amount: u128,
+) -> StdResult<()> {
+    let mut balances = Balances::from_storage(store);
+    let balance = balances.balance(account).checked_add(amount).ok_or_else(|| {
         StdError::generic_err("This tx will literally make them too rich. Try transferring less")
     })?;
-    balances.set_account_balance(to, to_balance);
-
+    balances.set_account_balance(account, balance);
     Ok(())
 }
 
-pub fn revoke_permit<S: Storage, A: Api, Q: Querier>(
+pub fn try_create_stream<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    permit_name: String,
+    recipient: HumanAddr,
+    rate_per_second: Uint128,
+    start: u64,
+    end: u64,
+    deposit: Uint128,
 ) -> StdResult<HandleResponse> {
-    RevokedPermits::revoke_permit(
+    if start < env.block.time {
+        return Err(StdError::generic_err("A stream can't start in the past"));
+    }
+    if end <= start {
+        return Err(StdError::generic_err("A stream must end after it starts"));
+    }
+    if rate_per_second.is_zero() {
+        return Err(StdError::generic_err("A stream must pay a positive rate"));
+    }
+    let expected = rate_per_second.u128().checked_mul((end - start) as u128);
+    if expected != Some(deposit.u128()) {
+        return Err(StdError::generic_err(
+            "The deposit must equal rate_per_second times the length of the stream",
+        ));
+    }
+
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let recipient = deps.api.canonical_address(&recipient)?;
+    if sender == recipient {
+        return Err(StdError::generic_err("A stream can't pay its own sender"));
+    }
+    check_not_frozen(&deps.storage, &sender, "sender")?;
+    // Refuse up front what withdrawing would refuse anyway, rather than strand the deposit
+    check_not_frozen(&deps.storage, &recipient, "recipient")?;
+    check_allowlisted(&deps.storage, &recipient)?;
+
+    let mut balances = Balances::from_storage(&mut deps.storage);
+    let sender_balance = balances.balance(&sender);
+    let sender_balance = sender_balance.checked_sub(deposit.u128()).ok_or_else(|| {
+        StdError::generic_err(format!(
+            "insufficient funds: balance={}, required={}",
+            sender_balance, deposit
+        ))
+    })?;
+    balances.set_account_balance(&sender, sender_balance);
+
+    let stream_id = add_stream(
         &mut deps.storage,
-        PREFIX_REVOKED_PERMITS,
-        &env.message.sender,
-        &permit_name,
-    );
+        Stream {
+            id: 0,
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            rate_per_second: rate_per_second.u128(),
+            start,
+            end,
+            deposit: deposit.u128(),
+            withdrawn: 0,
+            canceled_at: None,
+        },
+    )?;
+
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_stream_payment(
+        &mut deps.storage,
+        &sender,
+        &recipient,
+        true,
+        deposit,
+        symbol,
+        &env.block,
+    )?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::RevokePermit { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::CreateStream {
+            status: Success,
+            stream_id,
+        })?),
+    })
+}
+
+pub fn try_withdraw_from_stream<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stream_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut stream = read_stream(&deps.storage, stream_id)?;
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender != stream.recipient {
+        return Err(StdError::generic_err(
+            "Only the recipient of this stream can withdraw from it",
+        ));
+    }
+
+    let withdrawn = stream.withdrawable_at(env.block.time);
+    if withdrawn == 0 {
+        return Err(StdError::generic_err("There is nothing to withdraw from this stream yet"));
+    }
+    pay_from_stream(&mut deps.storage, &env, &mut stream, withdrawn)?;
+    write_stream(&mut deps.storage, &stream)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::WithdrawFromStream {
+            status: Success,
+            withdrawn: Uint128(withdrawn),
+        })?),
+    })
+}
+
+/// Stops a stream, paying the recipient what has streamed so far and refunding the rest to the
+/// sender. Either side can cancel while the stream is running. If the recipient can't receive
+/// tokens right now, what has streamed stays in the stream for them to withdraw later, so that the
+/// sender still gets the rest back.
+pub fn try_cancel_stream<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    stream_id: u64,
+) -> StdResult<HandleResponse> {
+    let mut stream = read_stream(&deps.storage, stream_id)?;
+    let by = deps.api.canonical_address(&env.message.sender)?;
+    if by != stream.sender && by != stream.recipient {
+        return Err(StdError::generic_err(
+            "Only the sender or recipient of this stream can cancel it",
+        ));
+    }
+    if stream.canceled_at.is_some() {
+        return Err(StdError::generic_err("This stream was already canceled"));
+    }
+    if env.block.time >= stream.end {
+        return Err(StdError::generic_err("This stream has already ended"));
+    }
+
+    check_not_frozen(&deps.storage, &stream.sender, "sender")?;
+
+    stream.canceled_at = Some(env.block.time);
+    let can_receive = check_not_frozen(&deps.storage, &stream.recipient, "recipient")
+        .and_then(|_| check_allowlisted(&deps.storage, &stream.recipient))
+        .is_ok();
+    let paid = if can_receive {
+        stream.withdrawable_at(env.block.time)
+    } else {
+        0
+    };
+    let refunded = stream.deposit - stream.streamed_at(env.block.time);
+    if paid > 0 {
+        pay_from_stream(&mut deps.storage, &env, &mut stream, paid)?;
+    }
+    write_stream(&mut deps.storage, &stream)?;
+
+    credit_balance(&mut deps.storage, &stream.sender, refunded)?;
+    let symbol = ReadonlyConfig::from_storage(&deps.storage).constants()?.symbol;
+    store_stream_cancel(
+        &mut deps.storage,
+        &stream.sender,
+        &stream.recipient,
+        &by,
+        Uint128(refunded),
+        symbol,
+        &env.block,
+    )?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CancelStream {
+            status: Success,
+            paid: Uint128(paid),
+            refunded: Uint128(refunded),
+        })?),
     })
 }
 
+fn pay_from_stream<S: Storage>(
+    store: &mut S,
+    env: &Env,
+    stream: &mut Stream,
+    amount: u128,
+) -> StdResult<()> {
+    check_not_frozen(&*store, &stream.recipient, "recipient")?;
+    check_allowlisted(&*store, &stream.recipient)?;
+    credit_balance(store, &stream.recipient, amount)?;
+    stream.withdrawn += amount;
+
+    let symbol = ReadonlyConfig::from_storage(&*store).constants()?.symbol;
+    store_stream_payment(
+        store,
+        &stream.sender,
+        &stream.recipient,
+        false,
+        Uint128(amount),
+        symbol,
+        &env.block,
+    )
+}
+
+/// Nominates `address` as the next admin. The current admin stays in place until
+/// the nominee calls `AcceptAdmin`.
 pub fn change_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -1011,14 +2816,117 @@ pub fn change_admin<S: Storage, A: Api, Q: Querier>(
 
     check_if_admin(&config, &env.message.sender)?;
 
+    config.set_pending_admin(Some(address))?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ChangeAdmin { status: Success })?),
+    })
+}
+
+pub fn accept_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    if config.pending_admin().as_ref() != Some(&env.message.sender) {
+        return Err(StdError::generic_err(
+            "Only the pending admin can accept the admin role",
+        ));
+    }
+
     let mut consts = config.constants()?;
-    consts.admin = address;
+    consts.admin = Some(env.message.sender);
     config.set_constants(&consts)?;
+    config.set_pending_admin(None)?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::ChangeAdmin { status: Success })?),
+        data: Some(to_binary(&HandleAnswer::AcceptAdmin { status: Success })?),
+    })
+}
+
+pub fn cancel_admin_transfer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    if config.pending_admin().is_none() {
+        return Err(StdError::generic_err("There is no pending admin transfer"));
+    }
+    config.set_pending_admin(None)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CancelAdminTransfer {
+            status: Success,
+        })?),
+    })
+}
+
+pub fn grant_role<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    role: Role,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    config.grant_role(role, address)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::GrantRole { status: Success })?),
+    })
+}
+
+pub fn revoke_role<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    role: Role,
+    address: HumanAddr,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    config.revoke_role(role, &address)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokeRole { status: Success })?),
+    })
+}
+
+/// Permanently removes the admin. Every admin command is disabled afterwards.
+pub fn renounce_admin<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> StdResult<HandleResponse> {
+    let mut config = Config::from_storage(&mut deps.storage);
+
+    check_if_admin(&config, &env.message.sender)?;
+
+    let mut consts = config.constants()?;
+    consts.admin = None;
+    config.set_constants(&consts)?;
+    config.set_pending_admin(None)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RenounceAdmin { status: Success })?),
     })
 }
 
@@ -1031,6 +2939,9 @@ pub fn try_mint_impl<S: Storage>(
     memo: Option<String>,
     block: &cosmwasm_std::BlockInfo,
 ) -> StdResult<()> {
+    check_not_frozen(storage, recipient, "recipient")?;
+    check_allowlisted(storage, recipient)?;
+
     let raw_amount = amount.u128();
 
     let mut balances = Balances::from_storage(storage);
@@ -1086,8 +2997,11 @@ pub fn try_mint<S: Storage, A: Api, Q: Querier>(
             "This mint attempt would increase the total supply above the supported maximum",
         ));
     }
+    check_max_supply(config.max_supply(), total_supply)?;
     config.set_total_supply(total_supply);
 
+    use_mint_capacity(&mut deps.storage, &env.message.sender, amount.u128(), &env.block)?;
+
     let minter = deps.api.canonical_address(&env.message.sender)?;
     let recipient = deps.api.canonical_address(&recipient)?;
     try_mint_impl(
@@ -1129,7 +3043,8 @@ pub fn try_batch_mint<S: Storage, A: Api, Q: Querier>(
         ));
     }
 
-    let mut total_supply = config.total_supply();
+    let original_total_supply = config.total_supply();
+    let mut total_supply = original_total_supply;
 
     // Quick loop to check that the total of amounts is valid
     for action in &actions {
@@ -1141,8 +3056,16 @@ pub fn try_batch_mint<S: Storage, A: Api, Q: Querier>(
             ));
         }
     }
+    check_max_supply(config.max_supply(), total_supply)?;
     config.set_total_supply(total_supply);
 
+    use_mint_capacity(
+        &mut deps.storage,
+        &env.message.sender,
+        total_supply - original_total_supply,
+        &env.block,
+    )?;
+
     let minter = deps.api.canonical_address(&env.message.sender)?;
     for action in actions {
         let recipient = deps.api.canonical_address(&action.recipient)?;
@@ -1166,6 +3089,17 @@ pub fn try_batch_mint<S: Storage, A: Api, Q: Querier>(
     Ok(res)
 }
 
+fn use_mint_capacity<S: Storage>(
+    storage: &mut S,
+    minter: &HumanAddr,
+    amount: u128,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let mut cap = read_minter_cap(storage, minter)?;
+    cap.consume(amount, block)?;
+    write_minter_cap(storage, minter, &cap)
+}
+
 pub fn try_set_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
@@ -1210,7 +3144,7 @@ pub fn set_contract_status<S: Storage, A: Api, Q: Querier>(
 ) -> StdResult<HandleResponse> {
     let mut config = Config::from_storage(&mut deps.storage);
 
-    check_if_admin(&config, &env.message.sender)?;
+    check_role(&config, Role::Pauser, &env.message.sender)?;
 
     config.set_contract_status(status_level);
 
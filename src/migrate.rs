@@ -0,0 +1,230 @@
+//! Versioned upgrades of the contract's stored state
+//!
+//! Every change to a stored layout bumps `CURRENT_SCHEMA_VERSION` and adds a step to
+//! `migrate_storage`. Singletons such as `Constants` are rewritten eagerly when the contract is
+//! migrated. Per-account records can't be enumerated in storage, so they are upgraded lazily by
+//! the `decode_*` helpers here, which understand every layout that has ever been written.
+
+use std::any::type_name;
+
+use cosmwasm_std::{CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
+use hermit_toolkit::storage::TypedStore;
+
+use serde::{Deserialize, Serialize};
+
+use crate::contract::validate_native_denoms;
+use crate::msg::{ExchangeRatio, MigrateMsg, NativeDenom, VestingTerms, DEFAULT_NATIVE_DENOM};
+use crate::state::{
+    Allowance, Config, Constants, VestingSchedule, KEY_CONSTANTS, PREFIX_CONFIG, PREFIX_VESTING,
+};
+
+pub const CURRENT_SCHEMA_VERSION: u32 = 5;
+
+/// Runs every migration step between the stored schema version and `CURRENT_SCHEMA_VERSION`.
+/// Returns the version the storage was migrated from.
+pub fn migrate_storage<S: Storage>(storage: &mut S, msg: &MigrateMsg) -> StdResult<u32> {
+    let from_version = Config::from_storage(storage).schema_version();
+    if from_version > CURRENT_SCHEMA_VERSION {
+        return Err(StdError::generic_err(format!(
+            "Cannot migrate from schema version {} to older version {}",
+            from_version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    let mut version = from_version;
+    while version < CURRENT_SCHEMA_VERSION {
+        match version {
+            1 => migrate_v1_to_v2(storage, msg)?,
+            2 => migrate_v2_to_v3(storage)?,
+            3 => migrate_v3_to_v4(),
+            4 => migrate_v4_to_v5(),
+            other => {
+                return Err(StdError::generic_err(format!(
+                    "No migration available from schema version {}",
+                    other
+                )))
+            }
+        }
+        version += 1;
+    }
+
+    Config::from_storage(storage).set_schema_version(CURRENT_SCHEMA_VERSION)?;
+
+    Ok(from_version)
+}
+
+// Version 1 -> 2: deposit and redeem moved from a hardcoded `ughm` denom to a configurable basket
+
+/// The layout of `Constants` before schema versioning was introduced
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConstantsV1 {
+    pub name: String,
+    pub admin: HumanAddr,
+    pub symbol: String,
+    pub decimals: u8,
+    pub prng_seed: Vec<u8>,
+    pub total_supply_is_public: bool,
+    pub deposit_is_enabled: bool,
+    pub redeem_is_enabled: bool,
+    pub mint_is_enabled: bool,
+    pub burn_is_enabled: bool,
+    pub contract_address: HumanAddr,
+}
+
+fn migrate_v1_to_v2<S: Storage>(storage: &mut S, msg: &MigrateMsg) -> StdResult<()> {
+    let old: ConstantsV1 = load_constants(storage)?;
+
+    // Version 1 deployments exchanged `ughm` for raw token units one to one
+    let native_denoms = msg.native_denoms.clone().unwrap_or_else(|| {
+        vec![NativeDenom {
+            denom: DEFAULT_NATIVE_DENOM.to_string(),
+            weight: ExchangeRatio::default(),
+        }]
+    });
+    validate_native_denoms(&native_denoms)?;
+
+    store_constants(
+        storage,
+        &ConstantsV2 {
+            name: old.name,
+            admin: old.admin,
+            symbol: old.symbol,
+            decimals: old.decimals,
+            prng_seed: old.prng_seed,
+            total_supply_is_public: old.total_supply_is_public,
+            deposit_is_enabled: old.deposit_is_enabled,
+            redeem_is_enabled: old.redeem_is_enabled,
+            mint_is_enabled: old.mint_is_enabled,
+            burn_is_enabled: old.burn_is_enabled,
+            native_denoms,
+            contract_address: old.contract_address,
+        },
+    )
+}
+
+// Version 2 -> 3: the admin can be renounced
+
+/// The layout of `Constants` at schema version 2
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ConstantsV2 {
+    pub name: String,
+    pub admin: HumanAddr,
+    pub symbol: String,
+    pub decimals: u8,
+    pub prng_seed: Vec<u8>,
+    pub total_supply_is_public: bool,
+    pub deposit_is_enabled: bool,
+    pub redeem_is_enabled: bool,
+    pub mint_is_enabled: bool,
+    pub burn_is_enabled: bool,
+    pub native_denoms: Vec<NativeDenom>,
+    pub contract_address: HumanAddr,
+}
+
+fn migrate_v2_to_v3<S: Storage>(storage: &mut S) -> StdResult<()> {
+    let old: ConstantsV2 = load_constants(storage)?;
+
+    Config::from_storage(storage).set_constants(&Constants {
+        name: old.name,
+        admin: Some(old.admin),
+        symbol: old.symbol,
+        decimals: old.decimals,
+        prng_seed: old.prng_seed,
+        total_supply_is_public: old.total_supply_is_public,
+        deposit_is_enabled: old.deposit_is_enabled,
+        redeem_is_enabled: old.redeem_is_enabled,
+        mint_is_enabled: old.mint_is_enabled,
+        burn_is_enabled: old.burn_is_enabled,
+        native_denoms: old.native_denoms,
+        contract_address: old.contract_address,
+    })
+}
+
+// Version 3 -> 4: allowances can limit spending per period
+
+/// Allowances are upgraded lazily by `decode_allowance`. Mandates store their own `amount` and
+/// `expiration` rather than an `Allowance`, so their layout is unchanged and nothing is rewritten.
+fn migrate_v3_to_v4() {}
+
+// Version 4 -> 5: vesting schedules record who funded them
+
+/// Vesting schedules moved to `PREFIX_VESTING_SCHEDULES` in their new layout, and those still under
+/// `PREFIX_VESTING` are upgraded lazily by `read_legacy_vesting_schedules`. A list of schedules in
+/// one layout could decode as the other, so the prefix tells them apart rather than the bytes.
+fn migrate_v4_to_v5() {}
+
+// Lazily upgraded records
+//
+// Transaction history records are unchanged since version 1, so `transaction_history` reads them as is.
+
+/// The layout of `Allowance` up to schema version 3
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AllowanceV1 {
+    pub amount: u128,
+    pub expiration: Option<u64>,
+}
+
+/// Decodes a stored `Allowance` of any layout. Older layouts are always shorter than the current
+/// one, so they never decode as the current layout by accident.
+pub fn decode_allowance(bytes: &[u8]) -> StdResult<Allowance> {
+    if let Ok(allowance) = bincode2::deserialize::<Allowance>(bytes) {
+        return Ok(allowance);
+    }
+
+    let old = bincode2::deserialize::<AllowanceV1>(bytes)
+        .map_err(|e| StdError::serialize_err(type_name::<Allowance>(), e))?;
+    Ok(Allowance {
+        amount: old.amount,
+        expiration: old.expiration,
+        period_limit: None,
+    })
+}
+
+/// The layout of `VestingSchedule` up to schema version 4
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VestingScheduleV1 {
+    pub amount: u128,
+    pub claimed: u128,
+    pub terms: VestingTerms,
+    pub revoked_at: Option<u64>,
+}
+
+/// Reads an account's vesting schedules as written up to schema version 4. Who funded them wasn't
+/// recorded back then.
+pub fn read_legacy_vesting_schedules<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Vec<VestingSchedule>> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_VESTING, storage);
+    let store = TypedStore::attach(&store);
+    let schedules: Vec<VestingScheduleV1> = store.may_load(account.as_slice())?.unwrap_or_default();
+    Ok(schedules
+        .into_iter()
+        .map(|old| VestingSchedule {
+            amount: old.amount,
+            claimed: old.claimed,
+            terms: old.terms,
+            revoked_at: old.revoked_at,
+            funder: None,
+        })
+        .collect())
+}
+
+// Helpers
+
+fn load_constants<T: serde::de::DeserializeOwned, S: ReadonlyStorage>(storage: &S) -> StdResult<T> {
+    let config = ReadonlyPrefixedStorage::new(PREFIX_CONFIG, storage);
+    let consts_bytes = config
+        .get(KEY_CONSTANTS)
+        .ok_or_else(|| StdError::generic_err("no constants stored in configuration"))?;
+    bincode2::deserialize::<T>(&consts_bytes).map_err(|e| StdError::serialize_err(type_name::<T>(), e))
+}
+
+fn store_constants<T: Serialize, S: Storage>(storage: &mut S, constants: &T) -> StdResult<()> {
+    let consts_bytes =
+        bincode2::serialize(constants).map_err(|e| StdError::serialize_err(type_name::<T>(), e))?;
+    let mut config = PrefixedStorage::new(PREFIX_CONFIG, storage);
+    config.set(KEY_CONSTANTS, &consts_bytes);
+    Ok(())
+}
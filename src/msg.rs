@@ -4,7 +4,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{Binary, HumanAddr, StdError, StdResult, Uint128};
-use hermit_toolkit::permit::Permit;
+use hermit_toolkit::permit::{Permit, PermitSignature};
 
 use crate::batch;
 use crate::tools::viewing_key::ViewingKey;
@@ -17,6 +17,30 @@ pub struct InitialBalance {
     pub amount: Uint128,
 }
 
+/// An initial allocation that is released to `address` over time
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct InitialVesting {
+    pub address: HumanAddr,
+    pub amount: Uint128,
+    pub terms: VestingTerms,
+}
+
+/// Nothing is released before `cliff_time`. From then on, the amount is released linearly between
+/// `start_time` and `end_time`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VestingTerms {
+    pub start_time: u64,
+    pub cliff_time: u64,
+    pub end_time: u64,
+    pub revocable: bool,
+}
+
+impl VestingTerms {
+    pub fn is_valid(&self) -> bool {
+        self.start_time <= self.cliff_time && self.cliff_time <= self.end_time
+    }
+}
+
 // 初始化消息结构体
 #[derive(Serialize,Deserialize,JsonSchema)]
 pub struct InitMsg {
@@ -25,6 +49,7 @@ pub struct InitMsg {
     pub symbol: String,
     pub decimals: u8,
     pub initial_balances: Option<Vec<InitialBalance>>,
+    pub initial_vesting: Option<Vec<InitialVesting>>,
     pub prng_seed: Binary,
     pub config: Option<InitConfig>,
 }
@@ -42,6 +67,13 @@ pub struct InitConfig {
     enable_redeem: Option<bool>,
     enable_mint: Option<bool>,
     enable_burn: Option<bool>,
+    native_denom: Option<String>,
+    exchange_ratio: Option<ExchangeRatio>,
+    native_denoms: Option<Vec<NativeDenom>>,
+    max_supply: Option<Uint128>,
+    enable_max_supply_increase: Option<bool>,
+    enable_allowlist: Option<bool>,
+    enable_force_transfer: Option<bool>,
 }
 
 impl InitConfig {
@@ -64,8 +96,109 @@ impl InitConfig {
     pub fn burn_enabled(&self) -> bool {
         self.enable_burn.unwrap_or(false)
     }
+
+    pub fn native_denom(&self) -> String {
+        self.native_denom
+            .clone()
+            .unwrap_or_else(|| DEFAULT_NATIVE_DENOM.to_string())
+    }
+
+    pub fn exchange_ratio(&self) -> ExchangeRatio {
+        self.exchange_ratio.clone().unwrap_or_default()
+    }
+
+    pub fn max_supply(&self) -> Option<u128> {
+        self.max_supply.map(|max_supply| max_supply.u128())
+    }
+
+    pub fn max_supply_increase_enabled(&self) -> bool {
+        self.enable_max_supply_increase.unwrap_or(false)
+    }
+
+    pub fn allowlist_enabled(&self) -> bool {
+        self.enable_allowlist.unwrap_or(false)
+    }
+
+    pub fn force_transfer_enabled(&self) -> bool {
+        self.enable_force_transfer.unwrap_or(false)
+    }
+
+    /// The deposit basket. Falls back to `native_denom` at `exchange_ratio` when
+    /// `native_denoms` is not set, and can't be combined with them.
+    pub fn native_denoms(&self) -> StdResult<Vec<NativeDenom>> {
+        match &self.native_denoms {
+            Some(_) if self.native_denom.is_some() || self.exchange_ratio.is_some() => {
+                Err(StdError::generic_err(
+                    "native_denoms can't be combined with native_denom or exchange_ratio",
+                ))
+            }
+            Some(native_denoms) => Ok(native_denoms.clone()),
+            None => Ok(vec![NativeDenom {
+                denom: self.native_denom(),
+                weight: self.exchange_ratio(),
+            }]),
+        }
+    }
+}
+
+/// A native denom accepted by deposit and redeem, weighted by its own exchange ratio.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct NativeDenom {
+    pub denom: String,
+    pub weight: ExchangeRatio,
+}
+
+pub const DEFAULT_NATIVE_DENOM: &str = "ughm";
+
+/// `native` raw units of the native denom are worth `token` raw units of this token.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct ExchangeRatio {
+    pub native: Uint128,
+    pub token: Uint128,
+}
+
+impl Default for ExchangeRatio {
+    fn default() -> Self {
+        Self {
+            native: Uint128(1),
+            token: Uint128(1),
+        }
+    }
+}
+
+impl ExchangeRatio {
+    pub fn is_valid(&self) -> bool {
+        !self.native.is_zero() && !self.token.is_zero()
+    }
+
+    /// Converts native units into token units.
+    /// Returns `None` if the result overflows or is not a whole number of token units.
+    pub fn native_to_token(&self, native_amount: u128) -> Option<u128> {
+        convert_exact(native_amount, self.token.u128(), self.native.u128())
+    }
+
+    /// Converts token units into native units.
+    /// Returns `None` if the result overflows or is not a whole number of native units.
+    pub fn token_to_native(&self, token_amount: u128) -> Option<u128> {
+        convert_exact(token_amount, self.native.u128(), self.token.u128())
+    }
+}
+
+fn convert_exact(amount: u128, numerator: u128, denominator: u128) -> Option<u128> {
+    let scaled = amount.checked_mul(numerator)?;
+    if denominator == 0 || scaled % denominator != 0 {
+        return None;
+    }
+    Some(scaled / denominator)
 }
 
+/// Only used when upgrading a deployment that predates configurable native denoms
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    /// Defaults to `ughm` at a 1:1 ratio, which is what those deployments used
+    pub native_denoms: Option<Vec<NativeDenom>>,
+}
 
 ///////////////////////////////////////////////////
 //                  Handle Msg
@@ -110,6 +243,61 @@ pub enum HandleMsg {
         memo: Option<String>,
         padding: Option<String>,
     },
+    ClaimVested {
+        padding: Option<String>,
+    },
+    CreateEscrow {
+        recipient: HumanAddr,
+        arbiter: Option<HumanAddr>,
+        amount: Uint128,
+        expires_at: u64,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    ReleaseEscrow {
+        escrow_id: u64,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    RefundEscrow {
+        escrow_id: u64,
+        memo: Option<String>,
+        padding: Option<String>,
+    },
+    /// `hashlock` is the SHA-256 hash of the preimage that unlocks the funds
+    LockHtlc {
+        recipient: HumanAddr,
+        amount: Uint128,
+        hashlock: Binary,
+        expires_at: u64,
+        padding: Option<String>,
+    },
+    RedeemHtlc {
+        htlc_id: u64,
+        preimage: Binary,
+        padding: Option<String>,
+    },
+    RefundHtlc {
+        htlc_id: u64,
+        padding: Option<String>,
+    },
+    /// `deposit` must equal `rate_per_second` times the length of the stream
+    CreateStream {
+        recipient: HumanAddr,
+        rate_per_second: Uint128,
+        start: u64,
+        end: u64,
+        deposit: Uint128,
+        padding: Option<String>,
+    },
+    WithdrawFromStream {
+        stream_id: u64,
+        padding: Option<String>,
+    },
+    CancelStream {
+        stream_id: u64,
+        padding: Option<String>,
+    },
     RegisterReceive {
         code_hash: String,
         padding: Option<String>,
@@ -124,10 +312,12 @@ pub enum HandleMsg {
     },
 
     // allowance
+    /// `period_limit` caps how much the spender may use per period, replacing any earlier limit
     IncreaseAllowance {
         spender: HumanAddr,
         amount: Uint128,
         expiration: Option<u64>,
+        period_limit: Option<SpendingLimit>,
         padding: Option<String>,
     },
     DecreaseAllowance {
@@ -136,6 +326,42 @@ pub enum HandleMsg {
         expiration: Option<u64>,
         padding: Option<String>,
     },
+    /// Replaces the amount and expiration of an allowance, keeping any period limit
+    SetAllowance {
+        spender: HumanAddr,
+        amount: Uint128,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    BatchSetAllowance {
+        actions: Vec<batch::SetAllowanceAction>,
+        padding: Option<String>,
+    },
+    /// Zeroes every allowance the sender has given since the allowance index was introduced, and
+    /// those to `spenders`. Older allowances are only known to the contract if listed in `spenders`.
+    RevokeAllAllowances {
+        spenders: Option<Vec<HumanAddr>>,
+        padding: Option<String>,
+    },
+    /// Authorizes `payee` to be paid `amount` every `period` seconds, starting at `first_due`
+    CreateMandate {
+        payee: HumanAddr,
+        amount: Uint128,
+        period: u64,
+        max_count: u32,
+        first_due: Option<u64>,
+        expiration: Option<u64>,
+        padding: Option<String>,
+    },
+    ExecuteMandate {
+        payer: HumanAddr,
+        payee: HumanAddr,
+        padding: Option<String>,
+    },
+    RevokeMandate {
+        payee: HumanAddr,
+        padding: Option<String>,
+    },
     TransferFrom {
         owner: HumanAddr,
         recipient: HumanAddr,
@@ -194,12 +420,108 @@ pub enum HandleMsg {
         minters: Vec<HumanAddr>,
         padding: Option<String>,
     },
+    SetMinterCap {
+        minter: HumanAddr,
+        lifetime_cap: Option<Uint128>,
+        window_cap: Option<Uint128>,
+        window_seconds: Option<u64>,
+        padding: Option<String>,
+    },
+    SetMaxSupply {
+        max_supply: Option<Uint128>,
+        padding: Option<String>,
+    },
+    AddToAllowlist {
+        addresses: Vec<HumanAddr>,
+        padding: Option<String>,
+    },
+    RemoveFromAllowlist {
+        addresses: Vec<HumanAddr>,
+        padding: Option<String>,
+    },
+    FreezeAccount {
+        address: HumanAddr,
+        reason: Option<String>,
+        padding: Option<String>,
+    },
+    UnfreezeAccount {
+        address: HumanAddr,
+        reason: Option<String>,
+        padding: Option<String>,
+    },
+    SetFeeConfig {
+        bps: u16,
+        min: Option<Uint128>,
+        max: Option<Uint128>,
+        collector: Option<HumanAddr>,
+        padding: Option<String>,
+    },
+    AddFeeExemptions {
+        addresses: Vec<HumanAddr>,
+        padding: Option<String>,
+    },
+    RemoveFeeExemptions {
+        addresses: Vec<HumanAddr>,
+        padding: Option<String>,
+    },
+    SetTokenMetadata {
+        name: Option<String>,
+        metadata: TokenMetadata,
+        padding: Option<String>,
+    },
+    UpdateConfig {
+        public_total_supply: Option<bool>,
+        enable_deposit: Option<bool>,
+        enable_redeem: Option<bool>,
+        enable_mint: Option<bool>,
+        enable_burn: Option<bool>,
+        /// Permanently freezes these features at their resulting setting
+        lock: Option<Vec<Feature>>,
+        padding: Option<String>,
+    },
 
     // admin
     ChangeAdmin {
         address: HumanAddr,
         padding: Option<String>,
     },
+    ForceTransfer {
+        from: HumanAddr,
+        to: HumanAddr,
+        amount: Uint128,
+        reason: String,
+        padding: Option<String>,
+    },
+    CreateVesting {
+        recipient: HumanAddr,
+        amount: Uint128,
+        terms: VestingTerms,
+        padding: Option<String>,
+    },
+    RevokeVesting {
+        account: HumanAddr,
+        index: u32,
+        padding: Option<String>,
+    },
+    AcceptAdmin {
+        padding: Option<String>,
+    },
+    CancelAdminTransfer {
+        padding: Option<String>,
+    },
+    RenounceAdmin {
+        padding: Option<String>,
+    },
+    GrantRole {
+        role: Role,
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    RevokeRole {
+        role: Role,
+        address: HumanAddr,
+        padding: Option<String>,
+    },
     SetContractStatus {
         level: ContractStatusLevel,
         padding: Option<String>,
@@ -210,6 +532,28 @@ pub enum HandleMsg {
         permit_name: String,
         padding: Option<String>,
     },
+    /// Narrows down what the sender's query permits named `permit_name` can read
+    SetPermitScope {
+        permit_name: String,
+        scope: PermitScope,
+        padding: Option<String>,
+    },
+    /// Invalidates every permit signed so far. Only permits whose name ends in `@<tag>`, for the
+    /// random tag in the answer, are valid afterwards. Before the first call, permit names aren't
+    /// checked.
+    RevokeAllPermits {
+        padding: Option<String>,
+    },
+    /// Transfers on behalf of the permit's signer, so anyone may relay it
+    TransferWithPermit {
+        permit: TransferPermit,
+        padding: Option<String>,
+    },
+    /// Sets an allowance on behalf of the permit's signer, so anyone may relay it
+    ApproveWithPermit {
+        permit: ApprovePermit,
+        padding: Option<String>,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
@@ -260,6 +604,30 @@ pub enum HandleAnswer {
         owner: HumanAddr,
         allowance: Uint128,
     },
+    SetAllowance {
+        allowance: AllowanceInfo,
+    },
+    BatchSetAllowance {
+        status: ResponseStatus,
+        allowances: Vec<AllowanceInfo>,
+    },
+    /// The zeroed allowances: the indexed ones and those to the requested `spenders`
+    RevokeAllAllowances {
+        status: ResponseStatus,
+        allowances: Vec<AllowanceInfo>,
+    },
+    CreateMandate {
+        status: ResponseStatus,
+    },
+    /// `installment` counts from 1
+    ExecuteMandate {
+        status: ResponseStatus,
+        installment: u32,
+        remaining: u32,
+    },
+    RevokeMandate {
+        status: ResponseStatus,
+    },
     TransferFrom {
         status: ResponseStatus,
     },
@@ -295,11 +663,109 @@ pub enum HandleAnswer {
     SetMinters {
         status: ResponseStatus,
     },
+    SetMinterCap {
+        status: ResponseStatus,
+    },
+    SetMaxSupply {
+        status: ResponseStatus,
+    },
+    AddToAllowlist {
+        status: ResponseStatus,
+    },
+    RemoveFromAllowlist {
+        status: ResponseStatus,
+    },
+    FreezeAccount {
+        status: ResponseStatus,
+    },
+    UnfreezeAccount {
+        status: ResponseStatus,
+    },
+    SetFeeConfig {
+        status: ResponseStatus,
+    },
+    AddFeeExemptions {
+        status: ResponseStatus,
+    },
+    RemoveFeeExemptions {
+        status: ResponseStatus,
+    },
+    SetTokenMetadata {
+        status: ResponseStatus,
+    },
+    UpdateConfig {
+        status: ResponseStatus,
+    },
 
     // other
     ChangeAdmin {
         status: ResponseStatus,
     },
+    ForceTransfer {
+        status: ResponseStatus,
+    },
+    CreateVesting {
+        status: ResponseStatus,
+    },
+    RevokeVesting {
+        status: ResponseStatus,
+        returned: Uint128,
+    },
+    ClaimVested {
+        status: ResponseStatus,
+        claimed: Uint128,
+    },
+    CreateEscrow {
+        status: ResponseStatus,
+        escrow_id: u64,
+    },
+    ReleaseEscrow {
+        status: ResponseStatus,
+    },
+    RefundEscrow {
+        status: ResponseStatus,
+    },
+    LockHtlc {
+        status: ResponseStatus,
+        htlc_id: u64,
+    },
+    RedeemHtlc {
+        status: ResponseStatus,
+        preimage: Binary,
+    },
+    RefundHtlc {
+        status: ResponseStatus,
+    },
+    CreateStream {
+        status: ResponseStatus,
+        stream_id: u64,
+    },
+    WithdrawFromStream {
+        status: ResponseStatus,
+        withdrawn: Uint128,
+    },
+    /// `paid` went to the recipient and `refunded` back to the sender. A recipient that couldn't
+    /// receive tokens is paid nothing and can withdraw what had streamed later.
+    CancelStream {
+        status: ResponseStatus,
+        paid: Uint128,
+        refunded: Uint128,
+    },
+    AcceptAdmin {
+        status: ResponseStatus,
+    },
+    CancelAdminTransfer {
+        status: ResponseStatus,
+    },
+    RenounceAdmin {
+        status: ResponseStatus,
+    },
+    GrantRole {
+        status: ResponseStatus,
+    },
+    RevokeRole {
+        status: ResponseStatus,
+    },
     SetContractStatus {
         status: ResponseStatus,
     },
@@ -308,6 +774,19 @@ pub enum HandleAnswer {
     RevokePermit {
         status: ResponseStatus,
     },
+    SetPermitScope {
+        status: ResponseStatus,
+    },
+    RevokeAllPermits {
+        status: ResponseStatus,
+        tag: String,
+    },
+    TransferWithPermit {
+        status: ResponseStatus,
+    },
+    ApproveWithPermit {
+        allowance: AllowanceInfo,
+    },
 }
 
 
@@ -318,7 +797,12 @@ pub enum HandleAnswer {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     TokenInfo {},
+    TokenMetadata {},
     TokenConfig {},
+    ConfigChanges {
+        page: Option<u32>,
+        page_size: u32,
+    },
     ContractStatus {},
     ExchangeRate {},
     Allowance {
@@ -326,10 +810,40 @@ pub enum QueryMsg {
         spender: HumanAddr,
         key: String,
     },
+    Mandate {
+        payer: HumanAddr,
+        payee: HumanAddr,
+        key: String,
+    },
+    AllowancesGiven {
+        owner: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+        time: Option<u64>,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+        time: Option<u64>,
+    },
     Balance {
         address: HumanAddr,
         key: String,
     },
+    /// The nonce the address' next transfer or approve permit must carry
+    PermitNonce {
+        address: HumanAddr,
+        key: String,
+    },
+    RevokedPermits {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
     TransferHistory {
         address: HumanAddr,
         key: String,
@@ -342,7 +856,42 @@ pub enum QueryMsg {
         page: Option<u32>,
         page_size: u32,
     },
+    VestingSchedule {
+        address: HumanAddr,
+        key: String,
+        time: Option<u64>,
+    },
+    Escrows {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Streams {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+        time: Option<u64>,
+    },
     Minters {},
+    MinterCap {
+        minter: HumanAddr,
+    },
+    MaxSupply {},
+    FeeConfig {},
+    Htlc {
+        htlc_id: u64,
+    },
+    IsFrozen {
+        address: HumanAddr,
+    },
+    Allowlist {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Admin {},
+    Roles {},
     WithPermit {
         permit: Permit,
         query: QueryWithPermit,
@@ -354,14 +903,26 @@ impl QueryMsg {
     pub fn get_validation_params(&self) -> (Vec<&HumanAddr>, ViewingKey) {
         match self {
             Self::Balance {address, key} => (vec![address], ViewingKey(key.clone())),
+            Self::PermitNonce {address, key} => (vec![address], ViewingKey(key.clone())),
+            Self::RevokedPermits {address, key, ..} => (vec![address], ViewingKey(key.clone())),
             Self::TransferHistory {address, key, ..} => (vec![address], ViewingKey(key.clone())),
             Self::TransactionHistory {address, key, ..} => (vec![address], ViewingKey(key.clone())),
+            Self::VestingSchedule {address, key, ..} => (vec![address], ViewingKey(key.clone())),
+            Self::Escrows {address, key, ..} => (vec![address], ViewingKey(key.clone())),
+            Self::Streams {address, key, ..} => (vec![address], ViewingKey(key.clone())),
             Self::Allowance {
                 owner,
                 spender,
                 key,
                 ..
             } => (vec![owner, spender], ViewingKey(key.clone())),
+            Self::Mandate {
+                payer,
+                payee,
+                key,
+            } => (vec![payer, payee], ViewingKey(key.clone())),
+            Self::AllowancesGiven {owner, key, ..} => (vec![owner], ViewingKey(key.clone())),
+            Self::AllowancesReceived {spender, key, ..} => (vec![spender], ViewingKey(key.clone())),
             _ => panic!("This query type does not require authentication")
         }
     }
@@ -370,6 +931,9 @@ impl QueryMsg {
 /////////////////////////////////////////
 //             permit
 /////////////////////////////////////////
+/// A query authorized by a permit. Once its owner used `RevokeAllPermits`, a permit is only valid if
+/// its `permit_name` ends in `@<tag>` for the owner's current permit tag, e.g.
+/// `my-permit@3f9a0c1d5e7b2a64`.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryWithPermit {
@@ -377,7 +941,28 @@ pub enum QueryWithPermit {
         owner: HumanAddr,
         spender: HumanAddr,
     },
+    Mandate {
+        payer: HumanAddr,
+        payee: HumanAddr,
+    },
+    AllowancesGiven {
+        owner: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+        time: Option<u64>,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        page: Option<u32>,
+        page_size: u32,
+        time: Option<u64>,
+    },
     Balance {},
+    PermitNonce {},
+    RevokedPermits {
+        page: Option<u32>,
+        page_size: u32,
+    },
     TransferHistory {
         page: Option<u32>,
         page_size: u32,
@@ -386,6 +971,105 @@ pub enum QueryWithPermit {
         page: Option<u32>,
         page_size: u32,
     },
+    VestingSchedule {
+        time: Option<u64>,
+    },
+    Escrows {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Streams {
+        page: Option<u32>,
+        page_size: u32,
+        time: Option<u64>,
+    },
+}
+
+/// Limits on a query permit, on top of its permissions. Queries can't see the block time, so a
+/// permit counts as expired once the contract handled a transaction at or after `expires_at`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitScope {
+    pub expires_at: Option<u64>,
+    /// Only transactions in this range are listed by history queries
+    pub history: Option<HistoryRange>,
+    /// Only allowances and mandates with these accounts can be queried
+    pub counterparties: Option<Vec<HumanAddr>>,
+}
+
+/// A range of transactions, by id and by block time, with inclusive bounds
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct HistoryRange {
+    pub from_id: Option<u64>,
+    pub to_id: Option<u64>,
+    pub from_time: Option<u64>,
+    pub to_time: Option<u64>,
+}
+
+impl HistoryRange {
+    /// A transaction without a block time is outside any time bounds
+    pub fn contains(&self, id: u64, block_time: Option<u64>) -> bool {
+        let id_in_range =
+            self.from_id.map_or(true, |from| id >= from) && self.to_id.map_or(true, |to| id <= to);
+        let time_in_range = match (self.from_time, self.to_time) {
+            (None, None) => true,
+            (from, to) => block_time.map_or(false, |time| {
+                from.map_or(true, |from| time >= from) && to.map_or(true, |to| time <= to)
+            }),
+        };
+        id_in_range && time_in_range
+    }
+}
+
+/// A transfer of `params.owner`'s tokens, signed off-chain the same way query permits are
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TransferPermit {
+    pub params: TransferPermitParams,
+    pub signature: PermitSignature,
+}
+
+/// The signed content of a transfer permit. `token` is the address of this contract, so a permit
+/// can't be replayed on another token. Fields are kept in alphabetical order, as signed documents
+/// are serialized with sorted keys.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TransferPermitParams {
+    pub amount: Uint128,
+    pub chain_id: String,
+    pub expires_at: u64,
+    pub memo: Option<String>,
+    pub nonce: u64,
+    pub owner: HumanAddr,
+    pub permit_name: String,
+    pub recipient: HumanAddr,
+    pub token: HumanAddr,
+}
+
+/// An allowance grant of `params.owner`, signed off-chain the same way query permits are
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ApprovePermit {
+    pub params: ApprovePermitParams,
+    pub signature: PermitSignature,
+}
+
+/// The signed content of an approve permit. `amount` and `expiration` replace those of the allowance,
+/// while `expires_at` is when the permit itself stops being usable. Fields are in alphabetical
+/// order, like those of `TransferPermitParams`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ApprovePermitParams {
+    pub amount: Uint128,
+    pub chain_id: String,
+    pub expiration: Option<u64>,
+    pub expires_at: u64,
+    pub nonce: u64,
+    pub owner: HumanAddr,
+    pub permit_name: String,
+    pub spender: HumanAddr,
+    pub token: HumanAddr,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
@@ -397,12 +1081,23 @@ pub enum QueryAnswer {
         decimals: u8,
         total_supply: Option<Uint128>,
     },
+    TokenMetadata {
+        name: String,
+        symbol: String,
+        decimals: u8,
+        metadata: TokenMetadata,
+    },
     TokenConfig {
         public_total_supply: bool,
         deposit_enabled: bool,
         redeem_enabled: bool,
         mint_enabled: bool,
         burn_enabled: bool,
+        locked_features: Vec<Feature>,
+    },
+    ConfigChanges {
+        changes: Vec<ConfigChange>,
+        total: u64,
     },
     ContractStatus {
         status: ContractStatusLevel,
@@ -410,16 +1105,42 @@ pub enum QueryAnswer {
     ExchangeRate {
         rate: Uint128,
         denom: String,
+        native_denoms: Vec<NativeDenom>,
     },
     Allowance {
         spender: HumanAddr,
         owner: HumanAddr,
         allowance: Uint128,
         expiration: Option<u64>,
+        period_limit: Option<PeriodLimitInfo>,
+    },
+    Mandate {
+        payer: HumanAddr,
+        payee: HumanAddr,
+        mandate: Option<MandateInfo>,
+    },
+    AllowancesGiven {
+        owner: HumanAddr,
+        allowances: Vec<AllowanceInfo>,
+        total: u64,
+    },
+    AllowancesReceived {
+        spender: HumanAddr,
+        allowances: Vec<AllowanceInfo>,
+        total: u64,
     },
     Balance {
         amount: Uint128,
     },
+    PermitNonce {
+        nonce: u64,
+    },
+    /// `tag` is the account's current permit tag, if it ever revoked all its permits
+    RevokedPermits {
+        permit_names: Vec<String>,
+        total: u64,
+        tag: Option<String>,
+    },
     TransferHistory {
         txs: Vec<Tx>,
         total: Option<u64>,
@@ -431,9 +1152,70 @@ pub enum QueryAnswer {
     ViewingKeyError {
         msg: String,
     },
+    VestingSchedule {
+        schedules: Vec<VestingInfo>,
+    },
+    Escrows {
+        escrows: Vec<EscrowInfo>,
+        total: u64,
+    },
+    Streams {
+        streams: Vec<StreamInfo>,
+        total: u64,
+    },
+    /// The parties and amount of an HTLC stay private, `preimage` is set once it was redeemed
+    Htlc {
+        htlc_id: u64,
+        hashlock: Binary,
+        expires_at: u64,
+        status: HtlcStatus,
+        preimage: Option<Binary>,
+    },
     Minters {
         minters: Vec<HumanAddr>,
     },
+    /// `window_remaining` is as of the last mint; the window is refilled from `window_resets_at`
+    MinterCap {
+        minter: HumanAddr,
+        lifetime_cap: Option<Uint128>,
+        lifetime_minted: Uint128,
+        lifetime_remaining: Option<Uint128>,
+        window_cap: Option<Uint128>,
+        window_seconds: Option<u64>,
+        window_minted: Uint128,
+        window_remaining: Option<Uint128>,
+        window_resets_at: Option<u64>,
+    },
+    IsFrozen {
+        frozen: bool,
+    },
+    Allowlist {
+        enabled: bool,
+        addresses: Vec<HumanAddr>,
+        total: u32,
+    },
+    /// A `None` collector means fees are burned
+    FeeConfig {
+        bps: u16,
+        min: Uint128,
+        max: Option<Uint128>,
+        collector: Option<HumanAddr>,
+        exempt: Vec<HumanAddr>,
+    },
+    /// `remaining` is only reported when the total supply is public
+    MaxSupply {
+        max_supply: Option<Uint128>,
+        can_increase: bool,
+        remaining: Option<Uint128>,
+    },
+    Admin {
+        admin: Option<HumanAddr>,
+        pending_admin: Option<HumanAddr>,
+    },
+    Roles {
+        admin: Option<HumanAddr>,
+        roles: Vec<RoleMembers>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
@@ -456,6 +1238,196 @@ pub enum ContractStatusLevel {
     StopAll,
 }
 
+/// Administrative roles. The admin implicitly holds all of them.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// May change the contract status
+    Pauser,
+    /// May add, remove and set minters
+    MinterManager,
+    /// May change the token configuration
+    ConfigManager,
+    /// May change the fee configuration
+    FeeManager,
+    /// May freeze and unfreeze accounts
+    ComplianceManager,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [
+        Role::Pauser,
+        Role::MinterManager,
+        Role::ConfigManager,
+        Role::FeeManager,
+        Role::ComplianceManager,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Pauser => "pauser",
+            Role::MinterManager => "minter_manager",
+            Role::ConfigManager => "config_manager",
+            Role::FeeManager => "fee_manager",
+            Role::ComplianceManager => "compliance_manager",
+        }
+    }
+}
+
+/// A vesting schedule as reported by the `vesting_schedule` query. `vested` and `claimable` are only
+/// reported when the query asks for a `time`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct VestingInfo {
+    pub index: u32,
+    pub amount: Uint128,
+    pub claimed: Uint128,
+    pub terms: VestingTerms,
+    pub revoked_at: Option<u64>,
+    pub vested: Option<Uint128>,
+    pub claimable: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EscrowStatus {
+    Pending,
+    Released,
+    Refunded,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum HtlcStatus {
+    Locked,
+    Redeemed,
+    Refunded,
+}
+
+/// An escrow as reported by the `escrows` query
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct EscrowInfo {
+    pub id: u64,
+    pub sender: HumanAddr,
+    pub recipient: HumanAddr,
+    pub arbiter: Option<HumanAddr>,
+    pub amount: Uint128,
+    pub expires_at: u64,
+    pub status: EscrowStatus,
+}
+
+/// An allowance as listed by the `allowances_given` and `allowances_received` queries, or as left by
+/// the allowance setting messages. Queries only report `expired` when asked for a `time`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllowanceInfo {
+    pub owner: HumanAddr,
+    pub spender: HumanAddr,
+    pub allowance: Uint128,
+    pub expiration: Option<u64>,
+    pub expired: Option<bool>,
+    pub period_limit: Option<PeriodLimitInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SpendingLimit {
+    pub limit: Uint128,
+    pub period_seconds: u64,
+}
+
+/// The per-period limit of an allowance. `spent` is as of the last spend, and is reset at `resets_at`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PeriodLimitInfo {
+    pub limit: Uint128,
+    pub period_seconds: u64,
+    pub spent: Uint128,
+    pub resets_at: u64,
+}
+
+/// A recurring payment mandate as reported by the `mandate` query
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MandateInfo {
+    pub amount: Uint128,
+    pub period: u64,
+    pub max_count: u32,
+    pub executed: u32,
+    pub next_due: u64,
+    pub expiration: Option<u64>,
+}
+
+/// A stream as reported by the `streams` query. `streamed` and `withdrawable` are only reported when
+/// the query asks for a `time`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct StreamInfo {
+    pub id: u64,
+    pub sender: HumanAddr,
+    pub recipient: HumanAddr,
+    pub rate_per_second: Uint128,
+    pub start: u64,
+    pub end: u64,
+    pub deposit: Uint128,
+    pub withdrawn: Uint128,
+    pub canceled_at: Option<u64>,
+    pub streamed: Option<Uint128>,
+    pub withdrawable: Option<Uint128>,
+}
+
+/// Descriptive token information for wallets and explorers
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, JsonSchema, Debug)]
+pub struct TokenMetadata {
+    pub description: Option<String>,
+    pub project_url: Option<String>,
+    pub logo: Option<Logo>,
+    pub marketing_contact: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Logo {
+    /// A link to the logo image
+    Url(String),
+    /// A small image stored on chain
+    Embedded(Binary),
+}
+
+/// Token features that can be switched on and off after init
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    PublicTotalSupply,
+    Deposit,
+    Redeem,
+    Mint,
+    Burn,
+}
+
+impl Feature {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::PublicTotalSupply => "public_total_supply",
+            Feature::Deposit => "deposit",
+            Feature::Redeem => "redeem",
+            Feature::Mint => "mint",
+            Feature::Burn => "burn",
+        }
+    }
+}
+
+/// An entry of the config-change audit log
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ConfigChange {
+    pub feature: Feature,
+    pub enabled: bool,
+    pub locked: bool,
+    pub changed_by: HumanAddr,
+    pub block_time: u64,
+    pub block_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoleMembers {
+    pub role: Role,
+    pub members: Vec<HumanAddr>,
+}
+
 pub fn status_level_to_u8(status_level: ContractStatusLevel) -> u8 {
     match status_level {
         ContractStatusLevel::NormalRun => 0,
@@ -1,7 +1,7 @@
 
 use cosmwasm_std::{Api, Binary, Extern, HumanAddr, Querier, QueryResult, ReadonlyStorage, StdResult, Storage, to_binary, Uint128};
-use crate::msg::{QueryAnswer, QueryMsg};
-use crate::state::{read_allowance, read_viewing_key, ReadonlyBalances, ReadonlyConfig};
+use crate::msg::{AllowanceInfo, EscrowInfo, HistoryRange, MandateInfo, QueryAnswer, QueryMsg, Role, RoleMembers, StreamInfo, VestingInfo};
+use crate::state::{ALLOWANCES_GIVEN, ALLOWANCES_RECEIVED, get_allowance_counterparties, get_allowlist, get_config_changes, get_escrows, get_revoked_permit_names, get_streams, is_frozen, PeriodLimit, read_allowance, read_htlc, read_mandate, read_minter_cap, read_permit_nonce, read_permit_tag, read_vesting_schedules, read_viewing_key, ReadonlyBalances, ReadonlyConfig};
 use crate::tools::viewing_key::VIEWING_KEY_SIZE;
 use crate::transaction_history::{get_transfers, get_txs};
 
@@ -24,19 +24,57 @@ pub fn viewing_keys_queries<S: Storage, A: Api, Q: Querier>(
             return match msg {
                 // Base
                 QueryMsg::Balance { address, .. } => query_balance(deps, &address),
+                QueryMsg::PermitNonce { address, .. } => query_permit_nonce(deps, &address),
+                QueryMsg::RevokedPermits {
+                    address,
+                    page,
+                    page_size,
+                    ..
+                } => query_revoked_permits(deps, &address, page.unwrap_or(0), page_size),
                 QueryMsg::TransferHistory {
                     address,
                     page,
                     page_size,
                     ..
-                } => query_transfers(deps, &address, page.unwrap_or(0), page_size),
+                } => query_transfers(deps, &address, page.unwrap_or(0), page_size, None),
                 QueryMsg::TransactionHistory {
                     address,
                     page,
                     page_size,
                     ..
-                } => query_transactions(deps, &address, page.unwrap_or(0), page_size),
+                } => query_transactions(deps, &address, page.unwrap_or(0), page_size, None),
                 QueryMsg::Allowance { owner, spender, .. } => query_allowance(deps, owner, spender),
+                QueryMsg::Mandate { payer, payee, .. } => query_mandate(deps, payer, payee),
+                QueryMsg::AllowancesGiven {
+                    owner,
+                    page,
+                    page_size,
+                    time,
+                    ..
+                } => query_allowances(deps, owner, true, page.unwrap_or(0), page_size, time),
+                QueryMsg::AllowancesReceived {
+                    spender,
+                    page,
+                    page_size,
+                    time,
+                    ..
+                } => query_allowances(deps, spender, false, page.unwrap_or(0), page_size, time),
+                QueryMsg::VestingSchedule { address, time, .. } => {
+                    query_vesting_schedule(deps, &address, time)
+                }
+                QueryMsg::Escrows {
+                    address,
+                    page,
+                    page_size,
+                    ..
+                } => query_escrows(deps, &address, page.unwrap_or(0), page_size),
+                QueryMsg::Streams {
+                    address,
+                    page,
+                    page_size,
+                    time,
+                    ..
+                } => query_streams(deps, &address, page.unwrap_or(0), page_size, time),
                 _ => panic!("This query type does not require authentication"),
             };
         }
@@ -52,22 +90,32 @@ pub fn query_exchange_rate<S: ReadonlyStorage>(storage: &S) -> QueryResult {
     let constants = config.constants()?;
 
     if constants.deposit_is_enabled || constants.redeem_is_enabled {
-        let rate: Uint128;
-        let denom: String;
-        // if token has more decimals than SCRT, you get magnitudes of SCRT per token
-        if constants.decimals >= 6 {
-            rate = Uint128(10u128.pow(constants.decimals as u32 - 6));
-            denom = "SCRT".to_string();
-            // if token has less decimals, you get magnitudes token for SCRT
-        } else {
-            rate = Uint128(10u128.pow(6 - constants.decimals as u32));
-            denom = constants.symbol;
+        // `rate` and `denom` describe the default denom, the full basket is in `native_denoms`
+        if let Some(primary) = constants.native_denoms.first() {
+            let native = primary.weight.native.u128();
+            let token = primary.weight.token.u128();
+            let rate: Uint128;
+            let denom: String;
+            // if a native unit is worth more than a token unit, you get magnitudes of tokens per native unit
+            if token >= native {
+                rate = Uint128(token / native);
+                denom = primary.denom.clone();
+                // otherwise, you get magnitudes of native units per token unit
+            } else {
+                rate = Uint128(native / token);
+                denom = constants.symbol.clone();
+            }
+            return to_binary(&QueryAnswer::ExchangeRate {
+                rate,
+                denom,
+                native_denoms: constants.native_denoms,
+            });
         }
-        return to_binary(&QueryAnswer::ExchangeRate { rate, denom });
     }
     to_binary(&QueryAnswer::ExchangeRate {
         rate: Uint128(0),
         denom: String::new(),
+        native_denoms: vec![],
     })
 }
 
@@ -89,6 +137,18 @@ pub fn query_token_info<S: ReadonlyStorage>(storage: &S) -> QueryResult {
     })
 }
 
+pub fn query_token_metadata<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let config = ReadonlyConfig::from_storage(storage);
+    let constants = config.constants()?;
+
+    to_binary(&QueryAnswer::TokenMetadata {
+        name: constants.name,
+        symbol: constants.symbol,
+        decimals: constants.decimals,
+        metadata: config.token_metadata(),
+    })
+}
+
 pub fn query_token_config<S: ReadonlyStorage>(storage: &S) -> QueryResult {
     let config = ReadonlyConfig::from_storage(storage);
     let constants = config.constants()?;
@@ -99,9 +159,16 @@ pub fn query_token_config<S: ReadonlyStorage>(storage: &S) -> QueryResult {
         redeem_enabled: constants.redeem_is_enabled,
         mint_enabled: constants.mint_is_enabled,
         burn_enabled: constants.burn_is_enabled,
+        locked_features: config.locked_features(),
     })
 }
 
+pub fn query_config_changes<S: ReadonlyStorage>(storage: &S, page: u32, page_size: u32) -> QueryResult {
+    let (changes, total) = get_config_changes(storage, page, page_size)?;
+
+    to_binary(&QueryAnswer::ConfigChanges { changes, total })
+}
+
 pub fn query_contract_status<S: ReadonlyStorage>(storage: &S) -> QueryResult {
     let config = ReadonlyConfig::from_storage(storage);
 
@@ -115,14 +182,21 @@ pub fn query_transfers<S: Storage, A: Api, Q: Querier>(
     account: &HumanAddr,
     page: u32,
     page_size: u32,
+    range: Option<&HistoryRange>,
 ) -> StdResult<Binary> {
     let address = deps.api.canonical_address(account)?;
-    let (txs, total) = get_transfers(&deps.api, &deps.storage, &address, page, page_size)?;
+    let (mut txs, total) = get_transfers(&deps.api, &deps.storage, &address, page, page_size)?;
 
-    let result = QueryAnswer::TransferHistory {
-        txs,
-        total: Some(total),
+    // A permit limited to a range only sees the part of each page within it, and not how many
+    // transactions there are in total
+    let total = match range {
+        Some(range) => {
+            txs.retain(|tx| range.contains(tx.id, tx.block_time));
+            None
+        }
+        None => Some(total),
     };
+    let result = QueryAnswer::TransferHistory { txs, total };
     to_binary(&result)
 }
 
@@ -131,14 +205,19 @@ pub fn query_transactions<S: Storage, A: Api, Q: Querier>(
     account: &HumanAddr,
     page: u32,
     page_size: u32,
+    range: Option<&HistoryRange>,
 ) -> StdResult<Binary> {
     let address = deps.api.canonical_address(account)?;
-    let (txs, total) = get_txs(&deps.api, &deps.storage, &address, page, page_size)?;
+    let (mut txs, total) = get_txs(&deps.api, &deps.storage, &address, page, page_size)?;
 
-    let result = QueryAnswer::TransactionHistory {
-        txs,
-        total: Some(total),
+    let total = match range {
+        Some(range) => {
+            txs.retain(|tx| range.contains(tx.id, Some(tx.block_time)));
+            None
+        }
+        None => Some(total),
     };
+    let result = QueryAnswer::TransactionHistory { txs, total };
     to_binary(&result)
 }
 
@@ -153,6 +232,126 @@ pub fn query_balance<S: Storage, A: Api, Q: Querier>(
     to_binary(&response)
 }
 
+pub fn query_permit_nonce<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+
+    let nonce = read_permit_nonce(&deps.storage, &address)?;
+    to_binary(&QueryAnswer::PermitNonce { nonce })
+}
+
+pub fn query_revoked_permits<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let (permit_names, total) = get_revoked_permit_names(&deps.storage, account, page, page_size)?;
+    let tag = read_permit_tag(&deps.storage, account)?;
+
+    to_binary(&QueryAnswer::RevokedPermits {
+        permit_names,
+        total,
+        tag,
+    })
+}
+
+pub fn query_vesting_schedule<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    time: Option<u64>,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+
+    // Queries don't know the block time, so vested amounts are only computed when one is given
+    let schedules = read_vesting_schedules(&deps.storage, &address)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, schedule)| VestingInfo {
+            index: index as u32,
+            amount: Uint128(schedule.amount),
+            claimed: Uint128(schedule.claimed),
+            vested: time.map(|time| Uint128(schedule.vested_at(time))),
+            claimable: time.map(|time| Uint128(schedule.claimable_at(time))),
+            terms: schedule.terms,
+            revoked_at: schedule.revoked_at,
+        })
+        .collect();
+
+    to_binary(&QueryAnswer::VestingSchedule { schedules })
+}
+
+pub fn query_escrows<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+    let (escrows, total) = get_escrows(&deps.storage, &address, page, page_size)?;
+
+    let escrows: StdResult<Vec<EscrowInfo>> = escrows
+        .into_iter()
+        .map(|escrow| {
+            Ok(EscrowInfo {
+                id: escrow.id,
+                sender: deps.api.human_address(&escrow.sender)?,
+                recipient: deps.api.human_address(&escrow.recipient)?,
+                arbiter: match escrow.arbiter {
+                    Some(arbiter) => Some(deps.api.human_address(&arbiter)?),
+                    None => None,
+                },
+                amount: Uint128(escrow.amount),
+                expires_at: escrow.expires_at,
+                status: escrow.status,
+            })
+        })
+        .collect();
+
+    to_binary(&QueryAnswer::Escrows {
+        escrows: escrows?,
+        total,
+    })
+}
+
+pub fn query_streams<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+    time: Option<u64>,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(account)?;
+    let (streams, total) = get_streams(&deps.storage, &address, page, page_size)?;
+
+    // Like vesting schedules, the streamed amounts can only be computed for a given time
+    let streams: StdResult<Vec<StreamInfo>> = streams
+        .into_iter()
+        .map(|stream| {
+            Ok(StreamInfo {
+                id: stream.id,
+                sender: deps.api.human_address(&stream.sender)?,
+                recipient: deps.api.human_address(&stream.recipient)?,
+                rate_per_second: Uint128(stream.rate_per_second),
+                start: stream.start,
+                end: stream.end,
+                deposit: Uint128(stream.deposit),
+                withdrawn: Uint128(stream.withdrawn),
+                canceled_at: stream.canceled_at,
+                streamed: time.map(|time| Uint128(stream.streamed_at(time))),
+                withdrawable: time.map(|time| Uint128(stream.withdrawable_at(time))),
+            })
+        })
+        .collect();
+
+    to_binary(&QueryAnswer::Streams {
+        streams: streams?,
+        total,
+    })
+}
+
 pub fn query_minters<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> StdResult<Binary> {
     let minters = ReadonlyConfig::from_storage(&deps.storage).minters();
 
@@ -160,6 +359,137 @@ pub fn query_minters<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) ->
     to_binary(&response)
 }
 
+pub fn query_minter_cap<S: ReadonlyStorage>(storage: &S, minter: HumanAddr) -> QueryResult {
+    let cap = read_minter_cap(storage, &minter)?;
+    let has_window = cap.window_cap.is_some();
+
+    to_binary(&QueryAnswer::MinterCap {
+        lifetime_cap: cap.lifetime_cap.map(Uint128),
+        lifetime_minted: Uint128(cap.lifetime_minted),
+        lifetime_remaining: cap.lifetime_remaining().map(Uint128),
+        window_cap: cap.window_cap.map(Uint128),
+        window_seconds: if has_window { Some(cap.window_seconds) } else { None },
+        window_minted: Uint128(cap.window_minted),
+        window_remaining: cap.window_remaining().map(Uint128),
+        window_resets_at: if has_window {
+            Some(cap.window_start.saturating_add(cap.window_seconds))
+        } else {
+            None
+        },
+        minter,
+    })
+}
+
+pub fn query_max_supply<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let config = ReadonlyConfig::from_storage(storage);
+    let constants = config.constants()?;
+    let max_supply = config.max_supply();
+
+    let remaining = if constants.total_supply_is_public {
+        max_supply.map(|max_supply| Uint128(max_supply.saturating_sub(config.total_supply())))
+    } else {
+        None
+    };
+
+    to_binary(&QueryAnswer::MaxSupply {
+        max_supply: max_supply.map(Uint128),
+        can_increase: config.max_supply_can_increase(),
+        remaining,
+    })
+}
+
+pub fn query_fee_config<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult {
+    let config = ReadonlyConfig::from_storage(&deps.storage);
+    let fee_config = config.fee_config();
+
+    let collector = match fee_config.collector {
+        Some(collector) => Some(deps.api.human_address(&collector)?),
+        None => None,
+    };
+    let exempt: StdResult<Vec<HumanAddr>> = config
+        .fee_exempt()
+        .iter()
+        .map(|address| deps.api.human_address(address))
+        .collect();
+
+    to_binary(&QueryAnswer::FeeConfig {
+        bps: fee_config.bps,
+        min: Uint128(fee_config.min),
+        max: fee_config.max.map(Uint128),
+        collector,
+        exempt: exempt?,
+    })
+}
+
+pub fn query_htlc<S: ReadonlyStorage>(storage: &S, htlc_id: u64) -> QueryResult {
+    let htlc = read_htlc(storage, htlc_id)?;
+
+    to_binary(&QueryAnswer::Htlc {
+        htlc_id,
+        hashlock: htlc.hashlock,
+        expires_at: htlc.expires_at,
+        status: htlc.status,
+        preimage: htlc.preimage,
+    })
+}
+
+pub fn query_is_frozen<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> QueryResult {
+    let account = deps.api.canonical_address(address)?;
+
+    to_binary(&QueryAnswer::IsFrozen {
+        frozen: is_frozen(&deps.storage, &account),
+    })
+}
+
+pub fn query_allowlist<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    page: u32,
+    page_size: u32,
+) -> QueryResult {
+    let (accounts, total) = get_allowlist(&deps.storage, page, page_size)?;
+    let addresses: StdResult<Vec<HumanAddr>> = accounts
+        .iter()
+        .map(|account| deps.api.human_address(account))
+        .collect();
+
+    to_binary(&QueryAnswer::Allowlist {
+        enabled: ReadonlyConfig::from_storage(&deps.storage).allowlist_enabled(),
+        addresses: addresses?,
+        total,
+    })
+}
+
+pub fn query_admin<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let config = ReadonlyConfig::from_storage(storage);
+    let constants = config.constants()?;
+
+    to_binary(&QueryAnswer::Admin {
+        admin: constants.admin,
+        pending_admin: config.pending_admin(),
+    })
+}
+
+pub fn query_roles<S: ReadonlyStorage>(storage: &S) -> QueryResult {
+    let config = ReadonlyConfig::from_storage(storage);
+    let constants = config.constants()?;
+
+    let roles = Role::ALL
+        .iter()
+        .map(|role| RoleMembers {
+            role: *role,
+            members: config.role_members(*role),
+        })
+        .collect();
+
+    to_binary(&QueryAnswer::Roles {
+        admin: constants.admin,
+        roles,
+    })
+}
+
 pub fn query_allowance<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     owner: HumanAddr,
@@ -175,6 +505,88 @@ pub fn query_allowance<S: Storage, A: Api, Q: Querier>(
         spender,
         allowance: Uint128(allowance.amount),
         expiration: allowance.expiration,
+        period_limit: allowance.period_limit.as_ref().map(PeriodLimit::info),
     };
     to_binary(&response)
-}
\ No newline at end of file
+}
+
+pub fn query_mandate<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    payer: HumanAddr,
+    payee: HumanAddr,
+) -> StdResult<Binary> {
+    let payer_address = deps.api.canonical_address(&payer)?;
+    let payee_address = deps.api.canonical_address(&payee)?;
+
+    let mandate = read_mandate(&deps.storage, &payer_address, &payee_address)?.map(|mandate| {
+        MandateInfo {
+            amount: Uint128(mandate.amount),
+            period: mandate.period,
+            max_count: mandate.max_count,
+            executed: mandate.executed,
+            next_due: mandate.next_due,
+            expiration: mandate.expiration,
+        }
+    });
+
+    to_binary(&QueryAnswer::Mandate {
+        payer,
+        payee,
+        mandate,
+    })
+}
+
+/// Lists the allowances `account` has given when `given` is set, or else those it has received
+pub fn query_allowances<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    account: HumanAddr,
+    given: bool,
+    page: u32,
+    page_size: u32,
+    time: Option<u64>,
+) -> StdResult<Binary> {
+    let address = deps.api.canonical_address(&account)?;
+    let direction = if given {
+        ALLOWANCES_GIVEN
+    } else {
+        ALLOWANCES_RECEIVED
+    };
+    let (counterparties, total) =
+        get_allowance_counterparties(&deps.storage, direction, &address, page, page_size)?;
+
+    let allowances: StdResult<Vec<AllowanceInfo>> = counterparties
+        .iter()
+        .map(|counterparty| {
+            let (owner, spender) = if given {
+                (&address, counterparty)
+            } else {
+                (counterparty, &address)
+            };
+            let allowance = read_allowance(&deps.storage, owner, spender)?;
+            Ok(AllowanceInfo {
+                owner: deps.api.human_address(owner)?,
+                spender: deps.api.human_address(spender)?,
+                allowance: Uint128(allowance.amount),
+                // Same as `Allowance::is_expired_at`, for a time instead of a block
+                expired: time.map(|time| allowance.expiration.map_or(false, |exp| time >= exp)),
+                expiration: allowance.expiration,
+                period_limit: allowance.period_limit.as_ref().map(PeriodLimit::info),
+            })
+        })
+        .collect();
+
+    let response = if given {
+        QueryAnswer::AllowancesGiven {
+            owner: account,
+            allowances: allowances?,
+            total,
+        }
+    } else {
+        QueryAnswer::AllowancesReceived {
+            spender: account,
+            allowances: allowances?,
+            total,
+        }
+    };
+    to_binary(&response)
+}
@@ -1,16 +1,17 @@
 use std::any::type_name;
 use std::convert::TryFrom;
 
-use cosmwasm_std::{CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage};
+use cosmwasm_std::{Binary, CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128};
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
 
-use hermit_toolkit::storage::{TypedStore, TypedStoreMut};
+use hermit_toolkit::storage::{AppendStore, AppendStoreMut, TypedStore, TypedStoreMut};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::msg::{status_level_to_u8, u8_to_status_level, ContractStatusLevel};
+use crate::msg::{status_level_to_u8, u8_to_status_level, ConfigChange, ContractStatusLevel, EscrowStatus, Feature, HtlcStatus, NativeDenom, PeriodLimitInfo, PermitScope, Role, TokenMetadata, VestingTerms};
 use serde::de::DeserializeOwned;
+use crate::migrate::{decode_allowance, read_legacy_vesting_schedules};
 use crate::tools::viewing_key::ViewingKey;
 
 pub static CONFIG_KEY: &[u8] = b"config";
@@ -21,19 +22,47 @@ pub const KEY_TOTAL_SUPPLY: &[u8] = b"total_supply";
 pub const KEY_CONTRACT_STATUS: &[u8] = b"contract_status";
 pub const KEY_MINTERS: &[u8] = b"minters";
 pub const KEY_TX_COUNT: &[u8] = b"tx-count";
+pub const KEY_SCHEMA_VERSION: &[u8] = b"schema-version";
+pub const KEY_PENDING_ADMIN: &[u8] = b"pending-admin";
+pub const KEY_ROLES: &[u8] = b"roles";
+pub const KEY_MAX_SUPPLY: &[u8] = b"max-supply";
+pub const KEY_MAX_SUPPLY_CAN_INCREASE: &[u8] = b"max-supply-can-increase";
+pub const KEY_LOCKED_FEATURES: &[u8] = b"locked-features";
+pub const KEY_TOKEN_METADATA: &[u8] = b"token-metadata";
+pub const KEY_FEE_CONFIG: &[u8] = b"fee-config";
+pub const KEY_FEE_EXEMPT: &[u8] = b"fee-exempt";
+pub const KEY_ALLOWLIST_ENABLED: &[u8] = b"allowlist-enabled";
+pub const KEY_FORCE_TRANSFER_ENABLED: &[u8] = b"force-transfer-enabled";
+pub const KEY_BLOCK_TIME: &[u8] = b"block-time";
 
 pub const PREFIX_CONFIG: &[u8] = b"config";
 pub const PREFIX_BALANCES: &[u8] = b"balances";
 pub const PREFIX_ALLOWANCES: &[u8] = b"allowances";
+pub const PREFIX_ALLOWANCE_INDEX: &[u8] = b"allowance-index";
 pub const PREFIX_VIEW_KEY: &[u8] = b"viewingkey";
 pub const PREFIX_RECEIVERS: &[u8] = b"receivers";
+pub const PREFIX_MINTER_CAPS: &[u8] = b"minter-caps";
+pub const PREFIX_CONFIG_CHANGES: &[u8] = b"config-changes";
+pub const PREFIX_FROZEN: &[u8] = b"frozen";
+pub const PREFIX_ALLOWLIST: &[u8] = b"allowlist";
+pub const PREFIX_VESTING: &[u8] = b"vesting";
+pub const PREFIX_VESTING_SCHEDULES: &[u8] = b"vesting-schedules";
+pub const PREFIX_ESCROWS: &[u8] = b"escrows";
+pub const PREFIX_HTLCS: &[u8] = b"htlcs";
+pub const PREFIX_STREAMS: &[u8] = b"streams";
+pub const PREFIX_MANDATES: &[u8] = b"mandates";
+pub const PREFIX_PERMIT_NONCES: &[u8] = b"permit-nonces";
+pub const PREFIX_PERMIT_TAGS: &[u8] = b"permit-tags";
+pub const PREFIX_REVOKED_PERMIT_NAMES: &[u8] = b"revoked-permit-names";
+pub const PREFIX_PERMIT_SCOPES: &[u8] = b"permit-scopes";
 
 // Config
 
 #[derive(Serialize, Debug, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct Constants {
     pub name: String,
-    pub admin: HumanAddr,
+    // `None` once the admin has been renounced
+    pub admin: Option<HumanAddr>,
     pub symbol: String,
     pub decimals: u8,
     pub prng_seed: Vec<u8>,
@@ -47,10 +76,70 @@ pub struct Constants {
     pub mint_is_enabled: bool,
     // is burn enabled
     pub burn_is_enabled: bool,
+    // the native denoms accepted by deposit and paid out by redeem, the first one is the default
+    pub native_denoms: Vec<NativeDenom>,
     // the address of this contract, used to validate query permits
     pub contract_address: HumanAddr,
 }
 
+impl Constants {
+    pub fn native_denom(&self, denom: &str) -> Option<&NativeDenom> {
+        self.native_denoms.iter().find(|native| native.denom == denom)
+    }
+
+    pub fn feature_enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::PublicTotalSupply => self.total_supply_is_public,
+            Feature::Deposit => self.deposit_is_enabled,
+            Feature::Redeem => self.redeem_is_enabled,
+            Feature::Mint => self.mint_is_enabled,
+            Feature::Burn => self.burn_is_enabled,
+        }
+    }
+
+    pub fn set_feature_enabled(&mut self, feature: Feature, enabled: bool) {
+        let flag = match feature {
+            Feature::PublicTotalSupply => &mut self.total_supply_is_public,
+            Feature::Deposit => &mut self.deposit_is_enabled,
+            Feature::Redeem => &mut self.redeem_is_enabled,
+            Feature::Mint => &mut self.mint_is_enabled,
+            Feature::Burn => &mut self.burn_is_enabled,
+        };
+        *flag = enabled;
+    }
+}
+
+/// The fee taken from transfers and sends. Without a collector the fee is burned.
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Default)]
+pub struct FeeConfig {
+    pub bps: u16,
+    pub min: u128,
+    pub max: Option<u128>,
+    pub collector: Option<CanonicalAddr>,
+}
+
+impl FeeConfig {
+    pub const MAX_BPS: u16 = 10_000;
+
+    /// The fee for transferring `amount`, never more than `amount` itself
+    pub fn fee_for(&self, amount: u128) -> u128 {
+        if amount == 0 {
+            return 0;
+        }
+        let mut fee = amount
+            .checked_mul(self.bps as u128)
+            .map(|product| product / Self::MAX_BPS as u128)
+            // An overflow here means amount is so large that the product doesn't fit,
+            // dividing first keeps the result within one unit of the exact fee
+            .unwrap_or_else(|| amount / Self::MAX_BPS as u128 * self.bps as u128);
+        fee = fee.max(self.min);
+        if let Some(max) = self.max {
+            fee = fee.min(max);
+        }
+        fee.min(amount)
+    }
+}
+
 pub struct ReadonlyConfig<'a, S: ReadonlyStorage> {
     storage: ReadonlyPrefixedStorage<'a, S>,
 }
@@ -85,6 +174,54 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfig<'a, S> {
     pub fn tx_count(&self) -> u64 {
         self.as_readonly().tx_count()
     }
+
+    pub fn schema_version(&self) -> u32 {
+        self.as_readonly().schema_version()
+    }
+
+    pub fn pending_admin(&self) -> Option<HumanAddr> {
+        self.as_readonly().pending_admin()
+    }
+
+    pub fn role_members(&self, role: Role) -> Vec<HumanAddr> {
+        self.as_readonly().role_members(role)
+    }
+
+    pub fn max_supply(&self) -> Option<u128> {
+        self.as_readonly().max_supply()
+    }
+
+    pub fn max_supply_can_increase(&self) -> bool {
+        self.as_readonly().max_supply_can_increase()
+    }
+
+    pub fn locked_features(&self) -> Vec<Feature> {
+        self.as_readonly().locked_features()
+    }
+
+    pub fn token_metadata(&self) -> TokenMetadata {
+        self.as_readonly().token_metadata()
+    }
+
+    pub fn fee_config(&self) -> FeeConfig {
+        self.as_readonly().fee_config()
+    }
+
+    pub fn fee_exempt(&self) -> Vec<CanonicalAddr> {
+        self.as_readonly().fee_exempt()
+    }
+
+    pub fn allowlist_enabled(&self) -> bool {
+        self.as_readonly().allowlist_enabled()
+    }
+
+    pub fn force_transfer_enabled(&self) -> bool {
+        self.as_readonly().force_transfer_enabled()
+    }
+
+    pub fn block_time(&self) -> u64 {
+        self.as_readonly().block_time()
+    }
 }
 
 fn ser_bin_data<T: Serialize>(obj: &T) -> StdResult<Vec<u8>> {
@@ -184,6 +321,115 @@ impl<'a, S: Storage> Config<'a, S> {
     pub fn set_tx_count(&mut self, count: u64) -> StdResult<()> {
         set_bin_data(&mut self.storage, KEY_TX_COUNT, &count)
     }
+
+    pub fn schema_version(&self) -> u32 {
+        self.as_readonly().schema_version()
+    }
+
+    pub fn set_schema_version(&mut self, version: u32) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_SCHEMA_VERSION, &version)
+    }
+
+    pub fn pending_admin(&self) -> Option<HumanAddr> {
+        self.as_readonly().pending_admin()
+    }
+
+    pub fn set_pending_admin(&mut self, pending_admin: Option<HumanAddr>) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_PENDING_ADMIN, &pending_admin)
+    }
+
+    pub fn role_members(&self, role: Role) -> Vec<HumanAddr> {
+        self.as_readonly().role_members(role)
+    }
+
+    pub fn grant_role(&mut self, role: Role, account: HumanAddr) -> StdResult<()> {
+        let mut members = self.role_members(role);
+        if !members.contains(&account) {
+            members.push(account);
+        }
+
+        set_bin_data(&mut self.storage, &role_key(role), &members)
+    }
+
+    pub fn revoke_role(&mut self, role: Role, account: &HumanAddr) -> StdResult<()> {
+        let mut members = self.role_members(role);
+        members.retain(|x| x != account);
+
+        set_bin_data(&mut self.storage, &role_key(role), &members)
+    }
+
+    pub fn max_supply(&self) -> Option<u128> {
+        self.as_readonly().max_supply()
+    }
+
+    pub fn set_max_supply(&mut self, max_supply: Option<u128>) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_MAX_SUPPLY, &max_supply)
+    }
+
+    pub fn max_supply_can_increase(&self) -> bool {
+        self.as_readonly().max_supply_can_increase()
+    }
+
+    pub fn set_max_supply_can_increase(&mut self, can_increase: bool) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_MAX_SUPPLY_CAN_INCREASE, &can_increase)
+    }
+
+    pub fn locked_features(&self) -> Vec<Feature> {
+        self.as_readonly().locked_features()
+    }
+
+    pub fn set_locked_features(&mut self, locked: &[Feature]) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_LOCKED_FEATURES, &locked)
+    }
+
+    pub fn token_metadata(&self) -> TokenMetadata {
+        self.as_readonly().token_metadata()
+    }
+
+    pub fn set_token_metadata(&mut self, metadata: &TokenMetadata) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_TOKEN_METADATA, metadata)
+    }
+
+    pub fn fee_config(&self) -> FeeConfig {
+        self.as_readonly().fee_config()
+    }
+
+    pub fn set_fee_config(&mut self, fee_config: &FeeConfig) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_FEE_CONFIG, fee_config)
+    }
+
+    pub fn fee_exempt(&self) -> Vec<CanonicalAddr> {
+        self.as_readonly().fee_exempt()
+    }
+
+    pub fn set_fee_exempt(&mut self, exempt: &[CanonicalAddr]) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_FEE_EXEMPT, &exempt)
+    }
+
+    pub fn allowlist_enabled(&self) -> bool {
+        self.as_readonly().allowlist_enabled()
+    }
+
+    pub fn set_allowlist_enabled(&mut self, enabled: bool) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_ALLOWLIST_ENABLED, &enabled)
+    }
+
+    pub fn force_transfer_enabled(&self) -> bool {
+        self.as_readonly().force_transfer_enabled()
+    }
+
+    pub fn set_force_transfer_enabled(&mut self, enabled: bool) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_FORCE_TRANSFER_ENABLED, &enabled)
+    }
+
+    pub fn block_time(&self) -> u64 {
+        self.as_readonly().block_time()
+    }
+
+    /// Queries can't see the block time, so the time of the latest handled block stands in for it
+    pub fn set_block_time(&mut self, time: u64) -> StdResult<()> {
+        set_bin_data(&mut self.storage, KEY_BLOCK_TIME, &time)
+    }
 }
 
 /// This struct refactors out the readonly methods that we need for `Config` and `ReadonlyConfig`
@@ -230,6 +476,60 @@ impl<'a, S: ReadonlyStorage> ReadonlyConfigImpl<'a, S> {
     pub fn tx_count(&self) -> u64 {
         get_bin_data(self.0, KEY_TX_COUNT).unwrap_or_default()
     }
+
+    /// Deployments that predate schema versioning have no version stored, which makes them version 1
+    pub fn schema_version(&self) -> u32 {
+        get_bin_data(self.0, KEY_SCHEMA_VERSION).unwrap_or(1)
+    }
+
+    pub fn pending_admin(&self) -> Option<HumanAddr> {
+        get_bin_data(self.0, KEY_PENDING_ADMIN).unwrap_or_default()
+    }
+
+    fn role_members(&self, role: Role) -> Vec<HumanAddr> {
+        get_bin_data(self.0, &role_key(role)).unwrap_or_default()
+    }
+
+    /// Deployments that predate the cap have no max supply stored, which leaves the supply uncapped
+    fn max_supply(&self) -> Option<u128> {
+        get_bin_data(self.0, KEY_MAX_SUPPLY).unwrap_or_default()
+    }
+
+    fn max_supply_can_increase(&self) -> bool {
+        get_bin_data(self.0, KEY_MAX_SUPPLY_CAN_INCREASE).unwrap_or_default()
+    }
+
+    fn locked_features(&self) -> Vec<Feature> {
+        get_bin_data(self.0, KEY_LOCKED_FEATURES).unwrap_or_default()
+    }
+
+    fn token_metadata(&self) -> TokenMetadata {
+        get_bin_data(self.0, KEY_TOKEN_METADATA).unwrap_or_default()
+    }
+
+    fn fee_config(&self) -> FeeConfig {
+        get_bin_data(self.0, KEY_FEE_CONFIG).unwrap_or_default()
+    }
+
+    fn fee_exempt(&self) -> Vec<CanonicalAddr> {
+        get_bin_data(self.0, KEY_FEE_EXEMPT).unwrap_or_default()
+    }
+
+    fn allowlist_enabled(&self) -> bool {
+        get_bin_data(self.0, KEY_ALLOWLIST_ENABLED).unwrap_or_default()
+    }
+
+    fn force_transfer_enabled(&self) -> bool {
+        get_bin_data(self.0, KEY_FORCE_TRANSFER_ENABLED).unwrap_or_default()
+    }
+
+    fn block_time(&self) -> u64 {
+        get_bin_data(self.0, KEY_BLOCK_TIME).unwrap_or_default()
+    }
+}
+
+fn role_key(role: Role) -> Vec<u8> {
+    [KEY_ROLES, role.as_str().as_bytes()].concat()
 }
 
 // Balances
@@ -303,6 +603,7 @@ impl<'a, S: ReadonlyStorage> ReadonlyBalancesImpl<'a, S> {
 pub struct Allowance {
     pub amount: u128,
     pub expiration: Option<u64>,
+    pub period_limit: Option<PeriodLimit>,
 }
 
 impl Allowance {
@@ -314,6 +615,51 @@ impl Allowance {
     }
 }
 
+/// Caps how much of an allowance can be spent within each period of `period_seconds`, on top of
+/// the allowance's amount. `spent` is what was spent in the period starting at `period_start`.
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct PeriodLimit {
+    pub limit: u128,
+    pub period_seconds: u64,
+    pub period_start: u64,
+    pub spent: u128,
+}
+
+impl PeriodLimit {
+    /// How the limit is reported by queries and answers
+    pub fn info(&self) -> PeriodLimitInfo {
+        PeriodLimitInfo {
+            limit: Uint128(self.limit),
+            period_seconds: self.period_seconds,
+            spent: Uint128(self.spent),
+            resets_at: self.period_start.saturating_add(self.period_seconds),
+        }
+    }
+
+    pub fn period_is_over_at(&self, block: &cosmwasm_std::BlockInfo) -> bool {
+        block.time >= self.period_start.saturating_add(self.period_seconds)
+    }
+
+    /// Records `amount` as spent at `block`, failing if it exceeds the limit of the current period
+    pub fn consume(&mut self, amount: u128, block: &cosmwasm_std::BlockInfo) -> StdResult<()> {
+        if self.period_is_over_at(block) {
+            self.period_start = block.time;
+            self.spent = 0;
+        }
+
+        let remaining = self.limit.saturating_sub(self.spent);
+        if amount > remaining {
+            return Err(StdError::generic_err(format!(
+                "This exceeds the allowance's limit for the current period: remaining={}, required={}",
+                remaining, amount
+            )));
+        }
+
+        self.spent += amount;
+        Ok(())
+    }
+}
+
 pub fn read_allowance<S: Storage>(
     store: &S,
     owner: &CanonicalAddr,
@@ -321,9 +667,11 @@ pub fn read_allowance<S: Storage>(
 ) -> StdResult<Allowance> {
     let owner_store =
         ReadonlyPrefixedStorage::multilevel(&[PREFIX_ALLOWANCES, owner.as_slice()], store);
-    let owner_store = TypedStore::attach(&owner_store);
-    let allowance = owner_store.may_load(spender.as_slice());
-    allowance.map(Option::unwrap_or_default)
+    match owner_store.get(spender.as_slice()) {
+        // Allowances can't be enumerated, so older layouts are upgraded as they are read
+        Some(allowance_bytes) => decode_allowance(&allowance_bytes),
+        None => Ok(Allowance::default()),
+    }
 }
 
 pub fn write_allowance<S: Storage>(
@@ -332,6 +680,15 @@ pub fn write_allowance<S: Storage>(
     spender: &CanonicalAddr,
     allowance: Allowance,
 ) -> StdResult<()> {
+    // Only live allowances are indexed, so nobody can clutter another account's index for free
+    if allowance.amount == 0 {
+        unindex_allowance(store, ALLOWANCES_GIVEN, owner, spender)?;
+        unindex_allowance(store, ALLOWANCES_RECEIVED, spender, owner)?;
+    } else {
+        index_allowance(store, ALLOWANCES_GIVEN, owner, spender)?;
+        index_allowance(store, ALLOWANCES_RECEIVED, spender, owner)?;
+    }
+
     let mut owner_store =
         PrefixedStorage::multilevel(&[PREFIX_ALLOWANCES, owner.as_slice()], store);
     let mut owner_store = TypedStoreMut::attach(&mut owner_store);
@@ -339,6 +696,868 @@ pub fn write_allowance<S: Storage>(
     owner_store.store(spender.as_slice(), &allowance)
 }
 
+// Allowance index
+//
+// Every account keeps a list of the accounts it has given non-zero allowances to, and of those it
+// has received them from. Entries are appended when an allowance becomes non-zero, and removed
+// when it drops to zero by moving the last entry into their place. Allowances written before the
+// index existed are only listed once they are written again.
+
+pub const ALLOWANCES_GIVEN: &[u8] = b"given";
+pub const ALLOWANCES_RECEIVED: &[u8] = b"received";
+const ALLOWANCE_INDEX_MEMBERS: &[u8] = b"members";
+const ALLOWANCE_INDEX_LIST: &[u8] = b"list";
+
+/// The position of `counterparty` in the `direction` list of `account`, if it is listed
+fn allowance_index_position<S: ReadonlyStorage>(
+    store: &S,
+    direction: &[u8],
+    account: &CanonicalAddr,
+    counterparty: &CanonicalAddr,
+) -> Option<u32> {
+    let members = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_ALLOWANCE_INDEX, direction, ALLOWANCE_INDEX_MEMBERS, account.as_slice()],
+        store,
+    );
+    get_bin_data(&members, counterparty.as_slice()).ok()
+}
+
+fn set_allowance_index_position<S: Storage>(
+    store: &mut S,
+    direction: &[u8],
+    account: &CanonicalAddr,
+    counterparty: &CanonicalAddr,
+    position: Option<u32>,
+) -> StdResult<()> {
+    let mut members = PrefixedStorage::multilevel(
+        &[PREFIX_ALLOWANCE_INDEX, direction, ALLOWANCE_INDEX_MEMBERS, account.as_slice()],
+        store,
+    );
+    match position {
+        Some(position) => set_bin_data(&mut members, counterparty.as_slice(), &position),
+        None => {
+            members.remove(counterparty.as_slice());
+            Ok(())
+        }
+    }
+}
+
+fn index_allowance<S: Storage>(
+    store: &mut S,
+    direction: &[u8],
+    account: &CanonicalAddr,
+    counterparty: &CanonicalAddr,
+) -> StdResult<()> {
+    if allowance_index_position(&*store, direction, account, counterparty).is_some() {
+        return Ok(());
+    }
+
+    let position = {
+        let mut list = PrefixedStorage::multilevel(
+            &[PREFIX_ALLOWANCE_INDEX, direction, ALLOWANCE_INDEX_LIST, account.as_slice()],
+            store,
+        );
+        let mut list = AppendStoreMut::attach_or_create(&mut list)?;
+        list.push(counterparty)?;
+        list.len() - 1
+    };
+    set_allowance_index_position(store, direction, account, counterparty, Some(position))
+}
+
+fn unindex_allowance<S: Storage>(
+    store: &mut S,
+    direction: &[u8],
+    account: &CanonicalAddr,
+    counterparty: &CanonicalAddr,
+) -> StdResult<()> {
+    let position = match allowance_index_position(&*store, direction, account, counterparty) {
+        Some(position) => position,
+        None => return Ok(()),
+    };
+
+    let moved = {
+        let mut list = PrefixedStorage::multilevel(
+            &[PREFIX_ALLOWANCE_INDEX, direction, ALLOWANCE_INDEX_LIST, account.as_slice()],
+            store,
+        );
+        let mut list = AppendStoreMut::<CanonicalAddr, _, _>::attach_or_create(&mut list)?;
+        let last = list.pop()?;
+        if position < list.len() {
+            list.set_at(position, &last)?;
+            Some(last)
+        } else {
+            None
+        }
+    };
+    if let Some(moved) = moved {
+        set_allowance_index_position(store, direction, account, &moved, Some(position))?;
+    }
+    set_allowance_index_position(store, direction, account, counterparty, None)
+}
+
+/// Returns a page of the counterparties of `account` in the `direction` index, latest first, and
+/// how many there are
+pub fn get_allowance_counterparties<S: ReadonlyStorage>(
+    storage: &S,
+    direction: &[u8],
+    account: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<CanonicalAddr>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_ALLOWANCE_INDEX, direction, ALLOWANCE_INDEX_LIST, account.as_slice()],
+        storage,
+    );
+    let store = AppendStore::<CanonicalAddr, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let counterparties: StdResult<Vec<CanonicalAddr>> = store
+        .iter()
+        .rev()
+        .skip((page * page_size) as _)
+        .take(page_size as _)
+        .collect();
+    counterparties.map(|counterparties| (counterparties, store.len() as u64))
+}
+
+// Recurring payment mandates
+
+/// A standing authorization for `payee` to be paid `amount` every `period` seconds, at most
+/// `max_count` times and never at or after `expiration`
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct Mandate {
+    pub amount: u128,
+    pub expiration: Option<u64>,
+    pub period: u64,
+    pub max_count: u32,
+    pub executed: u32,
+    pub next_due: u64,
+}
+
+impl Mandate {
+    /// Checks that the next installment may be paid at `block`
+    pub fn check_due_at(&self, block: &cosmwasm_std::BlockInfo) -> StdResult<()> {
+        if self.executed >= self.max_count {
+            return Err(StdError::generic_err("This mandate has no installments left"));
+        }
+        if matches!(self.expiration, Some(time) if block.time >= time) {
+            return Err(StdError::generic_err("This mandate has expired"));
+        }
+        if block.time < self.next_due {
+            return Err(StdError::generic_err(format!(
+                "The next installment is not due until {}",
+                self.next_due
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub fn read_mandate<S: ReadonlyStorage>(
+    store: &S,
+    payer: &CanonicalAddr,
+    payee: &CanonicalAddr,
+) -> StdResult<Option<Mandate>> {
+    let payer_store =
+        ReadonlyPrefixedStorage::multilevel(&[PREFIX_MANDATES, payer.as_slice()], store);
+    let payer_store = TypedStore::attach(&payer_store);
+    payer_store.may_load(payee.as_slice())
+}
+
+pub fn write_mandate<S: Storage>(
+    store: &mut S,
+    payer: &CanonicalAddr,
+    payee: &CanonicalAddr,
+    mandate: &Mandate,
+) -> StdResult<()> {
+    let mut payer_store =
+        PrefixedStorage::multilevel(&[PREFIX_MANDATES, payer.as_slice()], store);
+    let mut payer_store = TypedStoreMut::attach(&mut payer_store);
+    payer_store.store(payee.as_slice(), mandate)
+}
+
+pub fn remove_mandate<S: Storage>(store: &mut S, payer: &CanonicalAddr, payee: &CanonicalAddr) {
+    let mut payer_store =
+        PrefixedStorage::multilevel(&[PREFIX_MANDATES, payer.as_slice()], store);
+    payer_store.remove(payee.as_slice());
+}
+
+// Transfer permit nonces
+//
+// A transfer permit must carry its owner's current nonce, which is then bumped, so that every
+// permit can be used at most once and only in the order they were signed.
+
+pub fn read_permit_nonce<S: ReadonlyStorage>(store: &S, owner: &CanonicalAddr) -> StdResult<u64> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_PERMIT_NONCES, store);
+    let store = TypedStore::attach(&store);
+    let nonce = store.may_load(owner.as_slice());
+    nonce.map(Option::unwrap_or_default)
+}
+
+pub fn write_permit_nonce<S: Storage>(
+    store: &mut S,
+    owner: &CanonicalAddr,
+    nonce: u64,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_PERMIT_NONCES, store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(owner.as_slice(), &nonce)
+}
+
+// Permit revocations and scopes
+//
+// Revoking all of an account's permits draws a new random permit tag for it. Permits can't be told
+// apart by when they were signed, so a permit is tied to a tag by its name instead: once an
+// account has a tag, only names ending in `@<tag>` are valid. The tag can't be guessed before it's
+// drawn, so no permit signed earlier can carry it. Until then every name is valid, so that permits
+// signed before tags existed keep working whatever their names end in.
+
+pub fn read_permit_tag<S: ReadonlyStorage>(
+    store: &S,
+    account: &HumanAddr,
+) -> StdResult<Option<String>> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_PERMIT_TAGS, store);
+    let store = TypedStore::attach(&store);
+    store.may_load(account.as_str().as_bytes())
+}
+
+pub fn write_permit_tag<S: Storage>(
+    store: &mut S,
+    account: &HumanAddr,
+    tag: &str,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_PERMIT_TAGS, store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(account.as_str().as_bytes(), &tag.to_string())
+}
+
+/// The tag a permit was signed for, going by the `@<tag>` suffix of its name
+pub fn permit_name_tag(permit_name: &str) -> Option<&str> {
+    permit_name.rfind('@').map(|at| &permit_name[at + 1..])
+}
+
+pub fn check_permit_tag<S: ReadonlyStorage>(
+    store: &S,
+    account: &HumanAddr,
+    permit_name: &str,
+) -> StdResult<()> {
+    if let Some(tag) = read_permit_tag(store, account)? {
+        if permit_name_tag(permit_name) != Some(tag.as_str()) {
+            return Err(StdError::generic_err(format!(
+                "Permit {:?} isn't valid for the current permit tag of account {:?}",
+                permit_name, account
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The scope `account` set for its permits named `permit_name`, unrestricted if none was set
+pub fn read_permit_scope<S: ReadonlyStorage>(
+    store: &S,
+    account: &HumanAddr,
+    permit_name: &str,
+) -> StdResult<PermitScope> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_PERMIT_SCOPES, account.as_str().as_bytes()],
+        store,
+    );
+    let store = TypedStore::attach(&store);
+    let scope = store.may_load(permit_name.as_bytes());
+    scope.map(Option::unwrap_or_default)
+}
+
+pub fn write_permit_scope<S: Storage>(
+    store: &mut S,
+    account: &HumanAddr,
+    permit_name: &str,
+    scope: &PermitScope,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(
+        &[PREFIX_PERMIT_SCOPES, account.as_str().as_bytes()],
+        store,
+    );
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(permit_name.as_bytes(), scope)
+}
+
+/// Records a permit name revoked by `account`, so that revocations can be listed. Names revoked
+/// before this list existed are not in it.
+pub fn append_revoked_permit_name<S: Storage>(
+    store: &mut S,
+    account: &HumanAddr,
+    permit_name: &str,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(
+        &[PREFIX_REVOKED_PERMIT_NAMES, account.as_str().as_bytes()],
+        store,
+    );
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(&permit_name.to_string())
+}
+
+/// Returns a page of the permit names revoked by `account`, latest first, and how many there are
+pub fn get_revoked_permit_names<S: ReadonlyStorage>(
+    storage: &S,
+    account: &HumanAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<String>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_REVOKED_PERMIT_NAMES, account.as_str().as_bytes()],
+        storage,
+    );
+    let store = AppendStore::<String, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let names: StdResult<Vec<String>> = store
+        .iter()
+        .rev()
+        .skip((page * page_size) as _)
+        .take(page_size as _)
+        .collect();
+    names.map(|names| (names, store.len() as u64))
+}
+
+// Minter caps
+
+/// Limits on how much a single minter may mint, plus what it has minted so far.
+/// A minter without caps may mint without limits.
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Default, JsonSchema)]
+pub struct MinterCap {
+    pub lifetime_cap: Option<u128>,
+    pub lifetime_minted: u128,
+    pub window_cap: Option<u128>,
+    pub window_seconds: u64,
+    pub window_start: u64,
+    pub window_minted: u128,
+}
+
+impl MinterCap {
+    pub fn window_is_over_at(&self, block: &cosmwasm_std::BlockInfo) -> bool {
+        block.time >= self.window_start.saturating_add(self.window_seconds)
+    }
+
+    pub fn lifetime_remaining(&self) -> Option<u128> {
+        self.lifetime_cap
+            .map(|cap| cap.saturating_sub(self.lifetime_minted))
+    }
+
+    pub fn window_remaining(&self) -> Option<u128> {
+        self.window_cap
+            .map(|cap| cap.saturating_sub(self.window_minted))
+    }
+
+    /// Records `amount` as minted at `block`, failing if it exceeds either cap
+    pub fn consume(&mut self, amount: u128, block: &cosmwasm_std::BlockInfo) -> StdResult<()> {
+        if self.window_cap.is_some() && self.window_is_over_at(block) {
+            self.window_start = block.time;
+            self.window_minted = 0;
+        }
+
+        if let Some(remaining) = self.lifetime_remaining() {
+            if amount > remaining {
+                return Err(StdError::generic_err(format!(
+                    "This mint exceeds the minter's lifetime cap: remaining={}, required={}",
+                    remaining, amount
+                )));
+            }
+        }
+        if let Some(remaining) = self.window_remaining() {
+            if amount > remaining {
+                return Err(StdError::generic_err(format!(
+                    "This mint exceeds the minter's allowance for the current window: remaining={}, required={}",
+                    remaining, amount
+                )));
+            }
+        }
+
+        self.lifetime_minted = self.lifetime_minted.saturating_add(amount);
+        self.window_minted = self.window_minted.saturating_add(amount);
+        Ok(())
+    }
+}
+
+pub fn read_minter_cap<S: ReadonlyStorage>(store: &S, minter: &HumanAddr) -> StdResult<MinterCap> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_MINTER_CAPS, store);
+    let store = TypedStore::attach(&store);
+    let cap = store.may_load(minter.as_str().as_bytes());
+    cap.map(Option::unwrap_or_default)
+}
+
+pub fn write_minter_cap<S: Storage>(
+    store: &mut S,
+    minter: &HumanAddr,
+    cap: &MinterCap,
+) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_MINTER_CAPS, store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(minter.as_str().as_bytes(), cap)
+}
+
+// Frozen accounts
+
+pub fn is_frozen<S: ReadonlyStorage>(store: &S, account: &CanonicalAddr) -> bool {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_FROZEN, store);
+    store.get(account.as_slice()).is_some()
+}
+
+pub fn set_frozen<S: Storage>(store: &mut S, account: &CanonicalAddr, frozen: bool) {
+    let mut store = PrefixedStorage::new(PREFIX_FROZEN, store);
+    if frozen {
+        store.set(account.as_slice(), &[1]);
+    } else {
+        store.remove(account.as_slice());
+    }
+}
+
+// Allowlist
+//
+// Members are keyed by their canonical address and map to their position in a dense index, so the
+// list can be paged through. Removing a member moves the last member into its position.
+
+const ALLOWLIST_MEMBERS: &[u8] = b"members";
+const ALLOWLIST_INDEX: &[u8] = b"index";
+const ALLOWLIST_SIZE: &[u8] = b"size";
+
+pub fn is_allowlisted<S: ReadonlyStorage>(store: &S, account: &CanonicalAddr) -> bool {
+    let members = ReadonlyPrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_MEMBERS], store);
+    members.get(account.as_slice()).is_some()
+}
+
+pub fn allowlist_size<S: ReadonlyStorage>(store: &S) -> u32 {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_ALLOWLIST, store);
+    get_bin_data(&store, ALLOWLIST_SIZE).unwrap_or_default()
+}
+
+/// Returns whether `account` was added, i.e. wasn't a member already
+pub fn add_allowlisted_account<S: Storage>(store: &mut S, account: &CanonicalAddr) -> StdResult<bool> {
+    if is_allowlisted(&*store, account) {
+        return Ok(false);
+    }
+
+    let size = allowlist_size(&*store);
+    {
+        let mut members = PrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_MEMBERS], store);
+        set_bin_data(&mut members, account.as_slice(), &size)?;
+    }
+    {
+        let mut index = PrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_INDEX], store);
+        index.set(&size.to_be_bytes(), account.as_slice());
+    }
+    let mut store = PrefixedStorage::new(PREFIX_ALLOWLIST, store);
+    set_bin_data(&mut store, ALLOWLIST_SIZE, &(size + 1))?;
+
+    Ok(true)
+}
+
+/// Returns whether `account` was removed, i.e. was a member
+pub fn remove_allowlisted_account<S: Storage>(store: &mut S, account: &CanonicalAddr) -> StdResult<bool> {
+    let position: u32 = {
+        let members = ReadonlyPrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_MEMBERS], &*store);
+        match get_bin_data(&members, account.as_slice()) {
+            Ok(position) => position,
+            Err(_) => return Ok(false),
+        }
+    };
+
+    let last = allowlist_size(&*store) - 1;
+    let last_member = {
+        let mut index = PrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_INDEX], store);
+        let last_member = index
+            .get(&last.to_be_bytes())
+            .ok_or_else(|| StdError::generic_err("Allowlist index is corrupted"))?;
+        index.set(&position.to_be_bytes(), &last_member);
+        index.remove(&last.to_be_bytes());
+        CanonicalAddr(Binary(last_member))
+    };
+    {
+        let mut members = PrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_MEMBERS], store);
+        members.remove(account.as_slice());
+        if &last_member != account {
+            set_bin_data(&mut members, last_member.as_slice(), &position)?;
+        }
+    }
+    let mut store = PrefixedStorage::new(PREFIX_ALLOWLIST, store);
+    set_bin_data(&mut store, ALLOWLIST_SIZE, &last)?;
+
+    Ok(true)
+}
+
+/// Returns a page of allowlisted accounts and the size of the allowlist
+pub fn get_allowlist<S: ReadonlyStorage>(
+    store: &S,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<CanonicalAddr>, u32)> {
+    let size = allowlist_size(store);
+    let index = ReadonlyPrefixedStorage::multilevel(&[PREFIX_ALLOWLIST, ALLOWLIST_INDEX], store);
+
+    let start = page.saturating_mul(page_size).min(size);
+    let end = start.saturating_add(page_size).min(size);
+    let accounts: StdResult<Vec<CanonicalAddr>> = (start..end)
+        .map(|position| {
+            index
+                .get(&position.to_be_bytes())
+                .map(|account| CanonicalAddr(Binary(account)))
+                .ok_or_else(|| StdError::generic_err("Allowlist index is corrupted"))
+        })
+        .collect();
+
+    accounts.map(|accounts| (accounts, size))
+}
+
+// Config change log
+
+pub fn append_config_change<S: Storage>(store: &mut S, change: &ConfigChange) -> StdResult<()> {
+    let mut store = PrefixedStorage::new(PREFIX_CONFIG_CHANGES, store);
+    let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+    store.push(change)
+}
+
+/// Returns a page of config changes, latest first, and the total number of changes
+pub fn get_config_changes<S: ReadonlyStorage>(
+    storage: &S,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<ConfigChange>, u64)> {
+    let store = ReadonlyPrefixedStorage::new(PREFIX_CONFIG_CHANGES, storage);
+    let store = AppendStore::<ConfigChange, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let changes: StdResult<Vec<ConfigChange>> = store
+        .iter()
+        .rev()
+        .skip((page * page_size) as _)
+        .take(page_size as _)
+        .collect();
+    changes.map(|changes| (changes, store.len() as u64))
+}
+
+// Vesting
+
+/// Tokens held back from an account until they vest. Revoking a schedule cuts `amount` down to what
+/// had vested by then, and returns the rest to `funder`.
+#[derive(Serialize, Debug, Deserialize, Clone, PartialEq)]
+pub struct VestingSchedule {
+    pub amount: u128,
+    pub claimed: u128,
+    pub terms: VestingTerms,
+    pub revoked_at: Option<u64>,
+    /// Unknown for schedules created before schema version 5, whose remainder goes to the admin
+    /// revoking them
+    pub funder: Option<CanonicalAddr>,
+}
+
+impl VestingSchedule {
+    pub fn vested_at(&self, time: u64) -> u128 {
+        let terms = &self.terms;
+        if self.revoked_at.is_some() || time >= terms.end_time {
+            return self.amount;
+        }
+        if time < terms.cliff_time {
+            return 0;
+        }
+
+        let elapsed = (time - terms.start_time) as u128;
+        let duration = (terms.end_time - terms.start_time) as u128;
+        self.amount
+            .checked_mul(elapsed)
+            .map(|product| product / duration)
+            .unwrap_or_else(|| self.amount / duration * elapsed)
+    }
+
+    pub fn claimable_at(&self, time: u64) -> u128 {
+        self.vested_at(time).saturating_sub(self.claimed)
+    }
+}
+
+pub fn read_vesting_schedules<S: ReadonlyStorage>(
+    store: &S,
+    account: &CanonicalAddr,
+) -> StdResult<Vec<VestingSchedule>> {
+    let schedules = {
+        let store = ReadonlyPrefixedStorage::new(PREFIX_VESTING_SCHEDULES, store);
+        let store = TypedStore::attach(&store);
+        store.may_load(account.as_slice())?
+    };
+    match schedules {
+        Some(schedules) => Ok(schedules),
+        None => read_legacy_vesting_schedules(store, account),
+    }
+}
+
+/// Also drops the account's schedules in the layout before schema version 5, if there were any
+pub fn write_vesting_schedules<S: Storage>(
+    store: &mut S,
+    account: &CanonicalAddr,
+    schedules: &[VestingSchedule],
+) -> StdResult<()> {
+    PrefixedStorage::new(PREFIX_VESTING, store).remove(account.as_slice());
+
+    let mut store = PrefixedStorage::new(PREFIX_VESTING_SCHEDULES, store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(account.as_slice(), &schedules.to_vec())
+}
+
+// Escrow
+//
+// Escrows are keyed by a sequential id. Every party of an escrow gets the id appended to their own
+// index, so an account's escrows can be paged through.
+
+const ESCROW_RECORDS: &[u8] = b"records";
+const ESCROW_ACCOUNTS: &[u8] = b"accounts";
+const ESCROW_COUNT: &[u8] = b"count";
+
+/// Funds held by the contract until they are released to `recipient` or refunded to `sender`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Escrow {
+    pub id: u64,
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub arbiter: Option<CanonicalAddr>,
+    pub amount: u128,
+    pub expires_at: u64,
+    pub status: EscrowStatus,
+}
+
+impl Escrow {
+    /// The distinct accounts involved in this escrow
+    pub fn parties(&self) -> Vec<&CanonicalAddr> {
+        let mut parties = vec![&self.sender];
+        for party in std::iter::once(&self.recipient).chain(self.arbiter.iter()) {
+            if !parties.contains(&party) {
+                parties.push(party);
+            }
+        }
+        parties
+    }
+}
+
+/// Stores a new escrow under the next free id and returns that id
+pub fn add_escrow<S: Storage>(store: &mut S, mut escrow: Escrow) -> StdResult<u64> {
+    let id = {
+        let store = ReadonlyPrefixedStorage::new(PREFIX_ESCROWS, &*store);
+        let count: u64 = get_bin_data(&store, ESCROW_COUNT).unwrap_or_default();
+        count + 1
+    };
+    escrow.id = id;
+
+    for party in escrow.parties() {
+        let mut store =
+            PrefixedStorage::multilevel(&[PREFIX_ESCROWS, ESCROW_ACCOUNTS, party.as_slice()], store);
+        let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+        store.push(&id)?;
+    }
+    write_escrow(store, &escrow)?;
+    let mut store = PrefixedStorage::new(PREFIX_ESCROWS, store);
+    set_bin_data(&mut store, ESCROW_COUNT, &id)?;
+
+    Ok(id)
+}
+
+pub fn read_escrow<S: ReadonlyStorage>(store: &S, id: u64) -> StdResult<Escrow> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_ESCROWS, ESCROW_RECORDS], store);
+    let store = TypedStore::attach(&store);
+    store
+        .may_load(&id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("No escrow with id {}", id)))
+}
+
+pub fn write_escrow<S: Storage>(store: &mut S, escrow: &Escrow) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_ESCROWS, ESCROW_RECORDS], store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(&escrow.id.to_be_bytes(), escrow)
+}
+
+/// Returns a page of the escrows `account` is a party of, latest first, and how many there are
+pub fn get_escrows<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<Escrow>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_ESCROWS, ESCROW_ACCOUNTS, account.as_slice()],
+        storage,
+    );
+    let store = AppendStore::<u64, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let escrows: StdResult<Vec<Escrow>> = store
+        .iter()
+        .rev()
+        .skip((page * page_size) as _)
+        .take(page_size as _)
+        .map(|id| id.and_then(|id| read_escrow(storage, id)))
+        .collect();
+    escrows.map(|escrows| (escrows, store.len() as u64))
+}
+
+// Hash time-locked contracts
+
+const HTLC_RECORDS: &[u8] = b"records";
+const HTLC_COUNT: &[u8] = b"count";
+
+/// Funds locked until the preimage of `hashlock` is revealed, or refundable to `sender` once expired
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Htlc {
+    pub id: u64,
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub amount: u128,
+    pub hashlock: Binary,
+    pub expires_at: u64,
+    pub preimage: Option<Binary>,
+    pub status: HtlcStatus,
+}
+
+/// Stores a new HTLC under the next free id and returns that id
+pub fn add_htlc<S: Storage>(store: &mut S, mut htlc: Htlc) -> StdResult<u64> {
+    let id = {
+        let store = ReadonlyPrefixedStorage::new(PREFIX_HTLCS, &*store);
+        let count: u64 = get_bin_data(&store, HTLC_COUNT).unwrap_or_default();
+        count + 1
+    };
+    htlc.id = id;
+
+    write_htlc(store, &htlc)?;
+    let mut store = PrefixedStorage::new(PREFIX_HTLCS, store);
+    set_bin_data(&mut store, HTLC_COUNT, &id)?;
+
+    Ok(id)
+}
+
+pub fn read_htlc<S: ReadonlyStorage>(store: &S, id: u64) -> StdResult<Htlc> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_HTLCS, HTLC_RECORDS], store);
+    let store = TypedStore::attach(&store);
+    store
+        .may_load(&id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("No HTLC with id {}", id)))
+}
+
+pub fn write_htlc<S: Storage>(store: &mut S, htlc: &Htlc) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_HTLCS, HTLC_RECORDS], store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(&htlc.id.to_be_bytes(), htlc)
+}
+
+// Streams
+//
+// Laid out like escrows: records keyed by a sequential id, plus an index of ids per party.
+
+const STREAM_RECORDS: &[u8] = b"records";
+const STREAM_ACCOUNTS: &[u8] = b"accounts";
+const STREAM_COUNT: &[u8] = b"count";
+
+/// Tokens paid from `sender` to `recipient` every second between `start` and `end`. The whole
+/// deposit is locked up front. Canceling stops the stream at `canceled_at`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stream {
+    pub id: u64,
+    pub sender: CanonicalAddr,
+    pub recipient: CanonicalAddr,
+    pub rate_per_second: u128,
+    pub start: u64,
+    pub end: u64,
+    pub deposit: u128,
+    pub withdrawn: u128,
+    pub canceled_at: Option<u64>,
+}
+
+impl Stream {
+    /// How much has been paid to the recipient in total by `time`, withdrawn or not
+    pub fn streamed_at(&self, time: u64) -> u128 {
+        let stop = self.canceled_at.unwrap_or(self.end).min(self.end);
+        let elapsed = time.min(stop).saturating_sub(self.start) as u128;
+        self.rate_per_second.saturating_mul(elapsed).min(self.deposit)
+    }
+
+    pub fn withdrawable_at(&self, time: u64) -> u128 {
+        self.streamed_at(time).saturating_sub(self.withdrawn)
+    }
+}
+
+/// Stores a new stream under the next free id and returns that id
+pub fn add_stream<S: Storage>(store: &mut S, mut stream: Stream) -> StdResult<u64> {
+    let id = {
+        let store = ReadonlyPrefixedStorage::new(PREFIX_STREAMS, &*store);
+        let count: u64 = get_bin_data(&store, STREAM_COUNT).unwrap_or_default();
+        count + 1
+    };
+    stream.id = id;
+
+    for party in &[&stream.sender, &stream.recipient] {
+        let mut store =
+            PrefixedStorage::multilevel(&[PREFIX_STREAMS, STREAM_ACCOUNTS, party.as_slice()], store);
+        let mut store = AppendStoreMut::attach_or_create(&mut store)?;
+        store.push(&id)?;
+    }
+    write_stream(store, &stream)?;
+    let mut store = PrefixedStorage::new(PREFIX_STREAMS, store);
+    set_bin_data(&mut store, STREAM_COUNT, &id)?;
+
+    Ok(id)
+}
+
+pub fn read_stream<S: ReadonlyStorage>(store: &S, id: u64) -> StdResult<Stream> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_STREAMS, STREAM_RECORDS], store);
+    let store = TypedStore::attach(&store);
+    store
+        .may_load(&id.to_be_bytes())?
+        .ok_or_else(|| StdError::generic_err(format!("No stream with id {}", id)))
+}
+
+pub fn write_stream<S: Storage>(store: &mut S, stream: &Stream) -> StdResult<()> {
+    let mut store = PrefixedStorage::multilevel(&[PREFIX_STREAMS, STREAM_RECORDS], store);
+    let mut store = TypedStoreMut::attach(&mut store);
+    store.store(&stream.id.to_be_bytes(), stream)
+}
+
+/// Returns a page of the streams `account` pays or is paid by, latest first, and how many there are
+pub fn get_streams<S: ReadonlyStorage>(
+    storage: &S,
+    account: &CanonicalAddr,
+    page: u32,
+    page_size: u32,
+) -> StdResult<(Vec<Stream>, u64)> {
+    let store = ReadonlyPrefixedStorage::multilevel(
+        &[PREFIX_STREAMS, STREAM_ACCOUNTS, account.as_slice()],
+        storage,
+    );
+    let store = AppendStore::<u64, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok((vec![], 0));
+    };
+
+    let streams: StdResult<Vec<Stream>> = store
+        .iter()
+        .rev()
+        .skip((page * page_size) as _)
+        .take(page_size as _)
+        .map(|id| id.and_then(|id| read_stream(storage, id)))
+        .collect();
+    streams.map(|streams| (streams, store.len() as u64))
+}
+
 // Viewing Keys
 
 pub fn write_viewing_key<S: Storage>(store: &mut S, owner: &CanonicalAddr, key: &ViewingKey) {
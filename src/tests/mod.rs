@@ -1,2 +1,2 @@
-
-mod unittest_handle;
\ No newline at end of file
+mod unittest_handle;
+mod unittest_migrate;
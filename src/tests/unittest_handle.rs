@@ -3,15 +3,18 @@
 mod tests {
     use super::*;
     use crate::msg::{ContractStatusLevel, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, ResponseStatus};
-    use crate::msg::{InitConfig, InitialBalance};
+    use crate::msg::{AllowanceInfo, ApprovePermit, ApprovePermitParams, ConfigChange, EscrowInfo, EscrowStatus, ExchangeRatio, Feature, HistoryRange, HtlcStatus, MandateInfo, PeriodLimitInfo, SpendingLimit, InitConfig, InitialBalance, InitialVesting, Logo, NativeDenom, PermitScope, Role, RoleMembers, TokenMetadata, TransferPermit, TransferPermitParams, VestingInfo, VestingTerms};
     use cosmwasm_std::testing::*;
-    use cosmwasm_std::{from_binary, BlockInfo, ContractInfo, MessageInfo, QueryResponse, WasmMsg, InitResponse, StdResult, Extern, HumanAddr, Binary, Coin, Uint128, StdError, HandleResponse, to_binary, CosmosMsg, Env, Api};
+    use cosmwasm_std::{from_binary, BankMsg, BlockInfo, ContractInfo, MessageInfo, QueryResponse, WasmMsg, InitResponse, StdResult, Extern, HumanAddr, Binary, Coin, Uint128, StdError, HandleResponse, to_binary, CosmosMsg, Env, Api};
+    use cosmwasm_storage::PrefixedStorage;
+    use hermit_toolkit::permit::{pubkey_to_account, PermitSignature, PubKey};
     use std::any::Any;
     use crate::batch;
-    use crate::contract::{handle, init, query};
+    use crate::contract::{check_counterparty, check_no_counterparties, check_no_history_range, check_scope_expiry, handle, init, query};
+    use crate::query::{query_transactions, query_transfers};
     use crate::msg::ResponseStatus::Success;
     use crate::receiver::Hmip20ReceiveMsg;
-    use crate::state::{get_receiver_hash, read_allowance, read_viewing_key, ReadonlyBalances, ReadonlyConfig};
+    use crate::state::{get_receiver_hash, read_allowance, read_permit_scope, read_viewing_key, ReadonlyBalances, ReadonlyConfig, PREFIX_ALLOWANCES};
     use crate::tools::rand::sha_256;
     use crate::tools::viewing_key::{VIEWING_KEY_SIZE, ViewingKey};
 
@@ -32,6 +35,7 @@ mod tests {
             symbol: "SECSEC".to_string(),
             decimals: 8,
             initial_balances: Some(initial_balances),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: None,
         };
@@ -77,6 +81,7 @@ mod tests {
             symbol: "SECSEC".to_string(),
             decimals: 8,
             initial_balances: Some(initial_balances),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: Some(init_config),
         };
@@ -109,6 +114,67 @@ mod tests {
         (vk, deps)
     }
 
+    /// The public key that signed the permit fixtures. Its address happens to be 20 bytes of valid
+    /// UTF-8 without zeros, so `MockApi` can turn it into a `HumanAddr`.
+    const PERMIT_SIGNER_PUBKEY: &str = "AjBsz7+d6iDy0w76At+y1tRZv8wCSRPo3OptjMp4Bmhs";
+
+    fn permit_signer_helper() -> HumanAddr {
+        let pubkey = Binary::from_base64(PERMIT_SIGNER_PUBKEY).unwrap();
+        MockApi::new(20)
+            .human_address(&pubkey_to_account(&pubkey))
+            .unwrap()
+    }
+
+    fn permit_signature_helper(signature: &str) -> PermitSignature {
+        PermitSignature {
+            pub_key: PubKey {
+                r#type: "tendermint/PubKeySecp256k1".to_string(),
+                value: Binary::from_base64(PERMIT_SIGNER_PUBKEY).unwrap(),
+            },
+            signature: Binary::from_base64(signature).unwrap(),
+        }
+    }
+
+    /// A transfer of 100 tokens to alice with nonce 0, signed over the sign doc in the README
+    fn signed_transfer_permit_helper() -> TransferPermit {
+        TransferPermit {
+            params: TransferPermitParams {
+                amount: Uint128(100),
+                chain_id: "cosmos-testnet-14002".to_string(),
+                expires_at: 2_000_000_000,
+                memo: None,
+                nonce: 0,
+                owner: permit_signer_helper(),
+                permit_name: "relay".to_string(),
+                recipient: HumanAddr("alice".to_string()),
+                token: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+            },
+            signature: permit_signature_helper(
+                "mUrL0+Keoz/XCyyqv2a7TYECIxngBEA9GGKxSRvlVLwUnxcJYdgjU0QUeyMKNa3lgmhKep6ZTfV/makN+3ZvbQ==",
+            ),
+        }
+    }
+
+    /// An allowance of 300 tokens for alice with nonce 0, signed like the transfer permit fixture
+    fn signed_approve_permit_helper() -> ApprovePermit {
+        ApprovePermit {
+            params: ApprovePermitParams {
+                amount: Uint128(300),
+                chain_id: "cosmos-testnet-14002".to_string(),
+                expiration: None,
+                expires_at: 2_000_000_000,
+                nonce: 0,
+                owner: permit_signer_helper(),
+                permit_name: "dex".to_string(),
+                spender: HumanAddr("alice".to_string()),
+                token: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+            },
+            signature: permit_signature_helper(
+                "AQabzo9QoU2fvHNGJ8t3/XsEMgi3sZ0giDNMnI/Hf9IrydGEZQaJfdIdv0g7l5zgpUs6clbUEwzr0E6WmQ7IIw==",
+            ),
+        }
+    }
+
     fn extract_error_msg<T: Any>(error: StdResult<T>) -> String {
         match error {
             Ok(response) => {
@@ -139,12 +205,36 @@ mod tests {
             | HandleAnswer::RegisterReceive { status }
             | HandleAnswer::SetViewingKey { status }
             | HandleAnswer::TransferFrom { status }
+            | HandleAnswer::TransferWithPermit { status }
             | HandleAnswer::SendFrom { status }
             | HandleAnswer::BurnFrom { status }
             | HandleAnswer::Mint { status }
             | HandleAnswer::ChangeAdmin { status }
+            | HandleAnswer::ForceTransfer { status }
+            | HandleAnswer::AcceptAdmin { status }
+            | HandleAnswer::CancelAdminTransfer { status }
+            | HandleAnswer::RenounceAdmin { status }
+            | HandleAnswer::GrantRole { status }
+            | HandleAnswer::RevokeRole { status }
             | HandleAnswer::SetContractStatus { status }
             | HandleAnswer::SetMinters { status }
+            | HandleAnswer::SetMinterCap { status }
+            | HandleAnswer::SetMaxSupply { status }
+            | HandleAnswer::UpdateConfig { status }
+            | HandleAnswer::SetTokenMetadata { status }
+            | HandleAnswer::SetFeeConfig { status }
+            | HandleAnswer::FreezeAccount { status }
+            | HandleAnswer::AddToAllowlist { status }
+            | HandleAnswer::RemoveFromAllowlist { status }
+            | HandleAnswer::UnfreezeAccount { status }
+            | HandleAnswer::AddFeeExemptions { status }
+            | HandleAnswer::RemoveFeeExemptions { status }
+            | HandleAnswer::CreateVesting { status }
+            | HandleAnswer::ReleaseEscrow { status }
+            | HandleAnswer::RefundEscrow { status }
+            | HandleAnswer::RefundHtlc { status }
+            | HandleAnswer::CreateMandate { status }
+            | HandleAnswer::RevokeMandate { status }
             | HandleAnswer::AddMinters { status }
             | HandleAnswer::RemoveMinters { status } => {
                 matches!(status, ResponseStatus::Success { .. })
@@ -171,7 +261,7 @@ mod tests {
         assert_eq!(config.total_supply(), 5000);
         assert_eq!(config.contract_status(), ContractStatusLevel::NormalRun);
         assert_eq!(constants.name, "sec-sec".to_string());
-        assert_eq!(constants.admin, HumanAddr("admin".to_string()));
+        assert_eq!(constants.admin, Some(HumanAddr("admin".to_string())));
         assert_eq!(constants.symbol, "SECSEC".to_string());
         assert_eq!(constants.decimals, 8);
         assert_eq!(
@@ -201,7 +291,7 @@ mod tests {
         assert_eq!(config.total_supply(), 5000);
         assert_eq!(config.contract_status(), ContractStatusLevel::NormalRun);
         assert_eq!(constants.name, "sec-sec".to_string());
-        assert_eq!(constants.admin, HumanAddr("admin".to_string()));
+        assert_eq!(constants.admin, Some(HumanAddr("admin".to_string())));
         assert_eq!(constants.symbol, "SECSEC".to_string());
         assert_eq!(constants.decimals, 8);
         assert_eq!(
@@ -291,7 +381,7 @@ mod tests {
     }
 
     #[test]
-    fn test_handle_send() {
+    fn test_handle_transfer_fee() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -302,105 +392,139 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::RegisterReceive {
-            code_hash: "this_is_a_hash_of_a_code".to_string(),
+        let set_fee = |collector: Option<&str>| HandleMsg::SetFeeConfig {
+            bps: 100,
+            min: Some(Uint128(2)),
+            max: Some(Uint128(20)),
+            collector: collector.map(|collector| HumanAddr(collector.to_string())),
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), set_fee(None));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
 
-        let handle_msg = HandleMsg::Send {
-            recipient: HumanAddr("contract".to_string()),
-            recipient_code_hash: None,
-            amount: Uint128(100),
-            memo: Some("my memo".to_string()),
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), set_fee(Some("collector")));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let transfer = |amount: u128| HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(amount),
+            memo: None,
             padding: None,
-            msg: Some(to_binary("hey hey you you").unwrap()),
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result.clone()));
-        assert!(result.messages.contains(&CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: HumanAddr("contract".to_string()),
-            callback_code_hash: "this_is_a_hash_of_a_code".to_string(),
-            msg: Hmip20ReceiveMsg::new(
-                HumanAddr("bob".to_string()),
-                HumanAddr("bob".to_string()),
-                Uint128(100),
-                Some("my memo".to_string()),
-                Some(to_binary("hey hey you you").unwrap())
-            )
-                .into_binary()
-                .unwrap(),
-            send: vec![]
-        })));
-    }
+        let canonical = |deps: &Extern<MockStorage, MockApi, MockQuerier>, name: &str| {
+            deps.api.canonical_address(&HumanAddr(name.to_string())).unwrap()
+        };
+        let bob = canonical(&deps, "bob");
+        let alice = canonical(&deps, "alice");
+        let collector = canonical(&deps, "collector");
 
-    #[test]
-    fn test_handle_register_receive() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
-            amount: Uint128(5000),
-        }]);
-        assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
-        );
+        // 1% of 1000, then the minimum fee on a small transfer
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer(1000));
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer(100));
+        assert!(ensure_success(handle_result.unwrap()));
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&bob), 3900);
+            assert_eq!(balances.account_amount(&alice), 990 + 98);
+            assert_eq!(balances.account_amount(&collector), 10 + 2);
+        }
 
-        let handle_msg = HandleMsg::RegisterReceive {
-            code_hash: "this_is_a_hash_of_a_code".to_string(),
-            padding: None,
-        };
-        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
-        let result = handle_result.unwrap();
-        assert!(ensure_success(result));
+        let (txs, _) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &bob, 0, 2).unwrap();
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::Fee {
+                payer: HumanAddr("bob".to_string()),
+                collector: Some(HumanAddr("collector".to_string())),
+                transfer_id: Some(txs[1].id),
+            }
+        );
+        assert_eq!(txs[0].coins.amount, Uint128(2));
+        assert_eq!(txs[1].coins.amount, Uint128(98));
+        assert!(matches!(
+            txs[1].action,
+            crate::transaction_history::TxAction::Transfer { .. }
+        ));
 
-        let hash = get_receiver_hash(&deps.storage, &HumanAddr("contract".to_string()))
-            .unwrap()
-            .unwrap();
-        assert_eq!(hash, "this_is_a_hash_of_a_code".to_string());
-    }
+        // Exempt addresses pay no fee
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::AddFeeExemptions {
+                addresses: vec![HumanAddr("alice".to_string())],
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer(100));
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&alice),
+            1188
+        );
 
-    #[test]
-    fn test_handle_create_viewing_key() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
-            amount: Uint128(5000),
-        }]);
-        assert!(
-            init_result.is_ok(),
-            "Init failed: {}",
-            init_result.err().unwrap()
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::RemoveFeeExemptions {
+                addresses: vec![HumanAddr("alice".to_string())],
+                padding: None,
+            },
         );
+        assert!(ensure_success(handle_result.unwrap()));
 
-        let handle_msg = HandleMsg::CreateViewingKey {
-            entropy: "".to_string(),
-            padding: None,
-        };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        // Without a collector the fee is burned, capped at the maximum
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), set_fee(None));
+        assert!(ensure_success(handle_result.unwrap()));
+        let supply = ReadonlyConfig::from_storage(&deps.storage).total_supply();
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer(3000));
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).total_supply(),
+            supply - 20
+        );
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&alice),
+            1188 + 2980
         );
-        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
 
-        let key = match answer {
-            HandleAnswer::CreateViewingKey { key } => key,
-            _ => panic!("NOPE"),
-        };
-        let bob_canonical = deps
-            .api
-            .canonical_address(&HumanAddr("bob".to_string()))
-            .unwrap();
-        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
-        assert!(key.check_viewing_key(saved_vk.as_slice()));
+        let query_result = query(&deps, QueryMsg::FeeConfig {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::FeeConfig {
+                bps,
+                min,
+                max,
+                collector,
+                exempt,
+            } => {
+                assert_eq!(bps, 100);
+                assert_eq!(min, Uint128(2));
+                assert_eq!(max, Some(Uint128(20)));
+                assert_eq!(collector, None);
+                assert!(exempt.is_empty());
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetFeeConfig {
+                bps: 10_001,
+                min: None,
+                max: None,
+                collector: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The fee can't exceed 10000 basis points"));
     }
 
     #[test]
-    fn test_handle_set_viewing_key() {
+    fn test_handle_transfer_fee_frozen_collector() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
             address: HumanAddr("bob".to_string()),
             amount: Uint128(5000),
@@ -411,88 +535,2762 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        // Set VK
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: "hi lol".to_string(),
+        let handle_msg = HandleMsg::SetFeeConfig {
+            bps: 100,
+            min: None,
+            max: None,
+            collector: Some(HumanAddr("collector".to_string())),
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey {
-                status: ResponseStatus::Success
-            })
-                .unwrap(),
-        );
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_msg = HandleMsg::FreezeAccount {
+            address: HumanAddr("collector".to_string()),
+            reason: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
 
-        // Set valid VK
-        let actual_vk = ViewingKey("x".to_string().repeat(VIEWING_KEY_SIZE));
-        let handle_msg = HandleMsg::SetViewingKey {
-            key: actual_vk.0.clone(),
+        // A frozen collector still collects fees, rather than blocking transfers
+        let handle_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            memo: None,
             padding: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        let unwrapped_result: HandleAnswer =
-            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
-        assert_eq!(
-            to_binary(&unwrapped_result).unwrap(),
-            to_binary(&HandleAnswer::SetViewingKey { status: Success }).unwrap(),
-        );
-        let bob_canonical = deps
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let collector = deps
             .api
-            .canonical_address(&HumanAddr("bob".to_string()))
+            .canonical_address(&HumanAddr("collector".to_string()))
             .unwrap();
-        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
-        assert!(actual_vk.check_viewing_key(&saved_vk));
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&collector), 10);
+        assert_eq!(balances.account_amount(&alice), 990);
     }
 
     #[test]
-    fn test_handle_transfer_from() {
-        let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
-            amount: Uint128(5000),
-        }]);
+    fn test_handle_freeze_account() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![
+                InitialBalance {
+                    address: HumanAddr("bob".to_string()),
+                    amount: Uint128(5000),
+                },
+                InitialBalance {
+                    address: HumanAddr("alice".to_string()),
+                    amount: Uint128(5000),
+                },
+            ],
+            false,
+            false,
+            true,
+            true,
+            0,
+        );
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        // Transfer before allowance
-        let handle_msg = HandleMsg::TransferFrom {
-            owner: HumanAddr("bob".to_string()),
-            recipient: HumanAddr("alice".to_string()),
-            amount: Uint128(2500),
-            memo: None,
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::GrantRole {
+                role: Role::ComplianceManager,
+                address: HumanAddr("officer".to_string()),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let freeze_msg = HandleMsg::FreezeAccount {
+            address: HumanAddr("bob".to_string()),
+            reason: Some("court order".to_string()),
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), freeze_msg.clone());
         let error = extract_error_msg(handle_result);
-        assert!(error.contains("insufficient allowance"));
+        assert!(error.contains("Admin commands can only be run from admin address"));
 
-        // Transfer more than allowance
-        let handle_msg = HandleMsg::IncreaseAllowance {
-            spender: HumanAddr("alice".to_string()),
-            amount: Uint128(2000),
-            padding: None,
-            expiration: Some(1_571_797_420),
-        };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        let handle_result = handle(&mut deps, mock_env("officer", &[]), freeze_msg.clone());
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("officer", &[]), freeze_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This account is already frozen"));
+
+        let query_result = query(
+            &deps,
+            QueryMsg::IsFrozen {
+                address: HumanAddr("bob".to_string()),
+            },
         );
-        let handle_msg = HandleMsg::TransferFrom {
-            owner: HumanAddr("bob".to_string()),
-            recipient: HumanAddr("alice".to_string()),
-            amount: Uint128(2500),
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::IsFrozen { frozen } => assert!(frozen),
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let transfer = |recipient: &str| HandleMsg::Transfer {
+            recipient: HumanAddr(recipient.to_string()),
+            amount: Uint128(100),
             memo: None,
             padding: None,
         };
-        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer("alice"));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The sender account is frozen"));
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), transfer("bob"));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is frozen"));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::Mint {
+                recipient: HumanAddr("bob".to_string()),
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is frozen"));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::Burn {
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The sender account is frozen"));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::IncreaseAllowance {
+                spender: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                expiration: None,
+                period_limit: None,
+                padding: None,
+            },
+        );
+        assert!(handle_result.is_ok());
+        let handle_result = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::TransferFrom {
+                owner: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The owner account is frozen"));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::CreateEscrow {
+                recipient: HumanAddr("bob".to_string()),
+                arbiter: None,
+                amount: Uint128(100),
+                expires_at: 2_000_000_000,
+                memo: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is frozen"));
+        let handle_result = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::LockHtlc {
+                recipient: HumanAddr("bob".to_string()),
+                amount: Uint128(100),
+                hashlock: Binary(sha_256(b"secret").to_vec()),
+                expires_at: 2_000_000_000,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is frozen"));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("officer", &[]),
+            HandleMsg::UnfreezeAccount {
+                address: HumanAddr("bob".to_string()),
+                reason: None,
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer("alice"));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let (txs, _) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &bob, 0, 3).unwrap();
+        assert_eq!(
+            txs[1].action,
+            crate::transaction_history::TxAction::Unfreeze {
+                account: HumanAddr("bob".to_string()),
+                by: HumanAddr("officer".to_string()),
+            }
+        );
+        assert_eq!(
+            txs[2].action,
+            crate::transaction_history::TxAction::Freeze {
+                account: HumanAddr("bob".to_string()),
+                by: HumanAddr("officer".to_string()),
+            }
+        );
+        assert_eq!(txs[2].memo, Some("court order".to_string()));
+    }
+
+    #[test]
+    fn test_handle_allowlist() {
+        let mut deps = mock_dependencies(20, &[]);
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "enable_allowlist": true }"#.as_bytes(),
+        ))
+        .unwrap();
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            }]),
+            initial_vesting: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let transfer = |recipient: &str| HandleMsg::Transfer {
+            recipient: HumanAddr(recipient.to_string()),
+            amount: Uint128(100),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer("alice"));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is not on the allowlist"));
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::CreateEscrow {
+                recipient: HumanAddr("alice".to_string()),
+                arbiter: None,
+                amount: Uint128(100),
+                expires_at: 2_000_000_000,
+                memo: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is not on the allowlist"));
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::LockHtlc {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                hashlock: Binary(sha_256(b"secret").to_vec()),
+                expires_at: 2_000_000_000,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is not on the allowlist"));
+
+        let add_msg = HandleMsg::AddToAllowlist {
+            addresses: vec![
+                HumanAddr("alice".to_string()),
+                HumanAddr("charlie".to_string()),
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), add_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), add_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer("alice"));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::RemoveFromAllowlist {
+                addresses: vec![HumanAddr("alice".to_string())],
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), transfer("alice"));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is not on the allowlist"));
+
+        let query_result = query(
+            &deps,
+            QueryMsg::Allowlist {
+                page: None,
+                page_size: 10,
+            },
+        );
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Allowlist {
+                enabled,
+                addresses,
+                total,
+            } => {
+                assert!(enabled);
+                assert_eq!(
+                    addresses,
+                    vec![HumanAddr("bob".to_string()), HumanAddr("charlie".to_string())]
+                );
+                assert_eq!(total, 2);
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let query_result = query(
+            &deps,
+            QueryMsg::Allowlist {
+                page: Some(1),
+                page_size: 1,
+            },
+        );
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Allowlist { addresses, .. } => {
+                assert_eq!(addresses, vec![HumanAddr("charlie".to_string())]);
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_handle_force_transfer() {
+        let force_msg = |reason: &str| HandleMsg::ForceTransfer {
+            from: HumanAddr("bob".to_string()),
+            to: HumanAddr("vault".to_string()),
+            amount: Uint128(1000),
+            reason: reason.to_string(),
+            padding: None,
+        };
+
+        let (init_result, mut deps_for_failure) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        let handle_result = handle(
+            &mut deps_for_failure,
+            mock_env("admin", &[]),
+            force_msg("stolen funds"),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Forced transfers are not enabled for this token"));
+
+        let mut deps = mock_dependencies(20, &[]);
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "enable_force_transfer": true }"#.as_bytes(),
+        ))
+        .unwrap();
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            }]),
+            initial_vesting: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), force_msg("stolen funds"));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), force_msg(" "));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("A forced transfer requires a reason"));
+
+        // Frozen accounts can still be cleared
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::FreezeAccount {
+                address: HumanAddr("bob".to_string()),
+                reason: None,
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), force_msg("stolen funds"));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let vault = deps
+            .api
+            .canonical_address(&HumanAddr("vault".to_string()))
+            .unwrap();
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&bob), 4000);
+            assert_eq!(balances.account_amount(&vault), 1000);
+        }
+
+        let (txs, _) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &bob, 0, 1).unwrap();
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::ForcedTransfer {
+                from: HumanAddr("bob".to_string()),
+                by: HumanAddr("admin".to_string()),
+                recipient: HumanAddr("vault".to_string()),
+            }
+        );
+        assert_eq!(txs[0].memo, Some("stolen funds".to_string()));
+
+        // Like any transfer, it pays the transfer fee
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetFeeConfig {
+                bps: 100,
+                min: None,
+                max: None,
+                collector: None,
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), force_msg("stolen funds"));
+        assert!(ensure_success(handle_result.unwrap()));
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&bob), 3000);
+        assert_eq!(balances.account_amount(&vault), 1990);
+        assert_eq!(ReadonlyConfig::from_storage(&deps.storage).total_supply(), 4990);
+    }
+
+    #[test]
+    fn test_handle_escrow() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let create_msg = |amount: u128| HandleMsg::CreateEscrow {
+            recipient: HumanAddr("alice".to_string()),
+            arbiter: Some(HumanAddr("judge".to_string())),
+            amount: Uint128(amount),
+            expires_at: 2000,
+            memo: Some("order #1".to_string()),
+            padding: None,
+        };
+        let settle_msg = |escrow_id: u64, release: bool| {
+            if release {
+                HandleMsg::ReleaseEscrow {
+                    escrow_id,
+                    memo: None,
+                    padding: None,
+                }
+            } else {
+                HandleMsg::RefundEscrow {
+                    escrow_id,
+                    memo: None,
+                    padding: None,
+                }
+            }
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_result = handle(&mut deps, env_at("bob", 2000), create_msg(1000));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("An escrow must expire in the future"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 1000), create_msg(6000));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
+
+        for expected_id in 1..=2 {
+            let handle_result = handle(&mut deps, env_at("bob", 1000), create_msg(1000));
+            let answer: HandleAnswer =
+                from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+            match answer {
+                HandleAnswer::CreateEscrow { escrow_id, .. } => assert_eq!(escrow_id, expected_id),
+                _ => panic!("unexpected"),
+            }
+        }
+
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&bob),
+            3000
+        );
+
+        let handle_result = handle(&mut deps, env_at("alice", 1500), settle_msg(1, true));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the sender or arbiter of this escrow can settle it"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 1500), settle_msg(1, false));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This escrow can't be refunded before it expires at 2000"));
+
+        // The arbiter settles disputes either way
+        let handle_result = handle(&mut deps, env_at("judge", 1500), settle_msg(1, true));
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env_at("judge", 1500), settle_msg(1, false));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This escrow was already settled"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 2000), settle_msg(2, false));
+        assert!(ensure_success(handle_result.unwrap()));
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&bob), 4000);
+            assert_eq!(balances.account_amount(&alice), 1000);
+        }
+
+        let (txs, total) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &alice, 0, 10).unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::EscrowRefund {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+                by: HumanAddr("bob".to_string()),
+            }
+        );
+        assert_eq!(
+            txs[2].action,
+            crate::transaction_history::TxAction::EscrowCreate {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+                arbiter: Some(HumanAddr("judge".to_string())),
+            }
+        );
+        assert_eq!(txs[2].memo, Some("order #1".to_string()));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("judge", &[]),
+            HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::Escrows {
+            address: HumanAddr("judge".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 1,
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Escrows { escrows, total } => {
+                assert_eq!(total, 2);
+                assert_eq!(
+                    escrows,
+                    vec![EscrowInfo {
+                        id: 2,
+                        sender: HumanAddr("bob".to_string()),
+                        recipient: HumanAddr("alice".to_string()),
+                        arbiter: Some(HumanAddr("judge".to_string())),
+                        amount: Uint128(1000),
+                        expires_at: 2000,
+                        status: EscrowStatus::Refunded,
+                    }]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_handle_htlc() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let preimage = Binary::from("swap secret".as_bytes());
+        let hashlock = Binary::from(&sha_256(preimage.as_slice())[..]);
+        let lock_msg = HandleMsg::LockHtlc {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            hashlock: hashlock.clone(),
+            expires_at: 2000,
+            padding: None,
+        };
+        let redeem_msg = |htlc_id: u64, preimage: &Binary| HandleMsg::RedeemHtlc {
+            htlc_id,
+            preimage: preimage.clone(),
+            padding: None,
+        };
+        let refund_msg = |htlc_id: u64| HandleMsg::RefundHtlc {
+            htlc_id,
+            padding: None,
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("bob", 1000),
+            HandleMsg::LockHtlc {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(1000),
+                hashlock: Binary::from("too short".as_bytes()),
+                expires_at: 2000,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The hashlock must be a 32 byte SHA-256 hash"));
+
+        for expected_id in 1..=2 {
+            let handle_result = handle(&mut deps, env_at("bob", 1000), lock_msg.clone());
+            let answer: HandleAnswer =
+                from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+            match answer {
+                HandleAnswer::LockHtlc { htlc_id, .. } => assert_eq!(htlc_id, expected_id),
+                _ => panic!("unexpected"),
+            }
+        }
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("carol", 1500),
+            redeem_msg(1, &Binary::from("wrong secret".as_bytes())),
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The preimage does not match the hashlock"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 1500), refund_msg(1));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This HTLC can't be refunded before it expires at 2000"));
+
+        // Anyone can redeem with the preimage, but the funds go to the recipient
+        let handle_result = handle(&mut deps, env_at("carol", 1500), redeem_msg(1, &preimage));
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::RedeemHtlc { preimage: revealed, .. } => assert_eq!(revealed, preimage),
+            _ => panic!("unexpected"),
+        }
+        let handle_result = handle(&mut deps, env_at("carol", 1500), redeem_msg(1, &preimage));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This HTLC was already settled"));
+
+        let handle_result = handle(&mut deps, env_at("alice", 2000), redeem_msg(2, &preimage));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This HTLC has expired"));
+        let handle_result = handle(&mut deps, env_at("alice", 2000), refund_msg(2));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&bob), 4000);
+            assert_eq!(balances.account_amount(&alice), 1000);
+        }
+
+        let (txs, total) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &alice, 0, 10).unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::HtlcRefund {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+                by: HumanAddr("alice".to_string()),
+            }
+        );
+        assert_eq!(
+            txs[1].action,
+            crate::transaction_history::TxAction::HtlcRedeem {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+                by: HumanAddr("carol".to_string()),
+            }
+        );
+        assert_eq!(
+            txs[3].action,
+            crate::transaction_history::TxAction::HtlcLock {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+            }
+        );
+        assert_eq!(txs[3].coins.amount, Uint128(1000));
+        let carol = deps
+            .api
+            .canonical_address(&HumanAddr("carol".to_string()))
+            .unwrap();
+        let (_, total) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &carol, 0, 10).unwrap();
+        assert_eq!(total, 1);
+
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, QueryMsg::Htlc { htlc_id: 1 }).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Htlc {
+                hashlock: stored_hashlock,
+                status,
+                preimage: revealed,
+                ..
+            } => {
+                assert_eq!(stored_hashlock, hashlock);
+                assert_eq!(status, HtlcStatus::Redeemed);
+                assert_eq!(revealed, Some(preimage));
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_handle_streams() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let create_msg = |deposit: u128| HandleMsg::CreateStream {
+            recipient: HumanAddr("alice".to_string()),
+            rate_per_second: Uint128(10),
+            start: 1000,
+            end: 1100,
+            deposit: Uint128(deposit),
+            padding: None,
+        };
+        let withdraw_msg = HandleMsg::WithdrawFromStream {
+            stream_id: 1,
+            padding: None,
+        };
+        let cancel_msg = HandleMsg::CancelStream {
+            stream_id: 1,
+            padding: None,
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_result = handle(&mut deps, env_at("bob", 1000), create_msg(999));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The deposit must equal rate_per_second times"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 1000), create_msg(1000));
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::CreateStream { stream_id, .. } => assert_eq!(stream_id, 1),
+            _ => panic!("unexpected"),
+        }
+
+        let handle_result = handle(&mut deps, env_at("alice", 1000), withdraw_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is nothing to withdraw from this stream yet"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 1030), withdraw_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the recipient of this stream can withdraw from it"));
+
+        let handle_result = handle(&mut deps, env_at("alice", 1030), withdraw_msg.clone());
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::WithdrawFromStream { withdrawn, .. } => {
+                assert_eq!(withdrawn, Uint128(300))
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // Canceling settles both sides as of the current block
+        let handle_result = handle(&mut deps, env_at("bob", 1050), cancel_msg.clone());
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::CancelStream { paid, refunded, .. } => {
+                assert_eq!(paid, Uint128(200));
+                assert_eq!(refunded, Uint128(500));
+            }
+            _ => panic!("unexpected"),
+        }
+        let handle_result = handle(&mut deps, env_at("alice", 1060), cancel_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This stream was already canceled"));
+        let handle_result = handle(&mut deps, env_at("alice", 1080), withdraw_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is nothing to withdraw from this stream yet"));
+
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&bob), 4500);
+            assert_eq!(balances.account_amount(&alice), 500);
+        }
+
+        let (txs, total) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &alice, 0, 10).unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::StreamCancel {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+                by: HumanAddr("bob".to_string()),
+            }
+        );
+        assert_eq!(txs[0].coins.amount, Uint128(500));
+        assert_eq!(
+            txs[1].action,
+            crate::transaction_history::TxAction::StreamWithdraw {
+                sender: HumanAddr("bob".to_string()),
+                recipient: HumanAddr("alice".to_string()),
+            }
+        );
+        assert_eq!(txs[1].coins.amount, Uint128(200));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::Streams {
+            address: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+            time: Some(1080),
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Streams { streams, total } => {
+                assert_eq!(total, 1);
+                assert_eq!(streams[0].canceled_at, Some(1050));
+                assert_eq!(streams[0].withdrawn, Uint128(500));
+                assert_eq!(streams[0].streamed, Some(Uint128(500)));
+                assert_eq!(streams[0].withdrawable, Some(Uint128(0)));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // A frozen recipient can't get a new stream, and doesn't keep the sender from canceling
+        let create_msg = HandleMsg::CreateStream {
+            recipient: HumanAddr("alice".to_string()),
+            rate_per_second: Uint128(10),
+            start: 2000,
+            end: 2100,
+            deposit: Uint128(1000),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 2000), create_msg.clone());
+        assert!(handle_result.is_ok(), "{}", handle_result.err().unwrap());
+        let freeze_msg = |frozen: bool| {
+            let address = HumanAddr("alice".to_string());
+            if frozen {
+                HandleMsg::FreezeAccount {
+                    address,
+                    reason: None,
+                    padding: None,
+                }
+            } else {
+                HandleMsg::UnfreezeAccount {
+                    address,
+                    reason: None,
+                    padding: None,
+                }
+            }
+        };
+        let handle_result = handle(&mut deps, env_at("admin", 2010), freeze_msg(true));
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env_at("bob", 2010), create_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is frozen"));
+
+        let cancel_msg = HandleMsg::CancelStream {
+            stream_id: 2,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 2040), cancel_msg);
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::CancelStream { paid, refunded, .. } => {
+                assert_eq!(paid, Uint128(0));
+                assert_eq!(refunded, Uint128(600));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // What had streamed is held for the recipient until it can receive it
+        let withdraw_msg = HandleMsg::WithdrawFromStream {
+            stream_id: 2,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("alice", 2050), withdraw_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The recipient account is frozen"));
+        let handle_result = handle(&mut deps, env_at("admin", 2060), freeze_msg(false));
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env_at("alice", 2080), withdraw_msg);
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::WithdrawFromStream { withdrawn, .. } => {
+                assert_eq!(withdrawn, Uint128(400))
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&bob), 4100);
+        assert_eq!(balances.account_amount(&alice), 900);
+    }
+
+    #[test]
+    fn test_handle_mandates() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let create_msg = |max_count: u32, first_due: Option<u64>, expiration: u64| {
+            HandleMsg::CreateMandate {
+                payee: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                period: 30,
+                max_count,
+                first_due,
+                expiration: Some(expiration),
+                padding: None,
+            }
+        };
+        let execute_msg = HandleMsg::ExecuteMandate {
+            payer: HumanAddr("bob".to_string()),
+            payee: HumanAddr("alice".to_string()),
+            padding: None,
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_result = handle(&mut deps, env_at("carol", 1000), execute_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("No mandate from this payer to this payee"));
+
+        let handle_result = handle(&mut deps, env_at("bob", 1000), create_msg(2, None, 1100));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Anyone can execute a due installment, but only into the payee's account
+        let handle_result = handle(&mut deps, env_at("carol", 1000), execute_msg.clone());
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::ExecuteMandate {
+                installment,
+                remaining,
+                ..
+            } => {
+                assert_eq!(installment, 1);
+                assert_eq!(remaining, 1);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_result = handle(&mut deps, env_at("carol", 1010), execute_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The next installment is not due until 1030"));
+
+        let handle_result = handle(&mut deps, env_at("alice", 1030), execute_msg.clone());
+        assert!(handle_result.is_ok());
+        let handle_result = handle(&mut deps, env_at("alice", 1060), execute_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This mandate has no installments left"));
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("bob", 1060),
+            create_msg(5, Some(2000), 2000),
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env_at("alice", 2000), execute_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This mandate has expired"));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("alice", &[]),
+            HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let query_msg = QueryMsg::Mandate {
+            payer: HumanAddr("bob".to_string()),
+            payee: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+        };
+        let query_answer: QueryAnswer =
+            from_binary(&query(&deps, query_msg.clone()).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Mandate { mandate, .. } => assert_eq!(
+                mandate,
+                Some(MandateInfo {
+                    amount: Uint128(100),
+                    period: 30,
+                    max_count: 5,
+                    executed: 0,
+                    next_due: 2000,
+                    expiration: Some(2000),
+                })
+            ),
+            _ => panic!("unexpected"),
+        }
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::RevokeMandate {
+                payee: HumanAddr("alice".to_string()),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Mandate { mandate, .. } => assert_eq!(mandate, None),
+            _ => panic!("unexpected"),
+        }
+
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&alice),
+            200
+        );
+    }
+
+    #[test]
+    fn test_handle_allowance_period_limit() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let transfer_from_msg = |amount: u128| HandleMsg::TransferFrom {
+            owner: HumanAddr("bob".to_string()),
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let increase_msg = |period_seconds: u64| HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(1000),
+            expiration: None,
+            period_limit: Some(SpendingLimit {
+                limit: Uint128(300),
+                period_seconds,
+            }),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 1000), increase_msg(0));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("A period limit requires a period of at least one second"));
+        let handle_result = handle(&mut deps, env_at("bob", 1000), increase_msg(86400));
+        assert!(handle_result.is_ok());
+
+        let handle_result = handle(&mut deps, env_at("alice", 1000), transfer_from_msg(200));
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env_at("alice", 2000), transfer_from_msg(200));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains(
+            "This exceeds the allowance's limit for the current period: remaining=100, required=200"
+        ));
+
+        // The limit is available again once the period is over
+        let handle_result = handle(&mut deps, env_at("alice", 87400), transfer_from_msg(200));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let query_msg = QueryMsg::Allowance {
+            owner: HumanAddr("bob".to_string()),
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::Allowance {
+                allowance,
+                period_limit,
+                ..
+            } => {
+                assert_eq!(allowance, Uint128(600));
+                assert_eq!(
+                    period_limit,
+                    Some(PeriodLimitInfo {
+                        limit: Uint128(300),
+                        period_seconds: 86400,
+                        spent: Uint128(200),
+                        resets_at: 173800,
+                    })
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // Decreasing an expired allowance drops its period limit along with it
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("carol".to_string()),
+            amount: Uint128(1000),
+            expiration: Some(5000),
+            period_limit: Some(SpendingLimit {
+                limit: Uint128(300),
+                period_seconds: 86400,
+            }),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 1000), handle_msg);
+        assert!(handle_result.is_ok());
+        let handle_msg = HandleMsg::DecreaseAllowance {
+            spender: HumanAddr("carol".to_string()),
+            amount: Uint128(0),
+            expiration: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 6000), handle_msg);
+        assert!(handle_result.is_ok());
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("carol".to_string()),
+            amount: Uint128(500),
+            expiration: None,
+            period_limit: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 6000), handle_msg);
+        assert!(handle_result.is_ok());
+
+        let bob = deps.api.canonical_address(&HumanAddr("bob".to_string())).unwrap();
+        let carol = deps.api.canonical_address(&HumanAddr("carol".to_string())).unwrap();
+        let allowance = read_allowance(&deps.storage, &bob, &carol).unwrap();
+        assert_eq!(allowance.amount, 500);
+        assert_eq!(allowance.period_limit, None);
+        let handle_result = handle(&mut deps, env_at("carol", 6000), transfer_from_msg(400));
+        assert!(ensure_success(handle_result.unwrap()));
+    }
+
+    #[test]
+    fn test_handle_allowance_index() {
+        let increase_msg = |spender: &str, expiration: Option<u64>| HandleMsg::IncreaseAllowance {
+            spender: HumanAddr(spender.to_string()),
+            amount: Uint128(100),
+            expiration,
+            period_limit: None,
+            padding: None,
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        for (owner, spender, expiration) in [
+            ("bob", "alice", None),
+            ("bob", "carol", Some(2000)),
+            ("dave", "alice", None),
+            // Writing an allowance again doesn't list it twice
+            ("bob", "alice", None),
+        ]
+        .iter()
+        {
+            let handle_result = handle(
+                &mut deps,
+                mock_env(*owner, &[]),
+                increase_msg(spender, *expiration),
+            );
+            assert!(handle_result.is_ok());
+        }
+        for account in ["bob", "alice"].iter() {
+            let handle_result = handle(
+                &mut deps,
+                mock_env(*account, &[]),
+                HandleMsg::SetViewingKey {
+                    key: "key".to_string(),
+                    padding: None,
+                },
+            );
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+            time: Some(3000),
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::AllowancesGiven {
+                allowances, total, ..
+            } => {
+                assert_eq!(total, 2);
+                assert_eq!(
+                    allowances,
+                    vec![
+                        AllowanceInfo {
+                            owner: HumanAddr("bob".to_string()),
+                            spender: HumanAddr("carol".to_string()),
+                            allowance: Uint128(100),
+                            expiration: Some(2000),
+                            expired: Some(true),
+                            period_limit: None,
+                        },
+                        AllowanceInfo {
+                            owner: HumanAddr("bob".to_string()),
+                            spender: HumanAddr("alice".to_string()),
+                            allowance: Uint128(200),
+                            expiration: None,
+                            expired: Some(false),
+                            period_limit: None,
+                        },
+                    ]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: Some(1),
+            page_size: 1,
+            time: None,
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::AllowancesReceived {
+                allowances, total, ..
+            } => {
+                assert_eq!(total, 2);
+                assert_eq!(allowances.len(), 1);
+                assert_eq!(allowances[0].owner, HumanAddr("bob".to_string()));
+                assert_eq!(allowances[0].expired, None);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "wrong".to_string(),
+            page: None,
+            page_size: 10,
+            time: None,
+        };
+        let error = extract_error_msg(query(&deps, query_msg));
+        assert!(error.contains("Wrong viewing key"));
+
+        // Zero allowances aren't listed, so nobody can pad another account's index for free
+        let handle_result = handle(
+            &mut deps,
+            mock_env("eve", &[]),
+            HandleMsg::IncreaseAllowance {
+                spender: HumanAddr("alice".to_string()),
+                amount: Uint128(0),
+                expiration: None,
+                period_limit: None,
+                padding: None,
+            },
+        );
+        assert!(handle_result.is_ok());
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::DecreaseAllowance {
+                spender: HumanAddr("carol".to_string()),
+                amount: Uint128(100),
+                expiration: None,
+                padding: None,
+            },
+        );
+        assert!(handle_result.is_ok());
+        let handle_result = handle(
+            &mut deps,
+            mock_env("erin", &[]),
+            HandleMsg::IncreaseAllowance {
+                spender: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                expiration: None,
+                period_limit: Some(SpendingLimit {
+                    limit: Uint128(50),
+                    period_seconds: 3600,
+                }),
+                padding: None,
+            },
+        );
+        assert!(handle_result.is_ok());
+
+        let query_msg = QueryMsg::AllowancesGiven {
+            owner: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+            time: None,
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::AllowancesGiven {
+                allowances, total, ..
+            } => {
+                assert_eq!(total, 1);
+                assert_eq!(allowances[0].spender, HumanAddr("alice".to_string()));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let query_msg = QueryMsg::AllowancesReceived {
+            spender: HumanAddr("alice".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+            time: None,
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::AllowancesReceived {
+                allowances, total, ..
+            } => {
+                assert_eq!(total, 3);
+                assert_eq!(allowances[0].owner, HumanAddr("erin".to_string()));
+                assert_eq!(
+                    allowances[0].period_limit,
+                    Some(PeriodLimitInfo {
+                        limit: Uint128(50),
+                        period_seconds: 3600,
+                        spent: Uint128(0),
+                        resets_at: mock_env("erin", &[]).block.time + 3600,
+                    })
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_handle_set_and_revoke_allowances() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(100),
+            expiration: Some(1_000_000),
+            period_limit: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(handle_result.is_ok());
+
+        // The amount and expiration are replaced rather than adjusted
+        let handle_msg = HandleMsg::SetAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(50),
+            expiration: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::SetAllowance { allowance } => assert_eq!(
+                allowance,
+                AllowanceInfo {
+                    owner: HumanAddr("bob".to_string()),
+                    spender: HumanAddr("alice".to_string()),
+                    allowance: Uint128(50),
+                    expiration: None,
+                    expired: Some(false),
+                    period_limit: None,
+                }
+            ),
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::BatchSetAllowance {
+            actions: vec![
+                batch::SetAllowanceAction {
+                    spender: HumanAddr("alice".to_string()),
+                    amount: Uint128(10),
+                    expiration: None,
+                },
+                batch::SetAllowanceAction {
+                    spender: HumanAddr("carol".to_string()),
+                    amount: Uint128(20),
+                    expiration: Some(1000),
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::BatchSetAllowance { allowances, .. } => {
+                assert_eq!(allowances.len(), 2);
+                assert_eq!(allowances[0].allowance, Uint128(10));
+                assert_eq!(allowances[1].expired, Some(true));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // An allowance written before the index existed is only revoked when listed explicitly
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let dave = deps
+            .api
+            .canonical_address(&HumanAddr("dave".to_string()))
+            .unwrap();
+        {
+            let mut allowance_store = PrefixedStorage::multilevel(
+                &[PREFIX_ALLOWANCES, bob.as_slice()],
+                &mut deps.storage,
+            );
+            allowance_store.set(
+                dave.as_slice(),
+                &bincode2::serialize(&(30u128, None::<u64>)).unwrap(),
+            );
+        }
+
+        let handle_msg = HandleMsg::RevokeAllAllowances {
+            spenders: Some(vec![
+                HumanAddr("dave".to_string()),
+                HumanAddr("alice".to_string()),
+            ]),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::RevokeAllAllowances { allowances, .. } => {
+                let spenders: Vec<HumanAddr> =
+                    allowances.iter().map(|info| info.spender.clone()).collect();
+                assert_eq!(
+                    spenders,
+                    vec![
+                        HumanAddr("carol".to_string()),
+                        HumanAddr("alice".to_string()),
+                        HumanAddr("dave".to_string()),
+                    ]
+                );
+                assert!(allowances.iter().all(|info| info.allowance == Uint128(0)));
+            }
+            _ => panic!("unexpected"),
+        }
+        assert_eq!(read_allowance(&deps.storage, &bob, &dave).unwrap().amount, 0);
+
+        let handle_msg = HandleMsg::TransferFrom {
+            owner: HumanAddr("bob".to_string()),
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(1),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+    }
+
+    #[test]
+    fn test_handle_transfer_with_permit() {
+        let owner = permit_signer_helper();
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: owner.clone(),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env(owner.0.clone(), &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let nonce = |deps: &Extern<MockStorage, MockApi, MockQuerier>| {
+            let query_msg = QueryMsg::PermitNonce {
+                address: owner.clone(),
+                key: "key".to_string(),
+            };
+            match from_binary(&query(deps, query_msg).unwrap()).unwrap() {
+                QueryAnswer::PermitNonce { nonce } => nonce,
+                other => panic!("Unexpected: {:?}", other),
+            }
+        };
+        assert_eq!(nonce(&deps), 0);
+
+        let handle_msg = HandleMsg::TransferWithPermit {
+            permit: signed_transfer_permit_helper(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("relayer", &[]), handle_msg.clone());
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(nonce(&deps), 1);
+
+        let owner_address = deps.api.canonical_address(&owner).unwrap();
+        let alice = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&owner_address), 4900);
+            assert_eq!(balances.account_amount(&alice), 100);
+        }
+
+        // A used permit can't be replayed
+        let handle_result = handle(&mut deps, mock_env("relayer", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This permit's nonce is 0, expected 1"));
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&alice),
+            100
+        );
+    }
+
+    #[test]
+    fn test_handle_transfer_with_permit_rejections() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let env = mock_env("relayer", &[]);
+        let permit = |token: HumanAddr, chain_id: String, expires_at: u64| TransferPermit {
+            params: TransferPermitParams {
+                amount: Uint128(100),
+                chain_id,
+                expires_at,
+                memo: None,
+                nonce: 0,
+                owner: HumanAddr("bob".to_string()),
+                permit_name: "relay".to_string(),
+                recipient: HumanAddr("alice".to_string()),
+                token,
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary(vec![2; 33]),
+                },
+                signature: Binary(vec![0; 64]),
+            },
+        };
+        let cases = vec![
+            (
+                permit(
+                    HumanAddr("other-token".to_string()),
+                    env.block.chain_id.clone(),
+                    env.block.time + 100,
+                ),
+                "This permit is for token other-token",
+            ),
+            (
+                permit(
+                    env.contract.address.clone(),
+                    "other-chain".to_string(),
+                    env.block.time + 100,
+                ),
+                "This permit is for chain other-chain",
+            ),
+            (
+                permit(
+                    env.contract.address.clone(),
+                    env.block.chain_id.clone(),
+                    env.block.time,
+                ),
+                "This permit expired",
+            ),
+        ];
+        for (permit, expected) in cases {
+            let handle_msg = HandleMsg::TransferWithPermit {
+                permit,
+                padding: None,
+            };
+            let handle_result = handle(&mut deps, env.clone(), handle_msg);
+            let error = extract_error_msg(handle_result);
+            assert!(error.contains(expected), "{}", error);
+        }
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::PermitNonce {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::PermitNonce { nonce } => assert_eq!(nonce, 0),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_handle_approve_with_permit() {
+        let owner = permit_signer_helper();
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: owner.clone(),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::ApproveWithPermit {
+            permit: signed_approve_permit_helper(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("dex", &[]), handle_msg.clone());
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::ApproveWithPermit { allowance } => assert_eq!(
+                allowance,
+                AllowanceInfo {
+                    owner: owner.clone(),
+                    spender: HumanAddr("alice".to_string()),
+                    allowance: Uint128(300),
+                    expiration: None,
+                    expired: Some(false),
+                    period_limit: None,
+                }
+            ),
+            _ => panic!("unexpected"),
+        }
+
+        let transfer_from_msg = HandleMsg::TransferFrom {
+            owner: owner.clone(),
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(300),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), transfer_from_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // Transfer and approve permits share the owner's nonce, and neither can be replayed
+        let handle_result = handle(&mut deps, mock_env("dex", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This permit's nonce is 0, expected 1"));
+        let handle_msg = HandleMsg::TransferWithPermit {
+            permit: signed_transfer_permit_helper(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("relayer", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This permit's nonce is 0, expected 1"));
+
+        let owner_address = deps.api.canonical_address(&owner).unwrap();
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&owner_address),
+            4700
+        );
+    }
+
+    #[test]
+    fn test_handle_approve_with_permit_rejections() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::RevokePermit {
+            permit_name: "dex".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(handle_result.is_ok());
+
+        let env = mock_env("relayer", &[]);
+        let permit = |permit_name: &str, expires_at: u64| ApprovePermit {
+            params: ApprovePermitParams {
+                amount: Uint128(100),
+                chain_id: env.block.chain_id.clone(),
+                expiration: None,
+                expires_at,
+                nonce: 0,
+                owner: HumanAddr("bob".to_string()),
+                permit_name: permit_name.to_string(),
+                spender: HumanAddr("alice".to_string()),
+                token: env.contract.address.clone(),
+            },
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary(vec![2; 33]),
+                },
+                signature: Binary(vec![0; 64]),
+            },
+        };
+        let cases = vec![
+            (permit("dex", env.block.time + 100), "was revoked"),
+            (permit("other", env.block.time), "This permit expired"),
+        ];
+        for (permit, expected) in cases {
+            let handle_msg = HandleMsg::ApproveWithPermit {
+                permit,
+                padding: None,
+            };
+            let handle_result = handle(&mut deps, env.clone(), handle_msg);
+            let error = extract_error_msg(handle_result);
+            assert!(error.contains(expected), "{}", error);
+        }
+
+        let bob_canonical = deps.api.canonical_address(&HumanAddr("bob".to_string())).unwrap();
+        let alice_canonical = deps.api.canonical_address(&HumanAddr("alice".to_string())).unwrap();
+        let allowance = read_allowance(&deps.storage, &bob_canonical, &alice_canonical).unwrap();
+        assert_eq!(allowance.amount, 0);
+    }
+
+    #[test]
+    fn test_handle_revoke_all_permits() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        for permit_name in ["a", "b", "a"].iter() {
+            let handle_msg = HandleMsg::RevokePermit {
+                permit_name: permit_name.to_string(),
+                padding: None,
+            };
+            let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+            assert!(handle_result.is_ok());
+        }
+        let revoke_all = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>, sender: &str| {
+            let handle_msg = HandleMsg::RevokeAllPermits { padding: None };
+            let handle_result = handle(deps, mock_env(sender, &[]), handle_msg);
+            match from_binary(&handle_result.unwrap().data.unwrap()).unwrap() {
+                HandleAnswer::RevokeAllPermits { tag, .. } => tag,
+                _ => panic!("unexpected"),
+            }
+        };
+        let tag = revoke_all(&mut deps, "bob");
+        assert_eq!(tag.len(), 16);
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::RevokedPermits {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::RevokedPermits {
+                permit_names,
+                total,
+                tag: current_tag,
+            } => {
+                assert_eq!(permit_names, vec!["b".to_string(), "a".to_string()]);
+                assert_eq!(total, 2);
+                assert_eq!(current_tag, Some(tag.clone()));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // A permit is only valid if its name is tagged with the current tag
+        let env = mock_env("relayer", &[]);
+        let approve_with_name = |permit_name: String| HandleMsg::ApproveWithPermit {
+            permit: ApprovePermit {
+                params: ApprovePermitParams {
+                    amount: Uint128(100),
+                    chain_id: env.block.chain_id.clone(),
+                    expiration: None,
+                    expires_at: env.block.time + 100,
+                    nonce: 0,
+                    owner: HumanAddr("bob".to_string()),
+                    permit_name,
+                    spender: HumanAddr("alice".to_string()),
+                    token: env.contract.address.clone(),
+                },
+                signature: PermitSignature {
+                    pub_key: PubKey {
+                        r#type: "tendermint/PubKeySecp256k1".to_string(),
+                        value: Binary(vec![2; 33]),
+                    },
+                    signature: Binary(vec![0; 64]),
+                },
+            },
+            padding: None,
+        };
+        let tagged = format!("dex@{}", tag);
+        let names = [("dex", true), ("dex@1", true), (tagged.as_str(), false)];
+        for (permit_name, wrong_tag) in names.iter() {
+            let handle_msg = approve_with_name(permit_name.to_string());
+            let handle_result = handle(&mut deps, env.clone(), handle_msg);
+            // All fail, the last one only for its made up signature
+            let error = extract_error_msg(handle_result);
+            let tag_error = error.contains("isn't valid for the current permit tag");
+            assert_eq!(tag_error, *wrong_tag, "{}", error);
+        }
+
+        // Revoking again draws a new tag, which the old one isn't valid for
+        let new_tag = revoke_all(&mut deps, "bob");
+        assert_ne!(new_tag, tag);
+        let handle_result = handle(&mut deps, env.clone(), approve_with_name(tagged));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("isn't valid for the current permit tag"));
+
+        // Names aren't checked before the first revocation, even if they look tagged
+        let owner = permit_signer_helper();
+        let mut permit = signed_approve_permit_helper();
+        permit.params.permit_name = "dex@7".to_string();
+        permit.signature = permit_signature_helper(
+            "Sb5b22mhQKO70Y60Zoi71OnZfuF3g7BG94eoKun6kxkI3P9f8IwcmLFsLqfPCRYyucH8/DT+odECVNYw8w1Ppg==",
+        );
+        let handle_msg = HandleMsg::ApproveWithPermit {
+            permit,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env.clone(), handle_msg);
+        assert!(handle_result.is_ok(), "{}", handle_result.err().unwrap());
+
+        // A permit signed ahead of time with a guessed suffix doesn't survive the revocation
+        let mut permit = signed_approve_permit_helper();
+        permit.params.nonce = 1;
+        permit.params.permit_name = "dex@1".to_string();
+        permit.signature = permit_signature_helper(
+            "IQ68hIM9QyspjbTp/qG097BFjQ6IVXscYiTVpkZ7HON+M98tJp/6B4i5/WiOH7FZ+7mYdmvaY4O8Lc43hJ/5mg==",
+        );
+        revoke_all(&mut deps, owner.as_str());
+        let handle_msg = HandleMsg::ApproveWithPermit {
+            permit,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env.clone(), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("isn't valid for the current permit tag"));
+
+        let handle_msg = HandleMsg::TransferWithPermit {
+            permit: signed_transfer_permit_helper(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env, handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("isn't valid for the current permit tag"));
+    }
+
+    #[test]
+    fn test_handle_set_permit_scope() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::SetPermitScope {
+            permit_name: "audit".to_string(),
+            scope: PermitScope {
+                history: Some(HistoryRange {
+                    from_time: Some(200),
+                    to_time: Some(100),
+                    ..HistoryRange::default()
+                }),
+                ..PermitScope::default()
+            },
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("A history range can't end before it starts"));
+
+        let scope = PermitScope {
+            expires_at: Some(2_000_000_000),
+            history: Some(HistoryRange {
+                from_id: Some(2),
+                to_time: Some(1000),
+                ..HistoryRange::default()
+            }),
+            counterparties: Some(vec![HumanAddr("alice".to_string())]),
+        };
+        let handle_msg = HandleMsg::SetPermitScope {
+            permit_name: "audit".to_string(),
+            scope: scope.clone(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let bob = HumanAddr("bob".to_string());
+        assert_eq!(read_permit_scope(&deps.storage, &bob, "audit").unwrap(), scope);
+        assert_eq!(
+            read_permit_scope(&deps.storage, &bob, "other").unwrap(),
+            PermitScope::default()
+        );
+
+        let range = scope.history.unwrap();
+        assert!(range.contains(2, Some(1000)));
+        assert!(!range.contains(1, Some(1000)));
+        assert!(!range.contains(2, Some(1001)));
+        assert!(!range.contains(2, None));
+    }
+
+    #[test]
+    fn test_permit_scope_enforcement() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let transfer_at = |deps: &mut Extern<MockStorage, MockApi, MockQuerier>, time: u64| {
+            let handle_msg = HandleMsg::Transfer {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            };
+            let handle_result = handle(deps, env_at("bob", time), handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        };
+
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        let bob = HumanAddr("bob".to_string());
+        let alice = HumanAddr("alice".to_string());
+
+        // Before anything was handled, only the initial balance's time counts, and only for bob
+        let init_time = mock_env("instantiator", &[]).block.time;
+        let scope = PermitScope {
+            expires_at: Some(init_time),
+            ..PermitScope::default()
+        };
+        let error = extract_error_msg(check_scope_expiry(&deps, &bob, &scope, "audit"));
+        assert!(error.contains(&format!("Permit \"audit\" expired at {}", init_time)));
+        let carol = HumanAddr("carol".to_string());
+        assert!(check_scope_expiry(&deps, &carol, &scope, "audit").is_ok());
+
+        // Afterwards a scope expires with the first transaction at or after `expires_at`, even for
+        // dormant accounts and if that transaction doesn't write any history
+        let scope = PermitScope {
+            expires_at: Some(2_000_000_000),
+            ..PermitScope::default()
+        };
+        transfer_at(&mut deps, 1_999_999_999);
+        assert!(check_scope_expiry(&deps, &bob, &scope, "audit").is_ok());
+        assert!(check_scope_expiry(&deps, &carol, &scope, "audit").is_ok());
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("dave", 2_000_000_000), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).block_time(),
+            2_000_000_000
+        );
+        for account in [&bob, &carol].iter() {
+            let error = extract_error_msg(check_scope_expiry(&deps, account, &scope, "audit"));
+            assert!(error.contains("Permit \"audit\" expired at 2000000000"));
+        }
+        assert!(check_scope_expiry(&deps, &bob, &PermitScope::default(), "audit").is_ok());
+
+        let scope = PermitScope {
+            history: Some(HistoryRange::default()),
+            counterparties: Some(vec![alice.clone()]),
+            ..PermitScope::default()
+        };
+        let error = extract_error_msg(check_no_history_range(&scope, "audit", "escrows"));
+        assert!(error.contains("is limited to a history range, so it can't query escrows"));
+        assert!(check_counterparty(&scope, "audit", &alice).is_ok());
+        let error = extract_error_msg(check_counterparty(&scope, "audit", &bob));
+        assert!(error.contains("doesn't cover allowances with \"bob\""));
+        let error = extract_error_msg(check_no_counterparties(&scope, "audit"));
+        assert!(error.contains("is limited to allowances with specific counterparties"));
+
+        let scope = PermitScope::default();
+        assert!(check_no_history_range(&scope, "audit", "escrows").is_ok());
+        assert!(check_counterparty(&scope, "audit", &bob).is_ok());
+        assert!(check_no_counterparties(&scope, "audit").is_ok());
+    }
+
+    #[test]
+    fn test_query_history_range() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        for time in [100, 200, 300].iter() {
+            let mut env = mock_env("bob", &[]);
+            env.block.time = *time;
+            let handle_msg = HandleMsg::Transfer {
+                recipient: HumanAddr("alice".to_string()),
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            };
+            let handle_result = handle(&mut deps, env, handle_msg);
+            assert!(ensure_success(handle_result.unwrap()));
+        }
+        let bob = HumanAddr("bob".to_string());
+
+        let range = HistoryRange {
+            from_time: Some(150),
+            to_time: Some(300),
+            ..HistoryRange::default()
+        };
+        let query_result = query_transfers(&deps, &bob, 0, 10, Some(&range));
+        let (transfers, total) = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransferHistory { txs, total } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(
+            transfers.iter().map(|tx| tx.block_time).collect::<Vec<_>>(),
+            vec![Some(300), Some(200)]
+        );
+        assert_eq!(total, None);
+
+        // The mint at init is outside the time bounds
+        let query_result = query_transactions(&deps, &bob, 0, 10, Some(&range));
+        let txs = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, .. } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(txs.iter().map(|tx| tx.block_time).collect::<Vec<_>>(), vec![300, 200]);
+
+        let query_result = query_transactions(&deps, &bob, 0, 10, None);
+        let (txs, total) = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, total } => (txs, total),
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(txs.len(), 4);
+        assert_eq!(total, Some(4));
+
+        let range = HistoryRange {
+            from_id: Some(txs[2].id),
+            to_id: Some(txs[1].id),
+            ..HistoryRange::default()
+        };
+        let query_result = query_transactions(&deps, &bob, 0, 10, Some(&range));
+        let ranged = match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TransactionHistory { txs, .. } => txs,
+            _ => panic!("Unexpected"),
+        };
+        assert_eq!(ranged.iter().map(|tx| tx.id).collect::<Vec<_>>(), vec![txs[1].id, txs[2].id]);
+    }
+
+    #[test]
+    fn test_handle_vesting() {
+        let env_at = |sender: &str, time: u64| {
+            let mut env = mock_env(sender, &[]);
+            env.block.time = time;
+            env
+        };
+        let terms = |revocable: bool| VestingTerms {
+            start_time: 1000,
+            cliff_time: 1250,
+            end_time: 2000,
+            revocable,
+        };
+
+        let mut deps = mock_dependencies(20, &[]);
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: Some(vec![InitialBalance {
+                address: HumanAddr("admin".to_string()),
+                amount: Uint128(5000),
+            }]),
+            initial_vesting: Some(vec![InitialVesting {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(1000),
+                terms: terms(false),
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: None,
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        assert_eq!(ReadonlyConfig::from_storage(&deps.storage).total_supply(), 6000);
+
+        // Locked tokens are not part of the balance and can't be transferred
+        let transfer_msg = HandleMsg::Transfer {
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(100),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 1500), transfer_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient funds"));
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("bob", 1200),
+            HandleMsg::ClaimVested { padding: None },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There are no vested tokens to claim"));
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("bob", 1500),
+            HandleMsg::ClaimVested { padding: None },
+        );
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::ClaimVested { claimed, .. } => assert_eq!(claimed, Uint128(500)),
+            _ => panic!("unexpected"),
+        }
+        let handle_result = handle(&mut deps, env_at("bob", 1500), transfer_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let create_msg = |revocable: bool| HandleMsg::CreateVesting {
+            recipient: HumanAddr("carol".to_string()),
+            amount: Uint128(2000),
+            terms: terms(revocable),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("bob", 1500), create_msg(true));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("admin", 1000),
+            HandleMsg::CreateVesting {
+                recipient: HumanAddr("carol".to_string()),
+                amount: Uint128(2000),
+                terms: VestingTerms {
+                    start_time: 1000,
+                    cliff_time: 3000,
+                    end_time: 2000,
+                    revocable: true,
+                },
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("which must be no later than its end"));
+
+        let handle_result = handle(&mut deps, env_at("admin", 1000), create_msg(true));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let admin = deps
+            .api
+            .canonical_address(&HumanAddr("admin".to_string()))
+            .unwrap();
+        let carol = deps
+            .api
+            .canonical_address(&HumanAddr("carol".to_string()))
+            .unwrap();
+        assert_eq!(
+            ReadonlyBalances::from_storage(&deps.storage).account_amount(&admin),
+            3000
+        );
+
+        // Revoking keeps what has vested so far and returns the rest
+        let revoke_msg = |index: u32| HandleMsg::RevokeVesting {
+            account: HumanAddr("carol".to_string()),
+            index,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("admin", 1750), revoke_msg(1));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("No vesting schedule at index 1"));
+
+        let handle_result = handle(&mut deps, env_at("admin", 1750), revoke_msg(0));
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::RevokeVesting { returned, .. } => assert_eq!(returned, Uint128(500)),
+            _ => panic!("unexpected"),
+        }
+        let handle_result = handle(&mut deps, env_at("admin", 1750), revoke_msg(0));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This vesting schedule was already revoked"));
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("admin", 1500),
+            HandleMsg::RevokeVesting {
+                account: HumanAddr("bob".to_string()),
+                index: 0,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This vesting schedule is not revocable"));
+
+        let handle_result = handle(
+            &mut deps,
+            env_at("carol", 1800),
+            HandleMsg::ClaimVested { padding: None },
+        );
+        assert!(handle_result.is_ok());
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&admin), 3500);
+            assert_eq!(balances.account_amount(&carol), 1500);
+        }
+
+        let (txs, total) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &carol, 0, 10).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::VestingClaim {
+                beneficiary: HumanAddr("carol".to_string()),
+            }
+        );
+        assert_eq!(txs[0].coins.amount, Uint128(1500));
+        assert_eq!(
+            txs[1].action,
+            crate::transaction_history::TxAction::VestingRevoke {
+                beneficiary: HumanAddr("carol".to_string()),
+                by: HumanAddr("admin".to_string()),
+            }
+        );
+        assert_eq!(txs[1].coins.amount, Uint128(500));
+        assert_eq!(
+            txs[2].action,
+            crate::transaction_history::TxAction::VestingCreate {
+                beneficiary: HumanAddr("carol".to_string()),
+                by: HumanAddr("admin".to_string()),
+            }
+        );
+        assert_eq!(txs[2].coins.amount, Uint128(2000));
+
+        // The schedule query requires a viewing key
+        let handle_result = handle(
+            &mut deps,
+            mock_env("bob", &[]),
+            HandleMsg::SetViewingKey {
+                key: "key".to_string(),
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_msg = QueryMsg::VestingSchedule {
+            address: HumanAddr("bob".to_string()),
+            key: "wrong".to_string(),
+            time: None,
+        };
+        let query_result = query(&deps, query_msg);
+        let error = extract_error_msg(query_result);
+        assert!(error.contains("Wrong viewing key"));
+
+        let query_msg = QueryMsg::VestingSchedule {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            time: Some(1750),
+        };
+        let query_answer: QueryAnswer = from_binary(&query(&deps, query_msg).unwrap()).unwrap();
+        match query_answer {
+            QueryAnswer::VestingSchedule { schedules } => {
+                assert_eq!(
+                    schedules,
+                    vec![VestingInfo {
+                        index: 0,
+                        amount: Uint128(1000),
+                        claimed: Uint128(500),
+                        terms: terms(false),
+                        revoked_at: None,
+                        vested: Some(Uint128(750)),
+                        claimable: Some(Uint128(250)),
+                    }]
+                );
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // The remainder goes back to whoever funded the schedule, even after an admin handover
+        let handle_result = handle(
+            &mut deps,
+            env_at("admin", 1800),
+            HandleMsg::CreateVesting {
+                recipient: HumanAddr("carol".to_string()),
+                amount: Uint128(2000),
+                terms: VestingTerms {
+                    start_time: 3000,
+                    cliff_time: 3000,
+                    end_time: 4000,
+                    revocable: true,
+                },
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_msg = HandleMsg::ChangeAdmin {
+            address: HumanAddr("admin2".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, env_at("admin", 1900), handle_msg);
+        assert!(handle_result.is_ok());
+        let handle_msg = HandleMsg::AcceptAdmin { padding: None };
+        let handle_result = handle(&mut deps, env_at("admin2", 1900), handle_msg);
+        assert!(handle_result.is_ok());
+        let handle_result = handle(&mut deps, env_at("admin2", 3500), revoke_msg(1));
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::RevokeVesting { returned, .. } => assert_eq!(returned, Uint128(1000)),
+            _ => panic!("unexpected"),
+        }
+        let admin2 = deps
+            .api
+            .canonical_address(&HumanAddr("admin2".to_string()))
+            .unwrap();
+        {
+            let balances = ReadonlyBalances::from_storage(&deps.storage);
+            assert_eq!(balances.account_amount(&admin), 2500);
+            assert_eq!(balances.account_amount(&admin2), 0);
+        }
+        let (txs, _) =
+            crate::transaction_history::get_txs(&deps.api, &deps.storage, &admin, 0, 1).unwrap();
+        assert_eq!(
+            txs[0].action,
+            crate::transaction_history::TxAction::VestingRevoke {
+                beneficiary: HumanAddr("carol".to_string()),
+                by: HumanAddr("admin2".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_init_vesting() {
+        let init_msg = |amount: u128| InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            initial_balances: None,
+            initial_vesting: Some(vec![InitialVesting {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(amount),
+                terms: VestingTerms {
+                    start_time: 1000,
+                    cliff_time: 1250,
+                    end_time: 2000,
+                    revocable: false,
+                },
+            }]),
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(
+                from_binary(&Binary::from(r#"{ "enable_allowlist": true }"#.as_bytes())).unwrap(),
+            ),
+        };
+
+        let mut deps = mock_dependencies(20, &[]);
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg(0));
+        let error = extract_error_msg(init_result);
+        assert!(error.contains("Cannot vest zero tokens"));
+
+        // In allowlist mode beneficiaries are allowlisted, so that they can claim
+        let mut deps = mock_dependencies(20, &[]);
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg(1000));
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+        let mut env = mock_env("bob", &[]);
+        env.block.time = 2000;
+        let handle_result = handle(&mut deps, env, HandleMsg::ClaimVested { padding: None });
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::ClaimVested { claimed, .. } => assert_eq!(claimed, Uint128(1000)),
+            _ => panic!("unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_handle_send() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        let handle_msg = HandleMsg::Send {
+            recipient: HumanAddr("contract".to_string()),
+            recipient_code_hash: None,
+            amount: Uint128(100),
+            memo: Some("my memo".to_string()),
+            padding: None,
+            msg: Some(to_binary("hey hey you you").unwrap()),
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result.clone()));
+        assert!(result.messages.contains(&CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: HumanAddr("contract".to_string()),
+            callback_code_hash: "this_is_a_hash_of_a_code".to_string(),
+            msg: Hmip20ReceiveMsg::new(
+                HumanAddr("bob".to_string()),
+                HumanAddr("bob".to_string()),
+                Uint128(100),
+                Some("my memo".to_string()),
+                Some(to_binary("hey hey you you").unwrap())
+            )
+                .into_binary()
+                .unwrap(),
+            send: vec![]
+        })));
+    }
+
+    #[test]
+    fn test_handle_register_receive() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::RegisterReceive {
+            code_hash: "this_is_a_hash_of_a_code".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("contract", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result));
+
+        let hash = get_receiver_hash(&deps.storage, &HumanAddr("contract".to_string()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(hash, "this_is_a_hash_of_a_code".to_string());
+    }
+
+    #[test]
+    fn test_handle_create_viewing_key() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::CreateViewingKey {
+            entropy: "".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let answer: HandleAnswer = from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+
+        let key = match answer {
+            HandleAnswer::CreateViewingKey { key } => key,
+            _ => panic!("NOPE"),
+        };
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
+        assert!(key.check_viewing_key(saved_vk.as_slice()));
+    }
+
+    #[test]
+    fn test_handle_set_viewing_key() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Set VK
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "hi lol".to_string(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey {
+                status: ResponseStatus::Success
+            })
+                .unwrap(),
+        );
+
+        // Set valid VK
+        let actual_vk = ViewingKey("x".to_string().repeat(VIEWING_KEY_SIZE));
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: actual_vk.0.clone(),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let unwrapped_result: HandleAnswer =
+            from_binary(&handle_result.unwrap().data.unwrap()).unwrap();
+        assert_eq!(
+            to_binary(&unwrapped_result).unwrap(),
+            to_binary(&HandleAnswer::SetViewingKey { status: Success }).unwrap(),
+        );
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let saved_vk = read_viewing_key(&deps.storage, &bob_canonical).unwrap();
+        assert!(actual_vk.check_viewing_key(&saved_vk));
+    }
+
+    #[test]
+    fn test_handle_transfer_from() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // Transfer before allowance
+        let handle_msg = HandleMsg::TransferFrom {
+            owner: HumanAddr("bob".to_string()),
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(2500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("insufficient allowance"));
+
+        // Transfer more than allowance
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(2000),
+            padding: None,
+            expiration: Some(1_571_797_420),
+            period_limit: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        let handle_msg = HandleMsg::TransferFrom {
+            owner: HumanAddr("bob".to_string()),
+            recipient: HumanAddr("alice".to_string()),
+            amount: Uint128(2500),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
         let error = extract_error_msg(handle_result);
         assert!(error.contains("insufficient allowance"));
 
@@ -603,6 +3401,7 @@ mod tests {
             amount: Uint128(2000),
             padding: None,
             expiration: None,
+            period_limit: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         assert!(
@@ -750,6 +3549,7 @@ mod tests {
             amount: Uint128(2000),
             padding: None,
             expiration: None,
+            period_limit: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         assert!(
@@ -874,6 +3674,7 @@ mod tests {
                 amount: Uint128(allowance_size),
                 padding: None,
                 expiration: None,
+                period_limit: None,
             };
             let handle_result = handle(&mut deps, mock_env(*name, &[]), handle_msg);
             assert!(
@@ -1013,7 +3814,8 @@ mod tests {
             allowance,
             crate::state::Allowance {
                 amount: 0,
-                expiration: None
+                expiration: None,
+                period_limit: None,
             }
         );
 
@@ -1022,6 +3824,7 @@ mod tests {
             amount: Uint128(2000),
             padding: None,
             expiration: None,
+            period_limit: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         assert!(
@@ -1048,7 +3851,8 @@ mod tests {
             allowance,
             crate::state::Allowance {
                 amount: 1950,
-                expiration: None
+                expiration: None,
+                period_limit: None,
             }
         );
     }
@@ -1070,6 +3874,40 @@ mod tests {
             amount: Uint128(2000),
             padding: None,
             expiration: None,
+            period_limit: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let bob_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let alice_canonical = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+
+        let allowance = read_allowance(&deps.storage, &bob_canonical, &alice_canonical).unwrap();
+        assert_eq!(
+            allowance,
+            crate::state::Allowance {
+                amount: 2000,
+                expiration: None,
+                period_limit: None,
+            }
+        );
+
+        let handle_msg = HandleMsg::IncreaseAllowance {
+            spender: HumanAddr("alice".to_string()),
+            amount: Uint128(2000),
+            padding: None,
+            expiration: None,
+            period_limit: None,
         };
         let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
         assert!(
@@ -1078,51 +3916,412 @@ mod tests {
             handle_result.err().unwrap()
         );
 
-        let bob_canonical = deps
-            .api
-            .canonical_address(&HumanAddr("bob".to_string()))
-            .unwrap();
-        let alice_canonical = deps
-            .api
-            .canonical_address(&HumanAddr("alice".to_string()))
-            .unwrap();
+        let allowance = read_allowance(&deps.storage, &bob_canonical, &alice_canonical).unwrap();
+        assert_eq!(
+            allowance,
+            crate::state::Allowance {
+                amount: 4000,
+                expiration: None,
+                period_limit: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_handle_change_admin() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::ChangeAdmin {
+            address: HumanAddr("bob".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        // the admin doesn't change until the nominee accepts
+        let admin = ReadonlyConfig::from_storage(&deps.storage)
+            .constants()
+            .unwrap()
+            .admin;
+        assert_eq!(admin, Some(HumanAddr("admin".to_string())));
+
+        let handle_msg = HandleMsg::AcceptAdmin { padding: None };
+        let handle_result = handle(&mut deps, mock_env("alice", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the pending admin can accept the admin role"));
+
+        let handle_msg = HandleMsg::AcceptAdmin { padding: None };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let config = ReadonlyConfig::from_storage(&deps.storage);
+        assert_eq!(
+            config.constants().unwrap().admin,
+            Some(HumanAddr("bob".to_string()))
+        );
+        assert_eq!(config.pending_admin(), None);
+
+        // the pending nomination can't be replayed
+        let handle_msg = HandleMsg::AcceptAdmin { padding: None };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the pending admin can accept the admin role"));
+    }
+
+    #[test]
+    fn test_handle_cancel_admin_transfer() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::CancelAdminTransfer { padding: None };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("There is no pending admin transfer"));
+
+        let handle_msg = HandleMsg::ChangeAdmin {
+            address: HumanAddr("typo".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(&deps, QueryMsg::Admin {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Admin {
+                admin,
+                pending_admin,
+            } => {
+                assert_eq!(admin, Some(HumanAddr("admin".to_string())));
+                assert_eq!(pending_admin, Some(HumanAddr("typo".to_string())));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let handle_msg = HandleMsg::CancelAdminTransfer { padding: None };
+        let handle_result = handle(&mut deps, mock_env("typo", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_msg = HandleMsg::CancelAdminTransfer { padding: None };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::AcceptAdmin { padding: None };
+        let handle_result = handle(&mut deps, mock_env("typo", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the pending admin can accept the admin role"));
+    }
+
+    #[test]
+    fn test_handle_renounce_admin() {
+        let (init_result, mut deps) = init_helper(vec![InitialBalance {
+            address: HumanAddr("bob".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::ChangeAdmin {
+            address: HumanAddr("bob".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::RenounceAdmin { padding: None };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(&deps, QueryMsg::Admin {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Admin {
+                admin,
+                pending_admin,
+            } => {
+                assert_eq!(admin, None);
+                assert_eq!(pending_admin, None);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // renouncing also drops the pending nomination
+        let handle_msg = HandleMsg::AcceptAdmin { padding: None };
+        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Only the pending admin can accept the admin role"));
+
+        let handle_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+    }
+
+    #[test]
+    fn test_handle_roles() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("bob".to_string()),
+                amount: Uint128(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let handle_msg = HandleMsg::GrantRole {
+            role: Role::Pauser,
+            address: HumanAddr("pauser".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("pauser", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_msg = HandleMsg::GrantRole {
+            role: Role::Pauser,
+            address: HumanAddr("pauser".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::GrantRole {
+            role: Role::MinterManager,
+            address: HumanAddr("manager".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(&deps, QueryMsg::Roles {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::Roles { admin, roles } => {
+                assert_eq!(admin, Some(HumanAddr("admin".to_string())));
+                assert!(roles.contains(&RoleMembers {
+                    role: Role::Pauser,
+                    members: vec![HumanAddr("pauser".to_string())],
+                }));
+                assert!(roles.contains(&RoleMembers {
+                    role: Role::MinterManager,
+                    members: vec![HumanAddr("manager".to_string())],
+                }));
+                assert!(roles.contains(&RoleMembers {
+                    role: Role::FeeManager,
+                    members: vec![],
+                }));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // roles only grant their own permissions
+        let handle_msg = HandleMsg::AddMinters {
+            minters: vec![HumanAddr("pauser".to_string())],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("pauser", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the minter_manager role"));
+
+        let handle_msg = HandleMsg::AddMinters {
+            minters: vec![HumanAddr("bob".to_string())],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("manager", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+        let minters = ReadonlyConfig::from_storage(&deps.storage).minters();
+        assert_eq!(
+            minters,
+            vec![HumanAddr("admin".to_string()), HumanAddr("bob".to_string())]
+        );
+
+        let handle_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("manager", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the pauser role"));
+
+        let handle_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("pauser", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        // the pauser can still resume a stopped contract
+        let handle_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::NormalRun,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("pauser", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::RevokeRole {
+            role: Role::Pauser,
+            address: HumanAddr("pauser".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_msg = HandleMsg::SetContractStatus {
+            level: ContractStatusLevel::StopAll,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("pauser", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This command requires the pauser role"));
+    }
+
+    #[test]
+    fn test_handle_update_config() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let update_msg = HandleMsg::UpdateConfig {
+            public_total_supply: None,
+            enable_deposit: Some(true),
+            enable_redeem: None,
+            enable_mint: Some(false),
+            enable_burn: None,
+            lock: Some(vec![Feature::Mint]),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), update_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
 
-        let allowance = read_allowance(&deps.storage, &bob_canonical, &alice_canonical).unwrap();
-        assert_eq!(
-            allowance,
-            crate::state::Allowance {
-                amount: 2000,
-                expiration: None
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), update_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(&deps, QueryMsg::TokenConfig {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TokenConfig {
+                deposit_enabled,
+                mint_enabled,
+                locked_features,
+                ..
+            } => {
+                assert!(deposit_enabled);
+                assert!(!mint_enabled);
+                assert_eq!(locked_features, vec![Feature::Mint]);
             }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::Mint {
+                recipient: HumanAddr("lebron".to_string()),
+                amount: Uint128(100),
+                memo: None,
+                padding: None,
+            },
         );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Mint functionality is not enabled for this token"));
 
-        let handle_msg = HandleMsg::IncreaseAllowance {
-            spender: HumanAddr("alice".to_string()),
-            amount: Uint128(2000),
-            padding: None,
-            expiration: None,
-        };
-        let handle_result = handle(&mut deps, mock_env("bob", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        // A locked feature can't be switched back on
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::UpdateConfig {
+                public_total_supply: None,
+                enable_deposit: None,
+                enable_redeem: None,
+                enable_mint: Some(true),
+                enable_burn: None,
+                lock: None,
+                padding: None,
+            },
         );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The mint setting is locked"));
 
-        let allowance = read_allowance(&deps.storage, &bob_canonical, &alice_canonical).unwrap();
-        assert_eq!(
-            allowance,
-            crate::state::Allowance {
-                amount: 4000,
-                expiration: None
-            }
+        let query_result = query(
+            &deps,
+            QueryMsg::ConfigChanges {
+                page: None,
+                page_size: 10,
+            },
         );
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::ConfigChanges { changes, total } => {
+                assert_eq!(total, 3);
+                let env = mock_env("admin", &[]);
+                let change = |feature, enabled, locked| ConfigChange {
+                    feature,
+                    enabled,
+                    locked,
+                    changed_by: HumanAddr("admin".to_string()),
+                    block_time: env.block.time,
+                    block_height: env.block.height,
+                };
+                assert_eq!(
+                    changes,
+                    vec![
+                        change(Feature::Mint, false, true),
+                        change(Feature::Mint, false, false),
+                        change(Feature::Deposit, true, false),
+                    ]
+                );
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
     }
 
     #[test]
-    fn test_handle_change_admin() {
+    fn test_handle_set_token_metadata() {
         let (init_result, mut deps) = init_helper(vec![InitialBalance {
-            address: HumanAddr("bob".to_string()),
+            address: HumanAddr("lebron".to_string()),
             amount: Uint128(5000),
         }]);
         assert!(
@@ -1131,22 +4330,65 @@ mod tests {
             init_result.err().unwrap()
         );
 
-        let handle_msg = HandleMsg::ChangeAdmin {
-            address: HumanAddr("bob".to_string()),
+        let metadata = TokenMetadata {
+            description: Some("A token for testing".to_string()),
+            project_url: Some("https://example.com".to_string()),
+            logo: Some(Logo::Embedded(Binary::from(b"<svg></svg>".to_vec()))),
+            marketing_contact: Some("marketing@example.com".to_string()),
+        };
+        let handle_msg = HandleMsg::SetTokenMetadata {
+            name: Some("new-name".to_string()),
+            metadata: metadata.clone(),
             padding: None,
         };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
         let handle_result = handle(&mut deps, mock_env("admin", &[]), handle_msg);
-        assert!(
-            handle_result.is_ok(),
-            "handle() failed: {}",
-            handle_result.err().unwrap()
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let query_result = query(&deps, QueryMsg::TokenMetadata {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::TokenMetadata {
+                name,
+                symbol,
+                metadata: stored,
+                ..
+            } => {
+                assert_eq!(name, "new-name");
+                assert_eq!(symbol, "SECSEC");
+                assert_eq!(stored, metadata);
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetTokenMetadata {
+                name: None,
+                metadata: TokenMetadata {
+                    logo: Some(Logo::Embedded(Binary::from(vec![0u8; 5 * 1024 + 1]))),
+                    ..TokenMetadata::default()
+                },
+                padding: None,
+            },
         );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("The embedded logo must not exceed 5120 bytes"));
 
-        let admin = ReadonlyConfig::from_storage(&deps.storage)
-            .constants()
-            .unwrap()
-            .admin;
-        assert_eq!(admin, HumanAddr("bob".to_string()));
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetTokenMetadata {
+                name: Some("x".to_string()),
+                metadata: TokenMetadata::default(),
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Name is not in the expected format"));
     }
 
     #[test]
@@ -1243,7 +4485,7 @@ mod tests {
         let handle_result = handle(&mut deps_no_reserve, mock_env("butler", &[]), handle_msg);
         let error = extract_error_msg(handle_result);
         assert!(error.contains(
-            "You are trying to redeem for more SCRT than the token has in its deposit reserve."
+            "You are trying to redeem for more ughm than the token has in its deposit reserve."
         ));
 
         let handle_msg = HandleMsg::Redeem {
@@ -1259,56 +4501,235 @@ mod tests {
         );
 
         let balances = ReadonlyBalances::from_storage(&deps.storage);
-        let canonical = deps
-            .api
-            .canonical_address(&HumanAddr("butler".to_string()))
-            .unwrap();
-        assert_eq!(balances.account_amount(&canonical), 4000)
-    }
+        let canonical = deps
+            .api
+            .canonical_address(&HumanAddr("butler".to_string()))
+            .unwrap();
+        assert_eq!(balances.account_amount(&canonical), 4000)
+    }
+
+    #[test]
+    fn test_handle_deposit() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            true,
+            false,
+            false,
+            false,
+            0,
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let (init_result_for_failure, mut deps_for_failure) = init_helper(vec![InitialBalance {
+            address: HumanAddr("lebron".to_string()),
+            amount: Uint128(5000),
+        }]);
+        assert!(
+            init_result_for_failure.is_ok(),
+            "Init failed: {}",
+            init_result_for_failure.err().unwrap()
+        );
+        // test when deposit disabled
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps_for_failure,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "ughm".to_string(),
+                    amount: Uint128(1000),
+                }],
+            ),
+            handle_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Deposit functionality is not enabled for this token."));
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "ughm".to_string(),
+                    amount: Uint128(1000),
+                }],
+            ),
+            handle_msg,
+        );
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        let canonical = deps
+            .api
+            .canonical_address(&HumanAddr("lebron".to_string()))
+            .unwrap();
+        assert_eq!(balances.account_amount(&canonical), 6000)
+    }
+
+    #[test]
+    fn test_handle_deposit_and_redeem_with_exchange_ratio() {
+        // 18 decimal token backed by a 6 decimal native denom
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "uatom".to_string(),
+                amount: Uint128(5000),
+            }],
+        );
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "enable_deposit": true,
+                 "enable_redeem": true,
+                 "native_denom": "uatom",
+                 "exchange_ratio": { "native": "1", "token": "1000000000000" } }"#
+                .as_bytes(),
+        ))
+            .unwrap();
+        let init_msg = InitMsg {
+            name: "wrapped-atom".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "WATOM".to_string(),
+            decimals: 18,
+            initial_balances: None,
+            initial_vesting: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // the old hardcoded denom is no longer accepted
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "ughm".to_string(),
+                    amount: Uint128(1000),
+                }],
+            ),
+            handle_msg,
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tried to deposit an unsupported token"));
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "uatom".to_string(),
+                    amount: Uint128(1000),
+                }],
+            ),
+            handle_msg,
+        );
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
+        let canonical = deps
+            .api
+            .canonical_address(&HumanAddr("lebron".to_string()))
+            .unwrap();
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&canonical), 1000 * 10u128.pow(12));
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).total_supply(),
+            1000 * 10u128.pow(12)
+        );
+
+        // redeeming a fraction of a native unit is rejected
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(10u128.pow(12) + 1),
+            denom: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("does not convert to a whole number of uatom"));
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(400 * 10u128.pow(12)),
+            denom: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let result = handle_result.unwrap();
+        assert!(ensure_success(result.clone()));
+        assert_eq!(
+            result.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                to_address: HumanAddr("lebron".to_string()),
+                amount: vec![Coin {
+                    denom: "uatom".to_string(),
+                    amount: Uint128(400),
+                }],
+            })]
+        );
+
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&canonical), 600 * 10u128.pow(12));
 
-    #[test]
-    fn test_handle_deposit() {
-        let (init_result, mut deps) = init_helper_with_config(
-            vec![InitialBalance {
-                address: HumanAddr("lebron".to_string()),
-                amount: Uint128(5000),
-            }],
-            true,
-            false,
-            false,
-            false,
-            0,
-        );
+        // 2 decimal token where every token unit is worth 10000 native units
+        let mut deps = mock_dependencies(20, &[]);
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "enable_deposit": true,
+                 "exchange_ratio": { "native": "10000", "token": "1" } }"#
+                .as_bytes(),
+        ))
+            .unwrap();
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 2,
+            initial_balances: None,
+            initial_vesting: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
         assert!(
             init_result.is_ok(),
             "Init failed: {}",
             init_result.err().unwrap()
         );
 
-        let (init_result_for_failure, mut deps_for_failure) = init_helper(vec![InitialBalance {
-            address: HumanAddr("lebron".to_string()),
-            amount: Uint128(5000),
-        }]);
-        assert!(
-            init_result_for_failure.is_ok(),
-            "Init failed: {}",
-            init_result_for_failure.err().unwrap()
-        );
-        // test when deposit disabled
         let handle_msg = HandleMsg::Deposit { padding: None };
         let handle_result = handle(
-            &mut deps_for_failure,
+            &mut deps,
             mock_env(
                 "lebron",
                 &[Coin {
                     denom: "ughm".to_string(),
-                    amount: Uint128(1000),
+                    amount: Uint128(15000),
                 }],
             ),
             handle_msg,
         );
         let error = extract_error_msg(handle_result);
-        assert!(error.contains("Deposit functionality is not enabled for this token."));
+        assert!(error.contains("does not convert to a whole number of tokens"));
 
         let handle_msg = HandleMsg::Deposit { padding: None };
         let handle_result = handle(
@@ -1317,7 +4738,7 @@ mod tests {
                 "lebron",
                 &[Coin {
                     denom: "ughm".to_string(),
-                    amount: Uint128(1000),
+                    amount: Uint128(30000),
                 }],
             ),
             handle_msg,
@@ -1327,13 +4748,198 @@ mod tests {
             "handle() failed: {}",
             handle_result.err().unwrap()
         );
-
         let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&canonical), 3);
+    }
+
+    #[test]
+    fn test_handle_deposit_and_redeem_basket() {
+        let mut deps = mock_dependencies(
+            20,
+            &[
+                Coin {
+                    denom: "ughm".to_string(),
+                    amount: Uint128(1000),
+                },
+                Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128(10),
+                },
+            ],
+        );
+        let init_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "enable_deposit": true,
+                 "enable_redeem": true,
+                 "native_denoms": [
+                   { "denom": "ughm", "weight": { "native": "1", "token": "1" } },
+                   { "denom": "uusd", "weight": { "native": "1", "token": "3" } }
+                 ] }"#
+                .as_bytes(),
+        ))
+            .unwrap();
+        let init_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 6,
+            initial_balances: None,
+            initial_vesting: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(init_config),
+        };
+        let init_result = init(&mut deps, mock_env("instantiator", &[]), init_msg);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        // The basket can't be mixed with the single denom settings
+        let conflicting_config: InitConfig = from_binary(&Binary::from(
+            r#"{ "native_denom": "ughm",
+                 "native_denoms": [
+                   { "denom": "ughm", "weight": { "native": "1", "token": "1" } }
+                 ] }"#
+                .as_bytes(),
+        ))
+            .unwrap();
+        let conflicting_msg = InitMsg {
+            name: "sec-sec".to_string(),
+            admin: Some(HumanAddr("admin".to_string())),
+            symbol: "SECSEC".to_string(),
+            decimals: 6,
+            initial_balances: None,
+            initial_vesting: None,
+            prng_seed: Binary::from("lolz fun yay".as_bytes()),
+            config: Some(conflicting_config),
+        };
+        let init_result = init(
+            &mut mock_dependencies(20, &[]),
+            mock_env("instantiator", &[]),
+            conflicting_msg,
+        );
+        let error = extract_error_msg(init_result);
+        assert!(
+            error.contains("native_denoms can't be combined with native_denom or exchange_ratio")
+        );
+
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[
+                    Coin {
+                        denom: "ughm".to_string(),
+                        amount: Uint128(100),
+                    },
+                    Coin {
+                        denom: "uusd".to_string(),
+                        amount: Uint128(10),
+                    },
+                    Coin {
+                        denom: "ughm".to_string(),
+                        amount: Uint128(50),
+                    },
+                ],
+            ),
+            handle_msg,
+        );
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+
         let canonical = deps
             .api
             .canonical_address(&HumanAddr("lebron".to_string()))
             .unwrap();
-        assert_eq!(balances.account_amount(&canonical), 6000)
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&canonical), 100 + 30 + 50);
+
+        // unsupported denoms are rejected, even when nothing of them is sent
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[
+                    Coin {
+                        denom: "ughm".to_string(),
+                        amount: Uint128(100),
+                    },
+                    Coin {
+                        denom: "uatom".to_string(),
+                        amount: Uint128(0),
+                    },
+                ],
+            ),
+            HandleMsg::Deposit { padding: None },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tried to deposit an unsupported token"));
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(3),
+            denom: Some("uatom".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Tried to redeem for an unsupported token: uatom"));
+
+        // reserves are checked per denom
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(60),
+            denom: Some("uusd".to_string()),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), handle_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains(
+            "You are trying to redeem for more uusd than the token has in its deposit reserve."
+        ));
+
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(30),
+            denom: Some("uusd".to_string()),
+            padding: None,
+        };
+        let result = handle(&mut deps, mock_env("lebron", &[]), handle_msg).unwrap();
+        assert_eq!(
+            result.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                to_address: HumanAddr("lebron".to_string()),
+                amount: vec![Coin {
+                    denom: "uusd".to_string(),
+                    amount: Uint128(10),
+                }],
+            })]
+        );
+
+        // without a denom the first configured denom is paid out
+        let handle_msg = HandleMsg::Redeem {
+            amount: Uint128(150),
+            denom: None,
+            padding: None,
+        };
+        let result = handle(&mut deps, mock_env("lebron", &[]), handle_msg).unwrap();
+        assert_eq!(
+            result.messages,
+            vec![CosmosMsg::Bank(BankMsg::Send {
+                from_address: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+                to_address: HumanAddr("lebron".to_string()),
+                amount: vec![Coin {
+                    denom: "ughm".to_string(),
+                    amount: Uint128(150),
+                }],
+            })]
+        );
+
+        let balances = ReadonlyBalances::from_storage(&deps.storage);
+        assert_eq!(balances.account_amount(&canonical), 0);
+        assert_eq!(ReadonlyConfig::from_storage(&deps.storage).total_supply(), 0);
     }
 
     #[test]
@@ -1450,6 +5056,239 @@ mod tests {
         assert_eq!(new_supply, supply + mint_amount);
     }
 
+    #[test]
+    fn test_handle_minter_caps() {
+        let (init_result, mut deps) = init_helper_with_config(
+            vec![InitialBalance {
+                address: HumanAddr("lebron".to_string()),
+                amount: Uint128(5000),
+            }],
+            false,
+            false,
+            true,
+            false,
+            0,
+        );
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let mint = |amount: u128| HandleMsg::Mint {
+            recipient: HumanAddr("lebron".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+        let set_cap_msg = HandleMsg::SetMinterCap {
+            minter: HumanAddr("admin".to_string()),
+            lifetime_cap: Some(Uint128(500)),
+            window_cap: Some(Uint128(200)),
+            window_seconds: Some(100),
+            padding: None,
+        };
+
+        // Only the admin or a minter manager may set caps
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), set_cap_msg.clone());
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), set_cap_msg);
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), mint(150));
+        assert!(handle_result.is_ok(), "Mint failed: {}", handle_result.err().unwrap());
+
+        // The window allowance is used up
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), mint(100));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("allowance for the current window: remaining=50, required=100"));
+
+        let batch_msg = HandleMsg::BatchMint {
+            actions: vec![
+                batch::MintAction {
+                    recipient: HumanAddr("lebron".to_string()),
+                    amount: Uint128(30),
+                    memo: None,
+                },
+                batch::MintAction {
+                    recipient: HumanAddr("lebron".to_string()),
+                    amount: Uint128(30),
+                    memo: None,
+                },
+            ],
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), batch_msg);
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("remaining=50, required=60"));
+
+        let query_msg = QueryMsg::MinterCap {
+            minter: HumanAddr("admin".to_string()),
+        };
+        let query_result = query(&deps, query_msg.clone());
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::MinterCap {
+                lifetime_minted,
+                lifetime_remaining,
+                window_remaining,
+                window_resets_at,
+                ..
+            } => {
+                assert_eq!(lifetime_minted, Uint128(150));
+                assert_eq!(lifetime_remaining, Some(Uint128(350)));
+                assert_eq!(window_remaining, Some(Uint128(50)));
+                assert_eq!(window_resets_at, Some(mock_env("admin", &[]).block.time + 100));
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        // A new window refills the allowance, but the lifetime cap still applies
+        let mut env = mock_env("admin", &[]);
+        env.block.time += 100;
+        let handle_result = handle(&mut deps, env.clone(), mint(200));
+        assert!(handle_result.is_ok(), "Mint failed: {}", handle_result.err().unwrap());
+        env.block.time += 100;
+        let handle_result = handle(&mut deps, env.clone(), mint(200));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("lifetime cap: remaining=150, required=200"));
+
+        // Removing the caps keeps the minted totals
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetMinterCap {
+                minter: HumanAddr("admin".to_string()),
+                lifetime_cap: None,
+                window_cap: None,
+                window_seconds: None,
+                padding: None,
+            },
+        );
+        assert!(ensure_success(handle_result.unwrap()));
+        let handle_result = handle(&mut deps, env, mint(1000));
+        assert!(handle_result.is_ok(), "Mint failed: {}", handle_result.err().unwrap());
+
+        let query_result = query(&deps, query_msg);
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::MinterCap {
+                lifetime_cap,
+                lifetime_minted,
+                window_cap,
+                ..
+            } => {
+                assert_eq!(lifetime_cap, None);
+                assert_eq!(lifetime_minted, Uint128(1350));
+                assert_eq!(window_cap, None);
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let handle_result = handle(
+            &mut deps,
+            mock_env("admin", &[]),
+            HandleMsg::SetMinterCap {
+                minter: HumanAddr("admin".to_string()),
+                lifetime_cap: None,
+                window_cap: Some(Uint128(10)),
+                window_seconds: None,
+                padding: None,
+            },
+        );
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("A window cap requires a window length"));
+    }
+
+    #[test]
+    fn test_handle_max_supply() {
+        let init_with_balance = |amount: u128| {
+            let mut deps = mock_dependencies(20, &[]);
+            let init_config: InitConfig = from_binary(&Binary::from(
+                r#"{ "public_total_supply": true, "enable_mint": true, "max_supply": "6000" }"#
+                    .as_bytes(),
+            ))
+            .unwrap();
+            let init_msg = InitMsg {
+                name: "sec-sec".to_string(),
+                admin: Some(HumanAddr("admin".to_string())),
+                symbol: "SECSEC".to_string(),
+                decimals: 8,
+                initial_balances: Some(vec![InitialBalance {
+                    address: HumanAddr("lebron".to_string()),
+                    amount: Uint128(amount),
+                }]),
+                initial_vesting: None,
+                prng_seed: Binary::from("lolz fun yay".as_bytes()),
+                config: Some(init_config),
+            };
+            (init(&mut deps, mock_env("instantiator", &[]), init_msg), deps)
+        };
+
+        let (init_result, _) = init_with_balance(7000);
+        let error = extract_error_msg(init_result);
+        assert!(error.contains("above the max supply of 6000"));
+
+        let (init_result, mut deps) = init_with_balance(5000);
+        assert!(
+            init_result.is_ok(),
+            "Init failed: {}",
+            init_result.err().unwrap()
+        );
+
+        let mint = |amount: u128| HandleMsg::Mint {
+            recipient: HumanAddr("lebron".to_string()),
+            amount: Uint128(amount),
+            memo: None,
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), mint(1500));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("This would increase the total supply to 6500 above the max supply of 6000"));
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), mint(800));
+        assert!(handle_result.is_ok(), "Mint failed: {}", handle_result.err().unwrap());
+
+        let query_result = query(&deps, QueryMsg::MaxSupply {});
+        match from_binary(&query_result.unwrap()).unwrap() {
+            QueryAnswer::MaxSupply {
+                max_supply,
+                can_increase,
+                remaining,
+            } => {
+                assert_eq!(max_supply, Some(Uint128(6000)));
+                assert!(!can_increase);
+                assert_eq!(remaining, Some(Uint128(200)));
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+
+        let set_max_supply = |max_supply: Option<u128>| HandleMsg::SetMaxSupply {
+            max_supply: max_supply.map(Uint128),
+            padding: None,
+        };
+        let handle_result = handle(&mut deps, mock_env("lebron", &[]), set_max_supply(Some(5900)));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("Admin commands can only be run from admin address"));
+
+        for raised in [Some(7000), None] {
+            let handle_result = handle(&mut deps, mock_env("admin", &[]), set_max_supply(raised));
+            let error = extract_error_msg(handle_result);
+            assert!(error.contains("can only be lowered"));
+        }
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), set_max_supply(Some(5700)));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("cannot be lower than the current total supply"));
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), set_max_supply(Some(5800)));
+        assert!(ensure_success(handle_result.unwrap()));
+
+        let handle_result = handle(&mut deps, mock_env("admin", &[]), mint(1));
+        let error = extract_error_msg(handle_result);
+        assert!(error.contains("above the max supply of 5800"));
+    }
+
     #[test]
     fn test_handle_admin_commands() {
         let admin_err = "Admin commands can only be run from admin address".to_string();
@@ -1941,6 +5780,7 @@ mod tests {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
             }]),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: Some(init_config),
         };
@@ -2007,6 +5847,7 @@ mod tests {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
             }]),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: Some(init_config),
         };
@@ -2032,12 +5873,14 @@ mod tests {
                 redeem_enabled,
                 mint_enabled,
                 burn_enabled,
+                locked_features,
             } => {
                 assert_eq!(public_total_supply, true);
                 assert_eq!(deposit_enabled, false);
                 assert_eq!(redeem_enabled, false);
                 assert_eq!(mint_enabled, true);
                 assert_eq!(burn_enabled, false);
+                assert!(locked_features.is_empty());
             }
             _ => panic!("unexpected"),
         }
@@ -2045,7 +5888,7 @@ mod tests {
 
     #[test]
     fn test_query_exchange_rate() {
-        // test more dec than SCRT
+        // test more dec than the native denom
         let init_name = "sec-sec".to_string();
         let init_admin = HumanAddr("admin".to_string());
         let init_symbol = "SECSEC".to_string();
@@ -2061,7 +5904,8 @@ mod tests {
             \"enable_deposit\":{},
             \"enable_redeem\":{},
             \"enable_mint\":{},
-            \"enable_burn\":{}}}",
+            \"enable_burn\":{},
+            \"exchange_ratio\":{{\"native\":\"1\",\"token\":\"100\"}}}}",
                 true, true, false, false, false
             )
                 .as_bytes(),
@@ -2076,6 +5920,7 @@ mod tests {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
             }]),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: Some(init_config),
         };
@@ -2095,14 +5940,28 @@ mod tests {
         );
         let query_answer: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
         match query_answer {
-            QueryAnswer::ExchangeRate { rate, denom } => {
+            QueryAnswer::ExchangeRate {
+                rate,
+                denom,
+                native_denoms,
+            } => {
                 assert_eq!(rate, Uint128(100));
-                assert_eq!(denom, "SCRT");
+                assert_eq!(denom, "ughm");
+                assert_eq!(
+                    native_denoms,
+                    vec![NativeDenom {
+                        denom: "ughm".to_string(),
+                        weight: ExchangeRatio {
+                            native: Uint128(1),
+                            token: Uint128(100)
+                        }
+                    }]
+                );
             }
             _ => panic!("unexpected"),
         }
 
-        // test same number of decimals as SCRT
+        // test same number of decimals as the native denom
         let init_name = "sec-sec".to_string();
         let init_admin = HumanAddr("admin".to_string());
         let init_symbol = "SECSEC".to_string();
@@ -2133,6 +5992,7 @@ mod tests {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
             }]),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: Some(init_config),
         };
@@ -2152,14 +6012,14 @@ mod tests {
         );
         let query_answer: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
         match query_answer {
-            QueryAnswer::ExchangeRate { rate, denom } => {
+            QueryAnswer::ExchangeRate { rate, denom, .. } => {
                 assert_eq!(rate, Uint128(1));
-                assert_eq!(denom, "SCRT");
+                assert_eq!(denom, "ughm");
             }
             _ => panic!("unexpected"),
         }
 
-        // test less decimal places than SCRT
+        // test less decimal places than the native denom
         let init_name = "sec-sec".to_string();
         let init_admin = HumanAddr("admin".to_string());
         let init_symbol = "SECSEC".to_string();
@@ -2175,7 +6035,8 @@ mod tests {
             \"enable_deposit\":{},
             \"enable_redeem\":{},
             \"enable_mint\":{},
-            \"enable_burn\":{}}}",
+            \"enable_burn\":{},
+            \"exchange_ratio\":{{\"native\":\"1000\",\"token\":\"1\"}}}}",
                 true, true, false, false, false
             )
                 .as_bytes(),
@@ -2190,6 +6051,7 @@ mod tests {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
             }]),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: Some(init_config),
         };
@@ -2209,7 +6071,7 @@ mod tests {
         );
         let query_answer: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
         match query_answer {
-            QueryAnswer::ExchangeRate { rate, denom } => {
+            QueryAnswer::ExchangeRate { rate, denom, .. } => {
                 assert_eq!(rate, Uint128(1000));
                 assert_eq!(denom, "SECSEC");
             }
@@ -2235,6 +6097,7 @@ mod tests {
                 address: HumanAddr("giannis".to_string()),
                 amount: init_supply,
             }]),
+            initial_vesting: None,
             prng_seed: Binary::from("lolz fun yay".as_bytes()),
             config: None,
         };
@@ -2254,7 +6117,7 @@ mod tests {
         );
         let query_answer: QueryAnswer = from_binary(&query_result.unwrap()).unwrap();
         match query_answer {
-            QueryAnswer::ExchangeRate { rate, denom } => {
+            QueryAnswer::ExchangeRate { rate, denom, .. } => {
                 assert_eq!(rate, Uint128(0));
                 assert_eq!(denom, String::new());
             }
@@ -2279,6 +6142,7 @@ mod tests {
             amount: Uint128(2000),
             padding: None,
             expiration: None,
+            period_limit: None,
         };
         let handle_result = handle(&mut deps, mock_env("giannis", &[]), handle_msg);
         assert!(
@@ -0,0 +1,327 @@
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::*;
+    use cosmwasm_std::{from_binary, Api, Coin, HumanAddr, Storage, Uint128};
+    use cosmwasm_storage::PrefixedStorage;
+    use crate::contract::{handle, migrate, query};
+    use crate::migrate::{ConstantsV1, VestingScheduleV1, CURRENT_SCHEMA_VERSION};
+    use crate::msg::{ContractStatusLevel, ExchangeRatio, HandleMsg, MigrateMsg, NativeDenom, QueryAnswer, QueryMsg, VestingTerms};
+    use crate::state::{read_allowance, read_mandate, read_vesting_schedules, write_vesting_schedules, Config, ReadonlyConfig, KEY_CONSTANTS, PREFIX_ALLOWANCES, PREFIX_CONFIG, PREFIX_MANDATES, PREFIX_VESTING};
+    use crate::transaction_history::store_mint;
+
+    // Helper functions
+
+    /// Writes the state a version 1 deployment would have left behind, byte for byte
+    fn legacy_storage_helper(storage: &mut MockStorage) {
+        let constants = ConstantsV1 {
+            name: "sec-sec".to_string(),
+            admin: HumanAddr("admin".to_string()),
+            symbol: "SECSEC".to_string(),
+            decimals: 8,
+            prng_seed: vec![1, 2, 3],
+            total_supply_is_public: true,
+            deposit_is_enabled: true,
+            redeem_is_enabled: true,
+            mint_is_enabled: true,
+            burn_is_enabled: false,
+            contract_address: HumanAddr(MOCK_CONTRACT_ADDR.to_string()),
+        };
+        {
+            let mut config_store = PrefixedStorage::new(PREFIX_CONFIG, &mut *storage);
+            config_store.set(KEY_CONSTANTS, &bincode2::serialize(&constants).unwrap());
+        }
+
+        let mut config = Config::from_storage(storage);
+        config.set_total_supply(5000);
+        config.set_contract_status(ContractStatusLevel::NormalRun);
+        config
+            .set_minters(vec![HumanAddr("admin".to_string())])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_legacy_storage_has_version_1() {
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+
+        let config = ReadonlyConfig::from_storage(&deps.storage);
+        assert_eq!(config.schema_version(), 1);
+    }
+
+    #[test]
+    fn test_migrate_constants_from_v1() {
+        let mut deps = mock_dependencies(
+            20,
+            &[Coin {
+                denom: "ughm".to_string(),
+                amount: Uint128(1000),
+            }],
+        );
+        legacy_storage_helper(&mut deps.storage);
+
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), MigrateMsg::default());
+        assert!(
+            migrate_result.is_ok(),
+            "Migrate failed: {}",
+            migrate_result.err().unwrap()
+        );
+
+        let config = ReadonlyConfig::from_storage(&deps.storage);
+        assert_eq!(config.schema_version(), CURRENT_SCHEMA_VERSION);
+        assert_eq!(config.total_supply(), 5000);
+        let constants = config.constants().unwrap();
+        assert_eq!(constants.name, "sec-sec".to_string());
+        assert_eq!(constants.admin, Some(HumanAddr("admin".to_string())));
+        assert_eq!(constants.symbol, "SECSEC".to_string());
+        assert_eq!(constants.decimals, 8);
+        assert_eq!(constants.prng_seed, vec![1, 2, 3]);
+        assert_eq!(constants.total_supply_is_public, true);
+        assert_eq!(constants.deposit_is_enabled, true);
+        assert_eq!(constants.burn_is_enabled, false);
+        assert_eq!(
+            constants.native_denoms,
+            vec![NativeDenom {
+                denom: "ughm".to_string(),
+                weight: ExchangeRatio::default(),
+            }]
+        );
+
+        // Deposits keep working the way they did before the migration
+        let handle_msg = HandleMsg::Deposit { padding: None };
+        let handle_result = handle(
+            &mut deps,
+            mock_env(
+                "lebron",
+                &[Coin {
+                    denom: "ughm".to_string(),
+                    amount: Uint128(1000),
+                }],
+            ),
+            handle_msg,
+        );
+        assert!(
+            handle_result.is_ok(),
+            "handle() failed: {}",
+            handle_result.err().unwrap()
+        );
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).total_supply(),
+            6000
+        );
+
+        // Migrating again is a no-op
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), MigrateMsg::default());
+        assert!(migrate_result.is_ok());
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage)
+                .constants()
+                .unwrap()
+                .native_denoms
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_migrate_with_custom_native_denoms() {
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+
+        let migrate_msg = MigrateMsg {
+            native_denoms: Some(vec![NativeDenom {
+                denom: "ughm".to_string(),
+                weight: ExchangeRatio {
+                    native: Uint128(1),
+                    token: Uint128(100),
+                },
+            }]),
+        };
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), migrate_msg);
+        assert!(migrate_result.is_ok());
+
+        let query_result = query(&deps, QueryMsg::ExchangeRate {}).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::ExchangeRate { rate, denom, .. } => {
+                assert_eq!(rate, Uint128(100));
+                assert_eq!(denom, "ughm");
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+        let migrate_msg = MigrateMsg {
+            native_denoms: Some(vec![]),
+        };
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), migrate_msg);
+        assert!(migrate_result.is_err());
+        assert_eq!(
+            ReadonlyConfig::from_storage(&deps.storage).schema_version(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_schema() {
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+        Config::from_storage(&mut deps.storage)
+            .set_schema_version(CURRENT_SCHEMA_VERSION + 1)
+            .unwrap();
+
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), MigrateMsg::default());
+        assert!(migrate_result.is_err());
+    }
+
+    #[test]
+    fn test_migrate_keeps_allowances_and_history() {
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+
+        let owner = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let spender = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+
+        // A version 1 allowance: `amount: u128` followed by `expiration: Option<u64>`
+        let legacy_allowance = bincode2::serialize(&(2000u128, Some(1_700_000_000u64))).unwrap();
+        {
+            let mut allowance_store = PrefixedStorage::multilevel(
+                &[PREFIX_ALLOWANCES, owner.as_slice()],
+                &mut deps.storage,
+            );
+            allowance_store.set(spender.as_slice(), &legacy_allowance);
+        }
+
+        store_mint(
+            &mut deps.storage,
+            &owner,
+            &owner,
+            Uint128(5000),
+            "SECSEC".to_string(),
+            Some("Initial Balance".to_string()),
+            &mock_env("admin", &[]).block,
+        )
+        .unwrap();
+
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), MigrateMsg::default());
+        assert!(migrate_result.is_ok());
+
+        let allowance = read_allowance(&deps.storage, &owner, &spender).unwrap();
+        assert_eq!(allowance.amount, 2000);
+        assert_eq!(allowance.expiration, Some(1_700_000_000));
+        assert_eq!(allowance.period_limit, None);
+
+        let handle_msg = HandleMsg::SetViewingKey {
+            key: "key".to_string(),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("bob", &[]), handle_msg).unwrap();
+        let query_msg = QueryMsg::TransactionHistory {
+            address: HumanAddr("bob".to_string()),
+            key: "key".to_string(),
+            page: None,
+            page_size: 10,
+        };
+        let query_result = query(&deps, query_msg).unwrap();
+        match from_binary(&query_result).unwrap() {
+            QueryAnswer::TransactionHistory { txs, total } => {
+                assert_eq!(total, Some(1));
+                assert_eq!(txs[0].coins.amount, Uint128(5000));
+            }
+            other => panic!("Unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_migrate_keeps_mandates() {
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+
+        let payer = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let payee = deps
+            .api
+            .canonical_address(&HumanAddr("alice".to_string()))
+            .unwrap();
+
+        // A version 3 mandate: the version 3 allowance, then period, max_count, executed, next_due
+        let legacy_mandate = bincode2::serialize(&(
+            (100u128, Some(1_700_000_000u64)),
+            3600u64,
+            12u32,
+            2u32,
+            1_600_007_200u64,
+        ))
+        .unwrap();
+        {
+            let mut mandate_store = PrefixedStorage::multilevel(
+                &[PREFIX_MANDATES, payer.as_slice()],
+                &mut deps.storage,
+            );
+            mandate_store.set(payee.as_slice(), &legacy_mandate);
+        }
+
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), MigrateMsg::default());
+        assert!(migrate_result.is_ok());
+
+        let mandate = read_mandate(&deps.storage, &payer, &payee).unwrap().unwrap();
+        assert_eq!(mandate.amount, 100);
+        assert_eq!(mandate.expiration, Some(1_700_000_000));
+        assert_eq!(mandate.period, 3600);
+        assert_eq!(mandate.max_count, 12);
+        assert_eq!(mandate.executed, 2);
+        assert_eq!(mandate.next_due, 1_600_007_200);
+    }
+
+    #[test]
+    fn test_migrate_keeps_vesting_schedules() {
+        let mut deps = mock_dependencies(20, &[]);
+        legacy_storage_helper(&mut deps.storage);
+
+        let bob = deps
+            .api
+            .canonical_address(&HumanAddr("bob".to_string()))
+            .unwrap();
+        let legacy_schedule = VestingScheduleV1 {
+            amount: 1000,
+            claimed: 250,
+            terms: VestingTerms {
+                start_time: 1000,
+                cliff_time: 1250,
+                end_time: 2000,
+                revocable: true,
+            },
+            revoked_at: None,
+        };
+        {
+            let mut vesting_store = PrefixedStorage::new(PREFIX_VESTING, &mut deps.storage);
+            vesting_store.set(
+                bob.as_slice(),
+                &bincode2::serialize(&vec![legacy_schedule.clone()]).unwrap(),
+            );
+        }
+
+        let migrate_result = migrate(&mut deps, mock_env("admin", &[]), MigrateMsg::default());
+        assert!(migrate_result.is_ok());
+
+        let schedules = read_vesting_schedules(&deps.storage, &bob).unwrap();
+        assert_eq!(schedules.len(), 1);
+        assert_eq!(schedules[0].amount, legacy_schedule.amount);
+        assert_eq!(schedules[0].claimed, legacy_schedule.claimed);
+        assert_eq!(schedules[0].terms, legacy_schedule.terms);
+        assert_eq!(schedules[0].funder, None);
+
+        // Once rewritten, only the current layout is left
+        write_vesting_schedules(&mut deps.storage, &bob, &schedules).unwrap();
+        let vesting_store = PrefixedStorage::new(PREFIX_VESTING, &mut deps.storage);
+        assert_eq!(vesting_store.get(bob.as_slice()), None);
+        assert_eq!(read_vesting_schedules(&deps.storage, &bob).unwrap(), schedules);
+    }
+}
@@ -12,6 +12,7 @@ use crate::state::Config;
 
 const PREFIX_TXS: &[u8] = b"transactions";
 const PREFIX_TRANSFERS: &[u8] = b"transfers";
+const PREFIX_FEE_TRANSFERS: &[u8] = b"fee-transfers";
 
 #[derive(Serialize,Deserialize, JsonSchema, Clone,Debug)]
 pub struct Tx {
@@ -44,6 +45,94 @@ pub enum TxAction {
     },
     Deposit{},
     Redeem {},
+    /// A transfer fee paid by `payer`. The fee was burned when there is no `collector`.
+    /// `transfer_id` is the id of the transfer that paid it, and is only missing for fees
+    /// recorded before it was tracked.
+    Fee {
+        payer: HumanAddr,
+        collector: Option<HumanAddr>,
+        transfer_id: Option<u64>,
+    },
+    Freeze {
+        account: HumanAddr,
+        by: HumanAddr,
+    },
+    Unfreeze {
+        account: HumanAddr,
+        by: HumanAddr,
+    },
+    /// Funds moved out of `from` by the admin, without an allowance
+    ForcedTransfer {
+        from: HumanAddr,
+        by: HumanAddr,
+        recipient: HumanAddr,
+    },
+    /// Funds moved out of `sender`'s balance into an escrow for `recipient`
+    EscrowCreate {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        arbiter: Option<HumanAddr>,
+    },
+    /// Escrowed funds paid out to `recipient`, on behalf of `by`
+    EscrowRelease {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        by: HumanAddr,
+    },
+    /// Escrowed funds returned to `sender`, on behalf of `by`
+    EscrowRefund {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        by: HumanAddr,
+    },
+    /// A stream's deposit moved out of `sender`'s balance
+    StreamCreate {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+    },
+    /// Streamed funds paid out to `recipient`
+    StreamWithdraw {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+    },
+    /// The unstreamed rest of a canceled stream, returned to `sender`
+    StreamCancel {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        by: HumanAddr,
+    },
+    /// Tokens locked in a vesting schedule of `beneficiary`. `by` is the admin whose balance funded
+    /// it, or who minted it at init.
+    VestingCreate {
+        beneficiary: HumanAddr,
+        by: HumanAddr,
+    },
+    /// Vested tokens moved into `beneficiary`'s balance
+    VestingClaim {
+        beneficiary: HumanAddr,
+    },
+    /// The unvested rest of a revoked schedule, returned to `by`
+    VestingRevoke {
+        beneficiary: HumanAddr,
+        by: HumanAddr,
+    },
+    /// Funds moved out of `sender`'s balance into a hashed timelock for `recipient`
+    HtlcLock {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+    },
+    /// Locked funds paid out to `recipient` by `by`, who revealed the preimage
+    HtlcRedeem {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        by: HumanAddr,
+    },
+    /// Locked funds of an expired HTLC returned to `sender`, triggered by `by`
+    HtlcRefund {
+        sender: HumanAddr,
+        recipient: HumanAddr,
+        by: HumanAddr,
+    },
 }
 
 // Note that id is a globally incrementing counter.
@@ -102,6 +191,22 @@ enum TxCode {
     Burn = 2,
     Deposit = 3,
     Redeem = 4,
+    Fee = 5,
+    Freeze = 6,
+    Unfreeze = 7,
+    ForcedTransfer = 8,
+    EscrowCreate = 9,
+    EscrowRelease = 10,
+    EscrowRefund = 11,
+    StreamCreate = 12,
+    StreamWithdraw = 13,
+    StreamCancel = 14,
+    VestingCreate = 15,
+    VestingClaim = 16,
+    VestingRevoke = 17,
+    HtlcLock = 18,
+    HtlcRedeem = 19,
+    HtlcRefund = 20,
 }
 
 impl TxCode {
@@ -115,6 +220,22 @@ impl TxCode {
             2 => Ok(Burn),
             3 => Ok(Deposit),
             4 => Ok(Redeem),
+            5 => Ok(Fee),
+            6 => Ok(Freeze),
+            7 => Ok(Unfreeze),
+            8 => Ok(ForcedTransfer),
+            9 => Ok(EscrowCreate),
+            10 => Ok(EscrowRelease),
+            11 => Ok(EscrowRefund),
+            12 => Ok(StreamCreate),
+            13 => Ok(StreamWithdraw),
+            14 => Ok(StreamCancel),
+            15 => Ok(VestingCreate),
+            16 => Ok(VestingClaim),
+            17 => Ok(VestingRevoke),
+            18 => Ok(HtlcLock),
+            19 => Ok(HtlcRedeem),
+            20 => Ok(HtlcRefund),
             other => Err(StdError::generic_err(format!(
                 "Unexpected Tx code in transaction history: {} Storage is corrupted.",
                 other
@@ -174,6 +295,103 @@ impl StoredTxAction {
         }
     }
 
+    fn fee(payer: CanonicalAddr, collector: Option<CanonicalAddr>) -> Self {
+        Self {
+            tx_type: TxCode::Fee.to_u8(),
+            address1: Some(payer),
+            address2: collector,
+            address3: None,
+        }
+    }
+
+    fn freeze(account: CanonicalAddr, by: CanonicalAddr, frozen: bool) -> Self {
+        let tx_type = if frozen { TxCode::Freeze } else { TxCode::Unfreeze };
+        Self {
+            tx_type: tx_type.to_u8(),
+            address1: Some(account),
+            address2: Some(by),
+            address3: None,
+        }
+    }
+
+    fn forced_transfer(from: CanonicalAddr, by: CanonicalAddr, recipient: CanonicalAddr) -> Self {
+        Self {
+            tx_type: TxCode::ForcedTransfer.to_u8(),
+            address1: Some(from),
+            address2: Some(by),
+            address3: Some(recipient),
+        }
+    }
+
+    fn escrow_create(
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+        arbiter: Option<CanonicalAddr>,
+    ) -> Self {
+        Self {
+            tx_type: TxCode::EscrowCreate.to_u8(),
+            address1: Some(sender),
+            address2: Some(recipient),
+            address3: arbiter,
+        }
+    }
+
+    fn escrow_settle(
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+        by: CanonicalAddr,
+        released: bool,
+    ) -> Self {
+        let tx_type = if released {
+            TxCode::EscrowRelease
+        } else {
+            TxCode::EscrowRefund
+        };
+        Self {
+            tx_type: tx_type.to_u8(),
+            address1: Some(sender),
+            address2: Some(recipient),
+            address3: Some(by),
+        }
+    }
+
+    fn stream(
+        code: TxCode,
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+        by: Option<CanonicalAddr>,
+    ) -> Self {
+        Self {
+            tx_type: code.to_u8(),
+            address1: Some(sender),
+            address2: Some(recipient),
+            address3: by,
+        }
+    }
+
+    fn vesting(code: TxCode, beneficiary: CanonicalAddr, by: Option<CanonicalAddr>) -> Self {
+        Self {
+            tx_type: code.to_u8(),
+            address1: Some(beneficiary),
+            address2: by,
+            address3: None,
+        }
+    }
+
+    fn htlc(
+        code: TxCode,
+        sender: CanonicalAddr,
+        recipient: CanonicalAddr,
+        by: Option<CanonicalAddr>,
+    ) -> Self {
+        Self {
+            tx_type: code.to_u8(),
+            address1: Some(sender),
+            address2: Some(recipient),
+            address3: by,
+        }
+    }
+
     fn into_humanized<A: Api>(self, api: &A) -> StdResult<TxAction> {
         let transfer_addr_err = || {
             StdError::generic_err(
@@ -186,6 +404,24 @@ impl StoredTxAction {
         let burn_addr_err = || {
             StdError::generic_err("Missing address in stored Burn transaction. Storage is corrupt")
         };
+        let fee_addr_err = || {
+            StdError::generic_err("Missing address in stored Fee transaction. Storage is corrupt")
+        };
+        let freeze_addr_err = || {
+            StdError::generic_err("Missing address in stored Freeze transaction. Storage is corrupt")
+        };
+        let escrow_addr_err = || {
+            StdError::generic_err("Missing address in stored Escrow transaction. Storage is corrupt")
+        };
+        let stream_addr_err = || {
+            StdError::generic_err("Missing address in stored Stream transaction. Storage is corrupt")
+        };
+        let vesting_addr_err = || {
+            StdError::generic_err("Missing address in stored Vesting transaction. Storage is corrupt")
+        };
+        let htlc_addr_err = || {
+            StdError::generic_err("Missing address in stored HTLC transaction. Storage is corrupt")
+        };
 
         // In all of these, we ignore fields that we don't expect to find populated
         let action = match TxCode::from_u8(self.tx_type)? {
@@ -218,6 +454,148 @@ impl StoredTxAction {
             }
             TxCode::Deposit => TxAction::Deposit {},
             TxCode::Redeem => TxAction::Redeem {},
+            TxCode::Fee => {
+                let payer = self.address1.ok_or_else(fee_addr_err)?;
+                let payer = api.human_address(&payer)?;
+                let collector = match self.address2 {
+                    Some(collector) => Some(api.human_address(&collector)?),
+                    None => None,
+                };
+                // The transfer id isn't part of the stored action, `get_txs` fills it in
+                TxAction::Fee {
+                    payer,
+                    collector,
+                    transfer_id: None,
+                }
+            }
+            code @ TxCode::Freeze | code @ TxCode::Unfreeze => {
+                let account = self.address1.ok_or_else(freeze_addr_err)?;
+                let by = self.address2.ok_or_else(freeze_addr_err)?;
+                let account = api.human_address(&account)?;
+                let by = api.human_address(&by)?;
+                if let TxCode::Freeze = code {
+                    TxAction::Freeze { account, by }
+                } else {
+                    TxAction::Unfreeze { account, by }
+                }
+            }
+            TxCode::ForcedTransfer => {
+                let from = self.address1.ok_or_else(transfer_addr_err)?;
+                let by = self.address2.ok_or_else(transfer_addr_err)?;
+                let recipient = self.address3.ok_or_else(transfer_addr_err)?;
+                let from = api.human_address(&from)?;
+                let by = api.human_address(&by)?;
+                let recipient = api.human_address(&recipient)?;
+                TxAction::ForcedTransfer {
+                    from,
+                    by,
+                    recipient,
+                }
+            }
+            TxCode::EscrowCreate => {
+                let sender = self.address1.ok_or_else(escrow_addr_err)?;
+                let recipient = self.address2.ok_or_else(escrow_addr_err)?;
+                let sender = api.human_address(&sender)?;
+                let recipient = api.human_address(&recipient)?;
+                let arbiter = match self.address3 {
+                    Some(arbiter) => Some(api.human_address(&arbiter)?),
+                    None => None,
+                };
+                TxAction::EscrowCreate {
+                    sender,
+                    recipient,
+                    arbiter,
+                }
+            }
+            code @ TxCode::EscrowRelease | code @ TxCode::EscrowRefund => {
+                let sender = self.address1.ok_or_else(escrow_addr_err)?;
+                let recipient = self.address2.ok_or_else(escrow_addr_err)?;
+                let by = self.address3.ok_or_else(escrow_addr_err)?;
+                let sender = api.human_address(&sender)?;
+                let recipient = api.human_address(&recipient)?;
+                let by = api.human_address(&by)?;
+                if let TxCode::EscrowRelease = code {
+                    TxAction::EscrowRelease {
+                        sender,
+                        recipient,
+                        by,
+                    }
+                } else {
+                    TxAction::EscrowRefund {
+                        sender,
+                        recipient,
+                        by,
+                    }
+                }
+            }
+            code @ TxCode::StreamCreate | code @ TxCode::StreamWithdraw => {
+                let sender = self.address1.ok_or_else(stream_addr_err)?;
+                let recipient = self.address2.ok_or_else(stream_addr_err)?;
+                let sender = api.human_address(&sender)?;
+                let recipient = api.human_address(&recipient)?;
+                if let TxCode::StreamCreate = code {
+                    TxAction::StreamCreate { sender, recipient }
+                } else {
+                    TxAction::StreamWithdraw { sender, recipient }
+                }
+            }
+            TxCode::StreamCancel => {
+                let sender = self.address1.ok_or_else(stream_addr_err)?;
+                let recipient = self.address2.ok_or_else(stream_addr_err)?;
+                let by = self.address3.ok_or_else(stream_addr_err)?;
+                let sender = api.human_address(&sender)?;
+                let recipient = api.human_address(&recipient)?;
+                let by = api.human_address(&by)?;
+                TxAction::StreamCancel {
+                    sender,
+                    recipient,
+                    by,
+                }
+            }
+            TxCode::VestingClaim => {
+                let beneficiary = self.address1.ok_or_else(vesting_addr_err)?;
+                let beneficiary = api.human_address(&beneficiary)?;
+                TxAction::VestingClaim { beneficiary }
+            }
+            code @ TxCode::VestingCreate | code @ TxCode::VestingRevoke => {
+                let beneficiary = self.address1.ok_or_else(vesting_addr_err)?;
+                let by = self.address2.ok_or_else(vesting_addr_err)?;
+                let beneficiary = api.human_address(&beneficiary)?;
+                let by = api.human_address(&by)?;
+                if let TxCode::VestingCreate = code {
+                    TxAction::VestingCreate { beneficiary, by }
+                } else {
+                    TxAction::VestingRevoke { beneficiary, by }
+                }
+            }
+            TxCode::HtlcLock => {
+                let sender = self.address1.ok_or_else(htlc_addr_err)?;
+                let recipient = self.address2.ok_or_else(htlc_addr_err)?;
+                let sender = api.human_address(&sender)?;
+                let recipient = api.human_address(&recipient)?;
+                TxAction::HtlcLock { sender, recipient }
+            }
+            code @ TxCode::HtlcRedeem | code @ TxCode::HtlcRefund => {
+                let sender = self.address1.ok_or_else(htlc_addr_err)?;
+                let recipient = self.address2.ok_or_else(htlc_addr_err)?;
+                let by = self.address3.ok_or_else(htlc_addr_err)?;
+                let sender = api.human_address(&sender)?;
+                let recipient = api.human_address(&recipient)?;
+                let by = api.human_address(&by)?;
+                if let TxCode::HtlcRedeem = code {
+                    TxAction::HtlcRedeem {
+                        sender,
+                        recipient,
+                        by,
+                    }
+                } else {
+                    TxAction::HtlcRefund {
+                        sender,
+                        recipient,
+                        by,
+                    }
+                }
+            }
         };
 
         Ok(action)
@@ -296,7 +674,7 @@ pub fn store_transfer<S: Storage>(
     denom: String,
     memo: Option<String>,
     block: &cosmwasm_std::BlockInfo,
-) -> StdResult<()> {
+) -> StdResult<u64> {
     let id = increment_tx_count(store)?;
     let coins = Coin { denom, amount };
     let transfer = StoredLegacyTransfer {
@@ -328,7 +706,7 @@ pub fn store_transfer<S: Storage>(
     append_tx(store, &tx, receiver)?;
     append_transfer(store, &transfer, receiver)?;
 
-    Ok(())
+    Ok(id)
 }
 
 pub fn store_mint<S: Storage>(
@@ -409,6 +787,294 @@ pub fn store_redeem<S: Storage>(
     Ok(())
 }
 
+/// Stores the fee paid by the transfer with id `transfer_id`, and links the two records
+#[allow(clippy::too_many_arguments)]
+pub fn store_fee<S: Storage>(
+    store: &mut S,
+    payer: &CanonicalAddr,
+    collector: Option<&CanonicalAddr>,
+    amount: Uint128,
+    denom: String,
+    transfer_id: u64,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::fee(payer.clone(), collector.cloned());
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    // Stored tx records keep their layout, so the link lives next to them
+    let mut fee_transfers = PrefixedStorage::new(PREFIX_FEE_TRANSFERS, store);
+    fee_transfers.set(&id.to_be_bytes(), &transfer_id.to_be_bytes());
+
+    if let Some(collector) = collector {
+        if collector != payer {
+            append_tx(store, &tx, collector)?;
+        }
+    }
+    append_tx(store, &tx, payer)?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn store_forced_transfer<S: Storage>(
+    store: &mut S,
+    from: &CanonicalAddr,
+    by: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<u64> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::forced_transfer(from.clone(), by.clone(), recipient.clone());
+    let tx = StoredRichTx::new(id, action, coins, memo, block);
+
+    if by != from && by != recipient {
+        append_tx(store, &tx, by)?;
+    }
+    if from != recipient {
+        append_tx(store, &tx, from)?;
+    }
+    append_tx(store, &tx, recipient)?;
+
+    Ok(id)
+}
+
+pub fn store_freeze<S: Storage>(
+    store: &mut S,
+    account: &CanonicalAddr,
+    by: &CanonicalAddr,
+    frozen: bool,
+    denom: String,
+    memo: Option<String>,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin {
+        denom,
+        amount: Uint128(0),
+    };
+    let action = StoredTxAction::freeze(account.clone(), by.clone(), frozen);
+    let tx = StoredRichTx::new(id, action, coins, memo, block);
+
+    if by != account {
+        append_tx(store, &tx, by)?;
+    }
+    append_tx(store, &tx, account)?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn store_escrow_create<S: Storage>(
+    store: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    arbiter: Option<&CanonicalAddr>,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::escrow_create(sender.clone(), recipient.clone(), arbiter.cloned());
+    let tx = StoredRichTx::new(id, action, coins, memo, block);
+
+    append_tx_for_parties(store, &tx, &[Some(sender), Some(recipient), arbiter])
+}
+
+/// Records an escrow being released to its recipient, or refunded to its sender
+#[allow(clippy::too_many_arguments)]
+pub fn store_escrow_settle<S: Storage>(
+    store: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    by: &CanonicalAddr,
+    released: bool,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action =
+        StoredTxAction::escrow_settle(sender.clone(), recipient.clone(), by.clone(), released);
+    let tx = StoredRichTx::new(id, action, coins, memo, block);
+
+    append_tx_for_parties(store, &tx, &[Some(sender), Some(recipient), Some(by)])
+}
+
+/// Records a stream's deposit being locked, or streamed funds being withdrawn
+pub fn store_stream_payment<S: Storage>(
+    store: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    created: bool,
+    amount: Uint128,
+    denom: String,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let code = if created {
+        TxCode::StreamCreate
+    } else {
+        TxCode::StreamWithdraw
+    };
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::stream(code, sender.clone(), recipient.clone(), None);
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    append_tx_for_parties(store, &tx, &[Some(sender), Some(recipient)])
+}
+
+/// Records the refund of a canceled stream to its sender
+pub fn store_stream_cancel<S: Storage>(
+    store: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    by: &CanonicalAddr,
+    refunded: Uint128,
+    denom: String,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin {
+        denom,
+        amount: refunded,
+    };
+    let action = StoredTxAction::stream(
+        TxCode::StreamCancel,
+        sender.clone(),
+        recipient.clone(),
+        Some(by.clone()),
+    );
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    append_tx_for_parties(store, &tx, &[Some(sender), Some(recipient), Some(by)])
+}
+
+/// Records tokens being locked in a vesting schedule, by the admin or at init
+pub fn store_vesting_create<S: Storage>(
+    store: &mut S,
+    beneficiary: &CanonicalAddr,
+    by: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    memo: Option<String>,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action =
+        StoredTxAction::vesting(TxCode::VestingCreate, beneficiary.clone(), Some(by.clone()));
+    let tx = StoredRichTx::new(id, action, coins, memo, block);
+
+    append_tx_for_parties(store, &tx, &[Some(beneficiary), Some(by)])
+}
+
+/// Records vested tokens being claimed into the beneficiary's balance
+pub fn store_vesting_claim<S: Storage>(
+    store: &mut S,
+    beneficiary: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::vesting(TxCode::VestingClaim, beneficiary.clone(), None);
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    append_tx(store, &tx, beneficiary)
+}
+
+/// Records the unvested rest of a revoked schedule being returned to the admin
+pub fn store_vesting_revoke<S: Storage>(
+    store: &mut S,
+    beneficiary: &CanonicalAddr,
+    by: &CanonicalAddr,
+    funder: &CanonicalAddr,
+    returned: Uint128,
+    denom: String,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin {
+        denom,
+        amount: returned,
+    };
+    let action =
+        StoredTxAction::vesting(TxCode::VestingRevoke, beneficiary.clone(), Some(by.clone()));
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    append_tx_for_parties(store, &tx, &[Some(beneficiary), Some(by), Some(funder)])
+}
+
+/// Records funds being locked in an HTLC
+pub fn store_htlc_lock<S: Storage>(
+    store: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    amount: Uint128,
+    denom: String,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::htlc(TxCode::HtlcLock, sender.clone(), recipient.clone(), None);
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    append_tx_for_parties(store, &tx, &[Some(sender), Some(recipient)])
+}
+
+/// Records an HTLC being redeemed to its recipient, or refunded to its sender
+#[allow(clippy::too_many_arguments)]
+pub fn store_htlc_settle<S: Storage>(
+    store: &mut S,
+    sender: &CanonicalAddr,
+    recipient: &CanonicalAddr,
+    by: &CanonicalAddr,
+    redeemed: bool,
+    amount: Uint128,
+    denom: String,
+    block: &cosmwasm_std::BlockInfo,
+) -> StdResult<()> {
+    let code = if redeemed {
+        TxCode::HtlcRedeem
+    } else {
+        TxCode::HtlcRefund
+    };
+    let id = increment_tx_count(store)?;
+    let coins = Coin { denom, amount };
+    let action = StoredTxAction::htlc(code, sender.clone(), recipient.clone(), Some(by.clone()));
+    let tx = StoredRichTx::new(id, action, coins, None, block);
+
+    append_tx_for_parties(store, &tx, &[Some(sender), Some(recipient), Some(by)])
+}
+
+/// Appends `tx` once to the history of every distinct party
+fn append_tx_for_parties<S: Storage>(
+    store: &mut S,
+    tx: &StoredRichTx,
+    parties: &[Option<&CanonicalAddr>],
+) -> StdResult<()> {
+    let mut seen: Vec<&CanonicalAddr> = vec![];
+    for party in parties.iter().flatten() {
+        if !seen.contains(party) {
+            append_tx(store, tx, party)?;
+            seen.push(party);
+        }
+    }
+
+    Ok(())
+}
+
 fn append_tx<S: Storage>(
     store: &mut S,
     tx: &StoredRichTx,
@@ -459,7 +1125,41 @@ pub fn get_txs<A: Api, S: ReadonlyStorage>(
     let txs: StdResult<Vec<RichTx>> = tx_iter
         .map(|tx| tx.map(|tx| tx.into_humanized(api)).and_then(|x| x))
         .collect();
-    txs.map(|txs| (txs, store.len() as u64))
+    let mut txs = txs?;
+
+    let fee_transfers = ReadonlyPrefixedStorage::new(PREFIX_FEE_TRANSFERS, storage);
+    for tx in txs.iter_mut() {
+        if let TxAction::Fee { transfer_id, .. } = &mut tx.action {
+            *transfer_id = match fee_transfers.get(&tx.id.to_be_bytes()) {
+                Some(bytes) => {
+                    let bytes = <[u8; 8]>::try_from(bytes.as_slice()).map_err(|_| {
+                        StdError::generic_err("Corrupted data found. 8 byte expected.")
+                    })?;
+                    Some(u64::from_be_bytes(bytes))
+                }
+                None => None,
+            };
+        }
+    }
+
+    Ok((txs, store.len() as u64))
+}
+
+/// The block time of the latest tx in the history of `for_address`, if it has any
+pub fn get_latest_tx_time<S: ReadonlyStorage>(
+    storage: &S,
+    for_address: &CanonicalAddr,
+) -> StdResult<Option<u64>> {
+    let store = ReadonlyPrefixedStorage::multilevel(&[PREFIX_TXS, for_address.as_slice()], storage);
+    let store = AppendStore::<StoredRichTx, _, _>::attach(&store);
+    let store = if let Some(result) = store {
+        result?
+    } else {
+        return Ok(None);
+    };
+
+    let latest = store.iter().next_back().transpose()?;
+    Ok(latest.map(|tx| tx.block_time))
 }
 
 pub fn get_transfers<A: Api, S: ReadonlyStorage>(